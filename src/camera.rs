@@ -2,16 +2,16 @@ use nalgebra::{Matrix4, Rotation3, Unit, Vector3};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Camera {
-    eye_position: Vector3<f32>,
-    target_position: Vector3<f32>,
-    upward_direction: Unit<Vector3<f32>>,
+    pub(crate) eye_position: Vector3<f32>,
+    pub(crate) target_position: Vector3<f32>,
+    pub(crate) upward_direction: Unit<Vector3<f32>>,
 
-    fov_y: f32,
-    aspect_ratio: f32, // Width / height
-    near_clip: f32,
-    far_clip: f32,
+    pub(crate) fov_y: f32,
+    pub(crate) aspect_ratio: f32, // Width / height
+    pub(crate) near_clip: f32,
+    pub(crate) far_clip: f32,
 
-    zoom: f32,
+    pub(crate) zoom: f32,
 }
 
 impl Camera {
@@ -19,7 +19,7 @@ impl Camera {
         debug_assert!(fov_x > 0.0);
         debug_assert!(zoom > 0.0);
 
-        let aspect_ratio = 16.0 / 16.0;
+        let aspect_ratio = 1.0; // Square display
         let fov_y = fov_x * aspect_ratio;
         let near_clip = 0.1;
         let far_clip = 1.0e27;
@@ -0,0 +1,304 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write},
+    path::{Path, PathBuf},
+};
+
+use nalgebra::{Unit, Vector3};
+
+use crate::{
+    snapshot::{read_bytes, read_f32s, write_f32s},
+    Backend, Camera, Integrator, MassAssignment, MeshBoundary, NBody, Settings, Solver,
+};
+
+// Checkpoint layout (all little-endian):
+//   header: b"HVNC", version: u32
+//   settings: 10 f32 shader fields, integrator, solver, tile size, mesh size, mass assignment, backend,
+//             fallback flag, checkpoint interval and mesh boundary (u32 each), mesh box size (f32)
+//   camera: eye, target, up [f32; 3 each], fov y, aspect ratio, near clip, far clip, zoom (f32 each)
+//   steps: u64, time: f64, forces initialised: u8, jerks initialised: u8, num_massive: u64, num_ghost: u64
+//   massive positions, velocities, forces, jerks [[f32; 4]; num_massive each]
+//   ghost positions, velocities, forces, jerks [[f32; 4]; num_ghost each]
+const MAGIC: &[u8; 4] = b"HVNC";
+const VERSION: u32 = 2; // Version 1 had no mesh boundary or box size
+
+// Stable codes of the host-only options (their position in these lists)
+const INTEGRATORS: [Integrator; 5] = [
+    Integrator::Euler,
+    Integrator::Leapfrog,
+    Integrator::VelocityVerlet,
+    Integrator::Hermite,
+    Integrator::Yoshida,
+];
+const SOLVERS: [Solver; 4] = [
+    Solver::Direct,
+    Solver::TiledDirect,
+    Solver::Tree,
+    Solver::ParticleMesh,
+];
+const MASS_ASSIGNMENTS: [MassAssignment; 3] = [
+    MassAssignment::Ngp,
+    MassAssignment::Cic,
+    MassAssignment::Tsc,
+];
+const MESH_BOUNDARIES: [MeshBoundary; 2] = [MeshBoundary::Isolated, MeshBoundary::Periodic];
+const BACKENDS: [Backend; 5] = [
+    Backend::Any,
+    Backend::Vulkan,
+    Backend::Metal,
+    Backend::Dx12,
+    Backend::Gl,
+];
+
+// Everything needed to continue a run exactly where it left off
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub settings: Settings,
+    pub camera: Camera,
+    pub steps: u64,
+    pub time: f64,
+
+    // Whether the saved forces (and jerks) belong to the saved positions
+    pub(crate) forces_initialised: bool,
+    pub(crate) jerks_initialised: bool,
+
+    // Raw particle buffers: positions, velocities, forces and jerks
+    pub(crate) massive_buffers: [Vec<[f32; 4]>; 4],
+    pub(crate) ghost_buffers: [Vec<[f32; 4]>; 4],
+}
+
+impl Checkpoint {
+    pub fn num_massive_particles(&self) -> usize {
+        self.massive_buffers[0].len()
+    }
+
+    pub fn num_ghost_particles(&self) -> usize {
+        self.ghost_buffers[0].len()
+    }
+
+    // Particles to build the simulation from, before the remaining buffers are restored
+    pub fn state(&self) -> NBody {
+        let mut state = NBody::new();
+        for ([px, py, pz, mass], [vx, vy, vz, _]) in
+            self.massive_buffers[0].iter().zip(&self.massive_buffers[1])
+        {
+            state.add_massive_particle([*px, *py, *pz], [*vx, *vy, *vz], *mass);
+        }
+        for ([px, py, pz, kind], [vx, vy, vz, _]) in
+            self.ghost_buffers[0].iter().zip(&self.ghost_buffers[1])
+        {
+            state.add_ghost_particle([*px, *py, *pz], [*vx, *vy, *vz], *kind);
+        }
+
+        state
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        let settings = &self.settings;
+        write_f32s(writer, settings.as_slice())?;
+        for code in [
+            encode(&INTEGRATORS, settings.integrator),
+            encode(&SOLVERS, settings.solver),
+            settings.tile_size,
+            settings.mesh_size,
+            encode(&MASS_ASSIGNMENTS, settings.mass_assignment),
+            encode(&BACKENDS, settings.backend),
+            settings.force_fallback_adapter as u32,
+            settings.checkpoint_interval,
+            encode(&MESH_BOUNDARIES, settings.mesh_boundary),
+        ] {
+            writer.write_all(&code.to_le_bytes())?;
+        }
+        writer.write_all(&settings.mesh_box_size.to_le_bytes())?;
+
+        let camera = &self.camera;
+        write_f32s(
+            writer,
+            camera
+                .eye_position
+                .iter()
+                .chain(camera.target_position.iter())
+                .chain(camera.upward_direction.iter())
+                .chain([
+                    &camera.fov_y,
+                    &camera.aspect_ratio,
+                    &camera.near_clip,
+                    &camera.far_clip,
+                    &camera.zoom,
+                ]),
+        )?;
+
+        writer.write_all(&self.steps.to_le_bytes())?;
+        writer.write_all(&self.time.to_le_bytes())?;
+        writer.write_all(&[self.forces_initialised as u8, self.jerks_initialised as u8])?;
+        writer.write_all(&(self.num_massive_particles() as u64).to_le_bytes())?;
+        writer.write_all(&(self.num_ghost_particles() as u64).to_le_bytes())?;
+
+        for buffer in self.massive_buffers.iter().chain(&self.ghost_buffers) {
+            write_f32s(writer, buffer.iter().flatten())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let magic: [u8; 4] = read_bytes(reader)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a heavens checkpoint".to_string()));
+        }
+        let version = u32::from_le_bytes(read_bytes(reader)?);
+        if !(1..=VERSION).contains(&version) {
+            return Err(invalid_data(format!(
+                "Unsupported checkpoint version {}",
+                version
+            )));
+        }
+
+        let values = read_f32s(reader, 10)?;
+        let mut codes = [0; 9];
+        for code in &mut codes[..if version == 1 { 8 } else { 9 }] {
+            *code = u32::from_le_bytes(read_bytes(reader)?);
+        }
+        let mesh_box_size = match version {
+            1 => 0.0,
+            _ => f32::from_le_bytes(read_bytes(reader)?),
+        };
+        let settings = Settings {
+            display_width: values[0],
+            display_height: values[1],
+            pixel_size: values[2],
+            gravitational_constant: values[3],
+            time_step: values[4],
+            smoothing_length: values[5],
+            ghost_mass: values[6],
+            ghost_stack_visible_limit: values[7],
+            blur_radius: values[8],
+            opening_angle: values[9],
+            integrator: decode(&INTEGRATORS, codes[0], "integrator")?,
+            solver: decode(&SOLVERS, codes[1], "solver")?,
+            tile_size: codes[2],
+            mesh_size: codes[3],
+            mass_assignment: decode(&MASS_ASSIGNMENTS, codes[4], "mass assignment")?,
+            mesh_boundary: decode(&MESH_BOUNDARIES, codes[8], "mesh boundary")?,
+            mesh_box_size,
+            backend: decode(&BACKENDS, codes[5], "backend")?,
+            force_fallback_adapter: codes[6] != 0,
+            checkpoint_interval: codes[7],
+            // A checkpoint is taken after any relaxation, so resuming never relaxes again
+            relaxation_steps: 0,
+            relaxation_damping: 0.0,
+        };
+        if !settings.is_valid() {
+            return Err(invalid_data("Checkpoint settings are invalid".to_string()));
+        }
+
+        let values = read_f32s(reader, 14)?;
+        let camera = Camera {
+            eye_position: Vector3::new(values[0], values[1], values[2]),
+            target_position: Vector3::new(values[3], values[4], values[5]),
+            upward_direction: Unit::new_unchecked(Vector3::new(values[6], values[7], values[8])),
+            fov_y: values[9],
+            aspect_ratio: values[10],
+            near_clip: values[11],
+            far_clip: values[12],
+            zoom: values[13],
+        };
+
+        let steps = u64::from_le_bytes(read_bytes(reader)?);
+        let time = f64::from_le_bytes(read_bytes(reader)?);
+        let [forces_initialised, jerks_initialised] = read_bytes(reader)?.map(|flag: u8| flag != 0);
+        let num_massive = u64::from_le_bytes(read_bytes(reader)?) as usize;
+        let num_ghost = u64::from_le_bytes(read_bytes(reader)?) as usize;
+
+        let mut read_buffer = |count| -> Result<Vec<[f32; 4]>> {
+            (0..count)
+                .map(|_| {
+                    let mut value = [0.0; 4];
+                    for x in &mut value {
+                        *x = f32::from_le_bytes(read_bytes(reader)?);
+                    }
+                    Ok(value)
+                })
+                .collect()
+        };
+        let massive_buffers = [
+            read_buffer(num_massive)?,
+            read_buffer(num_massive)?,
+            read_buffer(num_massive)?,
+            read_buffer(num_massive)?,
+        ];
+        let ghost_buffers = [
+            read_buffer(num_ghost)?,
+            read_buffer(num_ghost)?,
+            read_buffer(num_ghost)?,
+            read_buffer(num_ghost)?,
+        ];
+
+        Ok(Self {
+            settings,
+            camera,
+            steps,
+            time,
+            forces_initialised,
+            jerks_initialised,
+            massive_buffers,
+            ghost_buffers,
+        })
+    }
+}
+
+// Checkpoints are named by their step, so a later one never overwrites an earlier one
+pub fn checkpoint_path(dir: &Path, step: u64) -> PathBuf {
+    dir.join(format!("checkpoint_{:010}.hvc", step))
+}
+
+// The checkpoint of the latest step in `dir`, if there is one
+pub fn latest_checkpoint(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut latest: Option<(u64, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let step = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("checkpoint_")?.strip_suffix(".hvc"))
+            .and_then(|step| step.parse::<u64>().ok());
+        if let Some(step) = step {
+            if latest
+                .as_ref()
+                .map_or(true, |(latest_step, _)| step > *latest_step)
+            {
+                latest = Some((step, path));
+            }
+        }
+    }
+
+    Ok(latest.map(|(_, path)| path))
+}
+
+fn encode<T: PartialEq>(values: &[T], value: T) -> u32 {
+    values.iter().position(|v| *v == value).unwrap() as u32
+}
+
+fn decode<T: Copy>(values: &[T], code: u32, name: &str) -> Result<T> {
+    values
+        .get(code as usize)
+        .copied()
+        .ok_or_else(|| invalid_data(format!("Unknown {} code {}", name, code)))
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
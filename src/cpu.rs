@@ -0,0 +1,429 @@
+use rayon::prelude::*;
+
+use crate::{Integrator, NBody, Settings, Snapshot, Solver};
+
+type Vec3 = [f64; 3];
+
+// Direct-sum simulation on the CPU, stepping the same integrators as the GPU `Simulation`
+// (a fallback without a GPU, and the golden reference for the GPU kernels; any other solver falls back to the direct
+// sum, with a warning)
+pub struct CpuSimulation {
+    settings: Settings,
+    state: NBody,
+    steps: u64,
+    time: f64,
+
+    massive_accelerations: Vec<Vec3>,
+    massive_jerks: Vec<Vec3>,
+    ghost_accelerations: Vec<Vec3>,
+    ghost_jerks: Vec<Vec3>,
+
+    damping: f64, // Rate at which velocities decay while relaxing [1/s] (zero otherwise)
+    damping_frame_velocity: Vec3, // Velocity of the centre of mass, which damping leaves alone
+
+    forces_initialised: bool,
+    jerks_initialised: bool,
+}
+
+impl CpuSimulation {
+    pub fn new(settings: Settings, initial_conditions: NBody) -> Self {
+        debug_assert!(initial_conditions.is_valid());
+        warn_if_not_direct(settings.solver);
+
+        Self {
+            settings,
+            state: initial_conditions,
+            steps: 0,
+            time: 0.0,
+            massive_accelerations: Vec::new(),
+            massive_jerks: Vec::new(),
+            ghost_accelerations: Vec::new(),
+            ghost_jerks: Vec::new(),
+            damping: 0.0,
+            damping_frame_velocity: [0.0; 3],
+            forces_initialised: false,
+            jerks_initialised: false,
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    // The cached forces and jerks may depend on the old settings, so they are recomputed before the next step
+    pub fn set_settings(&mut self, settings: Settings) {
+        warn_if_not_direct(settings.solver);
+        self.settings = settings;
+        self.forces_initialised = false;
+        self.jerks_initialised = false;
+    }
+
+    pub fn state(&self) -> &NBody {
+        &self.state
+    }
+
+    pub fn read_state(&self) -> NBody {
+        self.state.clone()
+    }
+
+    // Damped leapfrog steps, as `Simulation::relax`
+    pub fn relax(&mut self, num_steps: u32, damping: f32) {
+        debug_assert!(damping >= 0.0);
+
+        self.damping = damping as f64;
+        self.damping_frame_velocity = self.state.centre_of_mass_velocity().map(|v| v as f64);
+        for _ in 0..num_steps {
+            self.step_leapfrog(1.0);
+        }
+        self.damping = 0.0;
+        self.damping_frame_velocity = [0.0; 3];
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            step: self.steps,
+            time: self.time,
+            state: self.read_state(),
+        }
+    }
+
+    pub fn update(&mut self) {
+        match self.settings.integrator {
+            Integrator::Euler => self.step_euler(),
+            Integrator::Leapfrog => self.step_leapfrog(1.0),
+            Integrator::VelocityVerlet => self.step_velocity_verlet(),
+            Integrator::Hermite => self.step_hermite(),
+            Integrator::Yoshida => {
+                for weight in Integrator::YOSHIDA_WEIGHTS {
+                    self.step_leapfrog(weight as f64);
+                }
+            }
+        }
+
+        self.steps += 1;
+        self.time += self.settings.time_step as f64;
+    }
+
+    fn step_euler(&mut self) {
+        let dt = self.settings.time_step as f64;
+
+        self.calculate_forces(false);
+        self.kick(dt);
+        self.drift(dt);
+
+        self.forces_initialised = false;
+        self.jerks_initialised = false;
+    }
+
+    fn step_leapfrog(&mut self, weight: f64) {
+        let dt = self.settings.time_step as f64 * weight;
+
+        if !self.forces_initialised {
+            self.calculate_forces(false);
+            self.forces_initialised = true;
+        }
+
+        self.kick(0.5 * dt);
+        self.drift(dt);
+        self.calculate_forces(false);
+        self.kick(0.5 * dt);
+
+        self.jerks_initialised = false;
+    }
+
+    fn step_velocity_verlet(&mut self) {
+        let dt = self.settings.time_step as f64;
+
+        if !self.forces_initialised {
+            self.calculate_forces(false);
+            self.forces_initialised = true;
+        }
+
+        let previous_massive = self.massive_accelerations.clone();
+        let previous_ghost = self.ghost_accelerations.clone();
+
+        // Positions from the current accelerations
+        verlet_drift(
+            &mut self.state.massive_positions,
+            &self.state.massive_velocities,
+            &previous_massive,
+            dt,
+        );
+        verlet_drift(
+            &mut self.state.ghost_positions,
+            &self.state.ghost_velocities,
+            &previous_ghost,
+            dt,
+        );
+        self.calculate_forces(false);
+
+        // Velocities from the average of the old and new accelerations
+        for (previous, current, velocities) in [
+            (
+                &previous_massive,
+                &self.massive_accelerations,
+                &mut self.state.massive_velocities,
+            ),
+            (
+                &previous_ghost,
+                &self.ghost_accelerations,
+                &mut self.state.ghost_velocities,
+            ),
+        ] {
+            velocities
+                .par_iter_mut()
+                .zip(previous.par_iter().zip(current))
+                .for_each(|(v, (a0, a1))| {
+                    for k in 0..3 {
+                        v[k] = (v[k] as f64 + 0.5 * (a0[k] + a1[k]) * dt) as f32;
+                    }
+                });
+        }
+
+        self.jerks_initialised = false;
+    }
+
+    fn step_hermite(&mut self) {
+        let dt = self.settings.time_step as f64;
+
+        if !self.jerks_initialised {
+            self.calculate_forces(true);
+            self.forces_initialised = true;
+            self.jerks_initialised = true;
+        }
+
+        // State at the start of the step for the corrector
+        let massive_start = (
+            self.state.massive_positions.clone(),
+            self.state.massive_velocities.clone(),
+            self.massive_accelerations.clone(),
+            self.massive_jerks.clone(),
+        );
+        let ghost_start = (
+            self.state.ghost_positions.clone(),
+            self.state.ghost_velocities.clone(),
+            self.ghost_accelerations.clone(),
+            self.ghost_jerks.clone(),
+        );
+
+        // Taylor series predictor
+        hermite_predict(
+            &mut self.state.massive_positions,
+            &mut self.state.massive_velocities,
+            &self.massive_accelerations,
+            &self.massive_jerks,
+            dt,
+        );
+        hermite_predict(
+            &mut self.state.ghost_positions,
+            &mut self.state.ghost_velocities,
+            &self.ghost_accelerations,
+            &self.ghost_jerks,
+            dt,
+        );
+        self.calculate_forces(true);
+
+        // Fourth-order Hermite corrector
+        hermite_correct(
+            &mut self.state.massive_positions,
+            &mut self.state.massive_velocities,
+            &massive_start,
+            (&self.massive_accelerations, &self.massive_jerks),
+            dt,
+        );
+        hermite_correct(
+            &mut self.state.ghost_positions,
+            &mut self.state.ghost_velocities,
+            &ghost_start,
+            (&self.ghost_accelerations, &self.ghost_jerks),
+            dt,
+        );
+    }
+
+    // Accelerations (and jerks if requested) of every particle from a softened direct sum over the massive bodies
+    fn calculate_forces(&mut self, with_jerks: bool) {
+        let state = &self.state;
+        let settings = &self.settings;
+
+        let massive: Vec<(Vec3, Vec3)> = (0..state.massive_positions.len())
+            .into_par_iter()
+            .map(|n| {
+                pull(
+                    state,
+                    settings,
+                    state.massive_positions[n],
+                    state.massive_velocities[n],
+                    Some(n),
+                    with_jerks,
+                )
+            })
+            .collect();
+        let ghost: Vec<(Vec3, Vec3)> = state
+            .ghost_positions
+            .par_iter()
+            .zip(&state.ghost_velocities)
+            .map(|(p, v)| pull(state, settings, *p, *v, None, with_jerks))
+            .collect();
+
+        (self.massive_accelerations, self.massive_jerks) = massive.into_iter().unzip();
+        (self.ghost_accelerations, self.ghost_jerks) = ghost.into_iter().unzip();
+    }
+
+    fn kick(&mut self, dt: f64) {
+        let (retained, frame) = ((-self.damping * dt).exp(), self.damping_frame_velocity);
+        for (velocities, accelerations) in [
+            (
+                &mut self.state.massive_velocities,
+                &self.massive_accelerations,
+            ),
+            (&mut self.state.ghost_velocities, &self.ghost_accelerations),
+        ] {
+            velocities
+                .par_iter_mut()
+                .zip(accelerations)
+                .for_each(|(v, a)| {
+                    for k in 0..3 {
+                        v[k] = (frame[k] + retained * (v[k] as f64 - frame[k]) + a[k] * dt) as f32;
+                    }
+                });
+        }
+    }
+
+    fn drift(&mut self, dt: f64) {
+        for (positions, velocities) in [
+            (
+                &mut self.state.massive_positions,
+                &self.state.massive_velocities,
+            ),
+            (
+                &mut self.state.ghost_positions,
+                &self.state.ghost_velocities,
+            ),
+        ] {
+            positions.par_iter_mut().zip(velocities).for_each(|(p, v)| {
+                for k in 0..3 {
+                    p[k] = (p[k] as f64 + v[k] as f64 * dt) as f32;
+                }
+            });
+        }
+    }
+}
+
+fn warn_if_not_direct(solver: Solver) {
+    if !matches!(solver, Solver::Direct | Solver::TiledDirect) {
+        log::warn!("The CPU has no {:?} solver, using the direct sum", solver);
+    }
+}
+
+// Acceleration and jerk at a point moving with `velocity`, skipping the massive body `skip`
+fn pull(
+    state: &NBody,
+    settings: &Settings,
+    position: [f32; 3],
+    velocity: [f32; 3],
+    skip: Option<usize>,
+    with_jerk: bool,
+) -> (Vec3, Vec3) {
+    let softening2 = (settings.smoothing_length as f64).powi(2);
+    let grav_const = settings.gravitational_constant as f64;
+
+    let mut acceleration = [0.0; 3];
+    let mut jerk = [0.0; 3];
+    for (i, ((p1, v1), m1)) in state
+        .massive_positions
+        .iter()
+        .zip(&state.massive_velocities)
+        .zip(&state.massive_masses)
+        .enumerate()
+    {
+        if Some(i) == skip {
+            continue;
+        }
+
+        let dp: Vec3 = [0, 1, 2].map(|k| p1[k] as f64 - position[k] as f64);
+        let r2 = dp.iter().map(|x| x * x).sum::<f64>() + softening2;
+        let inv_r = 1.0 / r2.sqrt();
+        let gm_inv_r3 = grav_const * *m1 as f64 * inv_r * inv_r * inv_r;
+
+        for k in 0..3 {
+            acceleration[k] += gm_inv_r3 * dp[k];
+        }
+        if with_jerk {
+            let dv: Vec3 = [0, 1, 2].map(|k| v1[k] as f64 - velocity[k] as f64);
+            let rv = (0..3).map(|k| dp[k] * dv[k]).sum::<f64>() * inv_r * inv_r;
+            for k in 0..3 {
+                jerk[k] += gm_inv_r3 * (dv[k] - 3.0 * rv * dp[k]);
+            }
+        }
+    }
+
+    (acceleration, jerk)
+}
+
+fn verlet_drift(
+    positions: &mut [[f32; 3]],
+    velocities: &[[f32; 3]],
+    accelerations: &[Vec3],
+    dt: f64,
+) {
+    positions
+        .par_iter_mut()
+        .zip(velocities.par_iter().zip(accelerations))
+        .for_each(|(p, (v, a))| {
+            for k in 0..3 {
+                p[k] = (p[k] as f64 + (v[k] as f64 + a[k] * dt / 2.0) * dt) as f32;
+            }
+        });
+}
+
+fn hermite_predict(
+    positions: &mut [[f32; 3]],
+    velocities: &mut [[f32; 3]],
+    accelerations: &[Vec3],
+    jerks: &[Vec3],
+    dt: f64,
+) {
+    positions
+        .par_iter_mut()
+        .zip(velocities.par_iter_mut())
+        .zip(accelerations.par_iter().zip(jerks))
+        .for_each(|((p, v), (a, j))| {
+            for k in 0..3 {
+                let v0 = v[k] as f64;
+                p[k] = (p[k] as f64 + (v0 + (a[k] / 2.0 + j[k] * dt / 6.0) * dt) * dt) as f32;
+                v[k] = (v0 + (a[k] + j[k] * dt / 2.0) * dt) as f32;
+            }
+        });
+}
+
+type HermiteStart = (Vec<[f32; 3]>, Vec<[f32; 3]>, Vec<Vec3>, Vec<Vec3>);
+
+fn hermite_correct(
+    positions: &mut [[f32; 3]],
+    velocities: &mut [[f32; 3]],
+    (p0s, v0s, a0s, j0s): &HermiteStart,
+    (a1s, j1s): (&[Vec3], &[Vec3]),
+    dt: f64,
+) {
+    positions
+        .par_iter_mut()
+        .zip(velocities.par_iter_mut())
+        .enumerate()
+        .for_each(|(n, (p, v))| {
+            for k in 0..3 {
+                let (a0, j0, a1, j1) = (a0s[n][k], j0s[n][k], a1s[n][k], j1s[n][k]);
+                let v0 = v0s[n][k] as f64;
+                let v1 = v0 + ((a0 + a1) / 2.0 + (j0 - j1) * dt / 12.0) * dt;
+                p[k] = (p0s[n][k] as f64 + ((v0 + v1) / 2.0 + (a0 - a1) * dt / 12.0) * dt) as f32;
+                v[k] = v1 as f32;
+            }
+        });
+}
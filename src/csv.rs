@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Result, Write},
+    path::Path,
+};
+
+use crate::NBody;
+
+// One particle per row after a header naming the columns (found by name when reading, so extra
+// columns such as a pandas index are skipped):
+//   x,y,z,vx,vy,vz,m,kind
+// Massive particles come first with a kind of 0, followed by the ghosts with a mass of 0
+pub(crate) const COLUMNS: [&str; 8] = ["x", "y", "z", "vx", "vy", "vz", "m", "kind"];
+
+impl NBody {
+    pub fn load_csv(path: &Path) -> Result<Self> {
+        Self::read_csv(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn read_csv(reader: &mut impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| invalid_data("CSV file has no header".to_string()))??;
+        let names: Vec<&str> = split(header.trim_start_matches('\u{feff}')).collect();
+        let mut columns = [0; 8];
+        for (column, name) in columns.iter_mut().zip(COLUMNS) {
+            *column = names
+                .iter()
+                .position(|n| *n == name)
+                .ok_or_else(|| invalid_data(format!("CSV file has no {} column", name)))?;
+        }
+
+        let mut rows = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Lines are numbered from 1, counting the header
+            let line_number = index + 2;
+            let fields: Vec<&str> = split(&line).collect();
+            if fields.len() != names.len() {
+                return Err(invalid_data(format!(
+                    "line {}: expected {} fields but found {}",
+                    line_number,
+                    names.len(),
+                    fields.len()
+                )));
+            }
+
+            let mut row = [0.0; 8];
+            for (value, column) in row.iter_mut().zip(columns) {
+                *value = fields[column].parse().map_err(|_| {
+                    invalid_data(format!(
+                        "line {}: invalid number '{}'",
+                        line_number, fields[column]
+                    ))
+                })?;
+            }
+            rows.push(row);
+        }
+
+        Self::from_rows(rows)
+    }
+
+    pub fn write_csv(&self, writer: &mut impl Write) -> Result<()> {
+        writeln!(writer, "{}", COLUMNS.join(","))?;
+        for row in self.rows() {
+            let fields: Vec<String> = row.iter().map(f32::to_string).collect();
+            writeln!(writer, "{}", fields.join(","))?;
+        }
+        Ok(())
+    }
+
+    // Rows of the CSV columns, massive particles first
+    pub(crate) fn rows(&self) -> impl Iterator<Item = [f32; 8]> + '_ {
+        let massive = (0..self.num_massive_particles()).map(|n| {
+            let ([x, y, z], [vx, vy, vz]) = (self.massive_positions[n], self.massive_velocities[n]);
+            [x, y, z, vx, vy, vz, self.massive_masses[n], 0.0]
+        });
+        let ghost = (0..self.num_ghost_particles()).map(|n| {
+            let ([x, y, z], [vx, vy, vz]) = (self.ghost_positions[n], self.ghost_velocities[n]);
+            [x, y, z, vx, vy, vz, 0.0, self.ghost_kinds[n]]
+        });
+        massive.chain(ghost)
+    }
+
+    // Particles with a mass are massive and massless ones are ghosts
+    pub(crate) fn from_rows(rows: impl IntoIterator<Item = [f32; 8]>) -> Result<Self> {
+        let mut nbody = NBody::new();
+        for [x, y, z, vx, vy, vz, mass, kind] in rows {
+            if mass == 0.0 {
+                nbody.add_ghost_particle([x, y, z], [vx, vy, vz], kind);
+            } else if mass > 0.0 {
+                nbody.add_massive_particle([x, y, z], [vx, vy, vz], mass);
+            } else {
+                return Err(invalid_data(format!("Invalid particle mass {}", mass)));
+            }
+        }
+        Ok(nbody)
+    }
+}
+
+// Fields of a line, without surrounding whitespace or quotes
+fn split(line: &str) -> impl Iterator<Item = &str> {
+    line.split(',').map(|field| field.trim().trim_matches('"'))
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,145 @@
+use rand::Rng;
+
+use crate::{GalaxyBuilder, NBody};
+
+// Two galaxies on a Keplerian orbit about their common centre of mass, which sits at the origin at rest:
+//   pericentre: closest approach of the two centres
+//   eccentricity: below 1 for a bound orbit, 1 for a parabolic and above 1 for a hyperbolic one
+//   separation: distance between the centres at the start, on the way in to pericentre
+// The orbit lies in the xy plane with its angular momentum along +z, and at pericentre the second galaxy sits on
+// the +x side of the first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encounter {
+    pub pericentre: f32,
+    pub eccentricity: f32,
+    pub separation: f32,
+    pub first: DiscOrientation,
+    pub second: DiscOrientation,
+}
+
+// Spin of a disc relative to the orbit, after Toomre & Toomre (1972): the disc is tilted by `inclination` from the
+// orbital plane about its line of nodes, which lies in that plane `argument_of_pericentre` before the pericentre
+// direction (angles in radians). An inclination of 0 spins with the orbit and one of pi against it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiscOrientation {
+    pub inclination: f32,
+    pub argument_of_pericentre: f32,
+}
+
+impl DiscOrientation {
+    pub const PROGRADE: Self = Self {
+        inclination: 0.0,
+        argument_of_pericentre: 0.0,
+    };
+    pub const RETROGRADE: Self = Self {
+        inclination: std::f32::consts::PI,
+        argument_of_pericentre: 0.0,
+    };
+
+    // The direction of the disc's angular momentum, turned from +z about the line of nodes
+    pub fn spin_axis(&self) -> [f32; 3] {
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (sin_w, cos_w) = self.argument_of_pericentre.sin_cos();
+        [-sin_w * sin_i, -cos_w * sin_i, cos_i]
+    }
+
+    fn line_of_nodes(&self) -> [f32; 3] {
+        let (sin_w, cos_w) = self.argument_of_pericentre.sin_cos();
+        [cos_w, -sin_w, 0.0]
+    }
+}
+
+impl Encounter {
+    // Positions and velocities of two bodies of the given masses at the start of the orbit
+    pub fn states(
+        &self,
+        grav_const: f32,
+        first_mass: f32,
+        second_mass: f32,
+    ) -> [([f32; 3], [f32; 3]); 2] {
+        let (q, e, d) = (
+            self.pericentre as f64,
+            self.eccentricity as f64,
+            self.separation as f64,
+        );
+        debug_assert!(q > 0.0 && e >= 0.0);
+        debug_assert!(d >= q * (1.0 - 1.0e-6));
+        debug_assert!(e >= 1.0 || d <= q * (1.0 + e) / (1.0 - e) * (1.0 + 1.0e-6));
+
+        let total_mass = first_mass as f64 + second_mass as f64;
+        let mu = grav_const as f64 * total_mass;
+
+        // The conic r = p / (1 + e cos(nu)), entered at negative true anomaly on the way in
+        let semi_latus_rectum = q * (1.0 + e);
+        let true_anomaly = if e > 0.0 {
+            -((semi_latus_rectum / d - 1.0) / e).clamp(-1.0, 1.0).acos()
+        } else {
+            0.0
+        };
+        let (sin, cos) = true_anomaly.sin_cos();
+        let speed = (mu / semi_latus_rectum).sqrt();
+        let position = [d * cos, d * sin, 0.0];
+        let velocity = [-speed * sin, speed * (e + cos), 0.0];
+
+        // Each galaxy on the far side of the centre of mass from the other
+        let share = |fraction: f64, vector: [f64; 3]| vector.map(|x| (fraction * x) as f32);
+        let (first_share, second_share) = (
+            -(second_mass as f64) / total_mass,
+            first_mass as f64 / total_mass,
+        );
+        [
+            (share(first_share, position), share(first_share, velocity)),
+            (share(second_share, position), share(second_share, velocity)),
+        ]
+    }
+
+    // The two galaxies placed on the orbit with their discs turned to their orientations (their own centres,
+    // velocities and spin axes are replaced), the first galaxy's particles first
+    pub fn add_to(
+        &self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        first: &GalaxyBuilder,
+        second: &GalaxyBuilder,
+        nbody: &mut NBody,
+    ) {
+        let states = self.states(grav_const, first.total_mass(), second.total_mass());
+        for ((galaxy, orientation), (centre, velocity)) in
+            [(first, self.first), (second, self.second)]
+                .into_iter()
+                .zip(states)
+        {
+            let mut galaxy = galaxy.clone().spin_axis(orientation.spin_axis());
+            galaxy.centre = centre;
+            galaxy.velocity = velocity;
+            galaxy.add_to(rng, grav_const, nbody);
+        }
+    }
+
+    // Two pre-built systems (from any generator or snapshot) placed on the orbit by their centres of mass and bulk
+    // velocities, each turned about its centre of mass as a disc built like the generators' (turning clockwise seen
+    // from +z) would be to its orientation, the first system's particles first
+    pub fn add_systems_to(
+        &self,
+        grav_const: f32,
+        first: &NBody,
+        second: &NBody,
+        nbody: &mut NBody,
+    ) {
+        let states = self.states(grav_const, first.total_mass(), second.total_mass());
+        for ((system, orientation), (centre, velocity)) in
+            [(first, self.first), (second, self.second)]
+                .into_iter()
+                .zip(states)
+        {
+            let mut system = system.clone();
+            system.to_centre_of_mass_frame();
+            // Spinning about +z, then tilted about the line of nodes
+            system.rotate([1.0, 0.0, 0.0], std::f32::consts::PI);
+            system.rotate(orientation.line_of_nodes(), orientation.inclination);
+            system.translate(centre);
+            system.boost(velocity);
+            nbody.merge(&system);
+        }
+    }
+}
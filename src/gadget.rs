@@ -0,0 +1,410 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+use crate::{snapshot::write_f32s, NBody, Snapshot};
+
+// GADGET-2 snapshots are Fortran-style records, each wrapped in its byte length (u32 before and after):
+//   format 1: header, POS, VEL, ID and MASS blocks in that order
+//   format 2: the same blocks, each preceded by an 8-byte record of its 4-character label and size
+// The 256-byte header starts with:
+//   npart [u32; 6], massarr [f64; 6], time: f64, redshift: f64, flag_sfr: i32, flag_feedback: i32,
+//   npartTotal [u32; 6], flag_cooling: i32, num_files: i32, ...
+// Particles are ordered by type, and the MASS block only holds the types whose massarr entry is zero.
+// Files may be either endianness and POS/VEL may be single or double precision; they are written
+// little-endian in single precision.
+const HEADER_SIZE: usize = 256;
+const NUM_TYPES: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GadgetFormat {
+    Format1,
+    Format2,
+}
+
+// What a GADGET particle type becomes in an `NBody`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GadgetRole {
+    Massive,
+    Ghost(f32), // Ghosts of this kind (masses are discarded)
+    Ignored,
+}
+
+// Roles of types 0 (gas), 1 (halo), 2 (disc), 3 (bulge), 4 (stars) and 5 (boundary)
+// (when writing, massive particles and ghosts go to the first type with their role)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GadgetMapping(pub [GadgetRole; NUM_TYPES]);
+
+impl Default for GadgetMapping {
+    // Everything is massive except boundary particles, which carry the ghosts
+    fn default() -> Self {
+        Self([
+            GadgetRole::Massive,
+            GadgetRole::Massive,
+            GadgetRole::Massive,
+            GadgetRole::Massive,
+            GadgetRole::Massive,
+            GadgetRole::Ghost(0.0),
+        ])
+    }
+}
+
+impl GadgetMapping {
+    fn first_massive(&self) -> Option<usize> {
+        self.0.iter().position(|role| *role == GadgetRole::Massive)
+    }
+
+    fn first_ghost(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|role| matches!(role, GadgetRole::Ghost(_)))
+    }
+}
+
+impl Snapshot {
+    pub fn load_gadget(path: &Path, mapping: &GadgetMapping) -> Result<Self> {
+        Self::read_gadget(&mut File::open(path)?, mapping)
+    }
+
+    pub fn save_gadget(
+        &self,
+        path: &Path,
+        mapping: &GadgetMapping,
+        format: GadgetFormat,
+    ) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_gadget(&mut writer, mapping, format)?;
+        writer.flush()
+    }
+
+    // The step count is not part of the format, so it reads as zero
+    pub fn read_gadget(reader: &mut impl Read, mapping: &GadgetMapping) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < 4 {
+            return Err(invalid_data("Not a GADGET snapshot"));
+        }
+
+        // The first record is either the header (format 1) or its label (format 2)
+        let first_marker: [u8; 4] = bytes[..4].try_into().unwrap();
+        let (big_endian, format) = match (
+            u32::from_le_bytes(first_marker),
+            u32::from_be_bytes(first_marker),
+        ) {
+            (256, _) => (false, GadgetFormat::Format1),
+            (8, _) => (false, GadgetFormat::Format2),
+            (_, 256) => (true, GadgetFormat::Format1),
+            (_, 8) => (true, GadgetFormat::Format2),
+            _ => return Err(invalid_data("Not a GADGET snapshot")),
+        };
+        let mut records = Records {
+            bytes: &bytes,
+            big_endian,
+            format,
+        };
+
+        let (label, header) = records
+            .next()?
+            .ok_or_else(|| invalid_data("GADGET snapshot has no header"))?;
+        if label.is_some_and(|label| &label != b"HEAD") || header.len() != HEADER_SIZE {
+            return Err(invalid_data("GADGET snapshot has no header"));
+        }
+        let npart: [usize; NUM_TYPES] =
+            std::array::from_fn(|t| decode_u32(&header[4 * t..], big_endian) as usize);
+        let massarr: [f64; NUM_TYPES] =
+            std::array::from_fn(|t| decode_f64(&header[24 + 8 * t..], big_endian));
+        let time = decode_f64(&header[72..], big_endian);
+        let num_files = decode_u32(&header[124..], big_endian);
+        if num_files > 1 {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Multi-file GADGET snapshots are not supported",
+            ));
+        }
+
+        let num_particles: usize = npart.iter().sum();
+        let num_masses: usize = (0..NUM_TYPES)
+            .filter(|&t| massarr[t] == 0.0)
+            .map(|t| npart[t])
+            .sum();
+
+        // Format 1 blocks are positional, and the one after ID is only MASS if some masses are missing
+        let mut positions = None;
+        let mut velocities = None;
+        let mut masses = None;
+        let mut index = 0;
+        while let Some((label, block)) = records.next()? {
+            let label = match (label, index) {
+                (Some(label), _) => label,
+                (None, 0) => *b"POS ",
+                (None, 1) => *b"VEL ",
+                (None, 2) => *b"ID  ",
+                (None, 3) if num_masses > 0 => *b"MASS",
+                (None, _) => break,
+            };
+            match &label {
+                b"POS " => positions = Some(decode_reals(block, 3 * num_particles, big_endian)?),
+                b"VEL " => velocities = Some(decode_reals(block, 3 * num_particles, big_endian)?),
+                b"MASS" => masses = Some(decode_reals(block, num_masses, big_endian)?),
+                _ => {}
+            }
+            index += 1;
+        }
+
+        let missing = |name| invalid_data(&format!("GADGET snapshot has no {} block", name));
+        let positions = positions.ok_or_else(|| missing("POS"))?;
+        let velocities = velocities.ok_or_else(|| missing("VEL"))?;
+        let masses = match num_masses {
+            0 => Vec::new(),
+            _ => masses.ok_or_else(|| missing("MASS"))?,
+        };
+
+        let mut state = NBody::new();
+        let mut particle = 0;
+        let mut masses = masses.into_iter();
+        for t in 0..NUM_TYPES {
+            for _ in 0..npart[t] {
+                let position = std::array::from_fn(|k| positions[3 * particle + k]);
+                let velocity = std::array::from_fn(|k| velocities[3 * particle + k]);
+                let mass = if massarr[t] == 0.0 {
+                    masses.next().unwrap()
+                } else {
+                    massarr[t] as f32
+                };
+                particle += 1;
+
+                match mapping.0[t] {
+                    GadgetRole::Massive => {
+                        if mass.is_nan() || mass <= 0.0 {
+                            return Err(invalid_data(
+                                "GADGET snapshot contains a non-positive mass",
+                            ));
+                        }
+                        state.add_massive_particle(position, velocity, mass);
+                    }
+                    GadgetRole::Ghost(kind) => state.add_ghost_particle(position, velocity, kind),
+                    GadgetRole::Ignored => {}
+                }
+            }
+        }
+
+        Ok(Self {
+            step: 0,
+            time,
+            state,
+        })
+    }
+
+    pub fn write_gadget(
+        &self,
+        writer: &mut impl Write,
+        mapping: &GadgetMapping,
+        format: GadgetFormat,
+    ) -> Result<()> {
+        let state = &self.state;
+        let num_massive = state.num_massive_particles();
+        let num_ghost = state.num_ghost_particles();
+        let unmapped = |role| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("No GADGET particle type is mapped to {}", role),
+            )
+        };
+        let massive_type = match num_massive {
+            0 => None,
+            _ => Some(mapping.first_massive().ok_or_else(|| unmapped("massive"))?),
+        };
+        let ghost_type = match num_ghost {
+            0 => None,
+            _ => Some(mapping.first_ghost().ok_or_else(|| unmapped("ghosts"))?),
+        };
+
+        let mut npart = [0u32; NUM_TYPES];
+        if let Some(t) = massive_type {
+            npart[t] = num_massive as u32;
+        }
+        if let Some(t) = ghost_type {
+            npart[t] = num_ghost as u32;
+        }
+        let num_particles = num_massive + num_ghost;
+
+        // Every mass goes in the MASS block (ghosts weigh nothing), so massarr stays zero
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend(npart.iter().flat_map(|n| n.to_le_bytes()));
+        header.extend([0; 8 * NUM_TYPES]);
+        header.extend(self.time.to_le_bytes());
+        header.extend([0; 16]); // Redshift, star formation and feedback flags
+        header.extend(npart.iter().flat_map(|n| n.to_le_bytes()));
+        header.extend([0; 4]); // Cooling flag
+        header.extend(1u32.to_le_bytes()); // Number of files
+        header.resize(HEADER_SIZE, 0);
+
+        // Particles in type order
+        let massive_first = massive_type < ghost_type || ghost_type.is_none();
+        let ghost_masses = vec![0.0; num_ghost];
+        let (first, second) = if massive_first {
+            (
+                (
+                    state.massive_positions(),
+                    state.massive_velocities(),
+                    state.massive_masses(),
+                ),
+                (
+                    state.ghost_positions(),
+                    state.ghost_velocities(),
+                    &ghost_masses[..],
+                ),
+            )
+        } else {
+            (
+                (
+                    state.ghost_positions(),
+                    state.ghost_velocities(),
+                    &ghost_masses[..],
+                ),
+                (
+                    state.massive_positions(),
+                    state.massive_velocities(),
+                    state.massive_masses(),
+                ),
+            )
+        };
+
+        write_block(writer, format, b"HEAD", HEADER_SIZE, |writer| {
+            writer.write_all(&header)
+        })?;
+        write_block(writer, format, b"POS ", 12 * num_particles, |writer| {
+            write_f32s(writer, first.0.iter().chain(second.0).flatten())
+        })?;
+        write_block(writer, format, b"VEL ", 12 * num_particles, |writer| {
+            write_f32s(writer, first.1.iter().chain(second.1).flatten())
+        })?;
+        write_block(writer, format, b"ID  ", 4 * num_particles, |writer| {
+            (1..=num_particles as u32).try_for_each(|id| writer.write_all(&id.to_le_bytes()))
+        })?;
+        if num_particles > 0 {
+            write_block(writer, format, b"MASS", 4 * num_particles, |writer| {
+                write_f32s(writer, first.2.iter().chain(second.2))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+// A block and, in format 2, its label
+type Block<'a> = (Option<[u8; 4]>, &'a [u8]);
+
+// Walks the records of a snapshot
+struct Records<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+    format: GadgetFormat,
+}
+
+impl<'a> Records<'a> {
+    fn next(&mut self) -> Result<Option<Block<'a>>> {
+        match self.format {
+            GadgetFormat::Format1 => Ok(self.record()?.map(|block| (None, block))),
+            GadgetFormat::Format2 => {
+                let Some(label) = self.record()? else {
+                    return Ok(None);
+                };
+                if label.len() != 8 {
+                    return Err(invalid_data("Malformed GADGET block label"));
+                }
+                let block = self
+                    .record()?
+                    .ok_or_else(|| invalid_data("GADGET block label without a block"))?;
+                Ok(Some((Some(label[..4].try_into().unwrap()), block)))
+            }
+        }
+    }
+
+    fn record(&mut self) -> Result<Option<&'a [u8]>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let truncated = || Error::new(ErrorKind::UnexpectedEof, "Truncated GADGET snapshot");
+
+        if self.bytes.len() < 4 {
+            return Err(truncated());
+        }
+        let size = decode_u32(self.bytes, self.big_endian) as usize;
+        if self.bytes.len() < size + 8 {
+            return Err(truncated());
+        }
+        if decode_u32(&self.bytes[size + 4..], self.big_endian) as usize != size {
+            return Err(invalid_data("Mismatched GADGET record markers"));
+        }
+
+        let record = &self.bytes[4..size + 4];
+        self.bytes = &self.bytes[size + 8..];
+        Ok(Some(record))
+    }
+}
+
+fn write_block<W: Write>(
+    writer: &mut W,
+    format: GadgetFormat,
+    label: &[u8; 4],
+    size: usize,
+    write: impl FnOnce(&mut W) -> Result<()>,
+) -> Result<()> {
+    let too_large = || Error::new(ErrorKind::InvalidInput, "GADGET block over 4 GiB");
+    let size = u32::try_from(size).map_err(|_| too_large())?;
+    let marker = size.to_le_bytes();
+
+    if format == GadgetFormat::Format2 {
+        // The label block gives the size of the next block with its markers
+        let next_size = size.checked_add(8).ok_or_else(too_large)?;
+        writer.write_all(&8u32.to_le_bytes())?;
+        writer.write_all(label)?;
+        writer.write_all(&next_size.to_le_bytes())?;
+        writer.write_all(&8u32.to_le_bytes())?;
+    }
+    writer.write_all(&marker)?;
+    write(writer)?;
+    writer.write_all(&marker)
+}
+
+fn decode_u32(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = bytes[..4].try_into().unwrap();
+    match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    }
+}
+
+fn decode_f64(bytes: &[u8], big_endian: bool) -> f64 {
+    let bytes = bytes[..8].try_into().unwrap();
+    match big_endian {
+        true => f64::from_be_bytes(bytes),
+        false => f64::from_le_bytes(bytes),
+    }
+}
+
+// `count` single or double precision values, told apart by the size of the block
+fn decode_reals(block: &[u8], count: usize, big_endian: bool) -> Result<Vec<f32>> {
+    if block.len() == 4 * count {
+        Ok(block
+            .chunks_exact(4)
+            .map(|bytes| f32::from_bits(decode_u32(bytes, big_endian)))
+            .collect())
+    } else if block.len() == 8 * count {
+        Ok(block
+            .chunks_exact(8)
+            .map(|bytes| decode_f64(bytes, big_endian) as f32)
+            .collect())
+    } else {
+        Err(invalid_data(
+            "GADGET block size does not match the particle counts",
+        ))
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
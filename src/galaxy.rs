@@ -0,0 +1,342 @@
+use rand::Rng;
+
+use crate::{
+    nbody::{sample_exponential_disc, sample_spherical_model, Samples},
+    spherical::SphericalModel,
+    NBody, Orientation, Spin,
+};
+
+// Disc galaxy of up to three components, each sampled in equilibrium in the combined (spherically averaged)
+// potential of all of them:
+//   bulge: a Hernquist sphere
+//   disc: an exponential disc of a given Toomre Q, optionally traced by ghosts for rendering
+//   halo: a Hernquist sphere or a truncated NFW halo
+// The galaxy is built about the origin, turned to its orientation (by default `Orientation::default()`, turning
+// clockwise seen from +z like the other generators), and moved to `centre` with `velocity`. Particles are added bulge first, then the disc and the halo.
+#[derive(Clone, Debug)]
+pub struct GalaxyBuilder {
+    pub(crate) centre: [f32; 3],
+    pub(crate) velocity: [f32; 3],
+    orientation: Orientation,
+    bulge: Option<Bulge>,
+    disc: Option<Disc>,
+    halo: Option<Halo>,
+    ghosts: Option<Ghosts>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bulge {
+    scale_radius: f32,
+    mass: f32,
+    num_particles: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Disc {
+    scale_length: f32,
+    scale_height: f32,
+    mass: f32,
+    toomre_q: f32,
+    num_particles: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Halo {
+    Hernquist {
+        scale_radius: f32,
+        mass: f32,
+        num_particles: usize,
+    },
+    Nfw {
+        scale_radius: f32,
+        concentration: f32,
+        mass: f32,
+        num_particles: usize,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Ghosts {
+    num_particles: usize,
+    kind: f32,
+}
+
+// A tabulated spherical component scaled to its radius and mass
+struct Sphere {
+    model: SphericalModel,
+    scale_radius: f32,
+    mass: f32,
+    num_particles: usize,
+}
+
+impl Sphere {
+    fn enclosed_mass(&self, r: f64) -> f64 {
+        self.mass as f64 * self.model.enclosed_mass(r / self.scale_radius as f64)
+    }
+
+    // In equilibrium with the mass of the other components, `external_mass(r)` in physical units
+    fn set_external_mass(&mut self, external_mass: impl Fn(f64) -> f64) {
+        let (a, m) = (self.scale_radius as f64, self.mass as f64);
+        self.model.set_external_mass(|x| external_mass(x * a) / m);
+    }
+
+    fn sample(&self, rng: &mut impl Rng, grav_const: f32) -> Samples {
+        sample_spherical_model(
+            rng,
+            &self.model,
+            grav_const,
+            self.scale_radius,
+            self.mass,
+            self.num_particles,
+        )
+    }
+}
+
+impl GalaxyBuilder {
+    // An empty galaxy in the xy plane
+    pub fn new(centre: [f32; 3], velocity: [f32; 3]) -> Self {
+        Self {
+            centre,
+            velocity,
+            orientation: Orientation::default(),
+            bulge: None,
+            disc: None,
+            halo: None,
+            ghosts: None,
+        }
+    }
+
+    // Direction of the disc's angular momentum (need not be normalised)
+    pub fn spin_axis(self, spin_axis: [f32; 3]) -> Self {
+        self.orientation(Orientation::from_spin_axis(spin_axis, Spin::Anticlockwise))
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn bulge(mut self, scale_radius: f32, mass: f32, num_particles: usize) -> Self {
+        debug_assert!(scale_radius > 0.0);
+        debug_assert!(mass > 0.0);
+        debug_assert!(num_particles > 0);
+
+        self.bulge = Some(Bulge {
+            scale_radius,
+            mass,
+            num_particles,
+        });
+        self
+    }
+
+    pub fn disc(
+        mut self,
+        scale_length: f32,
+        scale_height: f32,
+        mass: f32,
+        toomre_q: f32,
+        num_particles: usize,
+    ) -> Self {
+        debug_assert!(scale_length > 0.0);
+        debug_assert!(scale_height > 0.0);
+        debug_assert!(mass > 0.0);
+        debug_assert!(toomre_q > 0.0);
+        debug_assert!(num_particles > 0);
+
+        self.disc = Some(Disc {
+            scale_length,
+            scale_height,
+            mass,
+            toomre_q,
+            num_particles,
+        });
+        self
+    }
+
+    pub fn hernquist_halo(mut self, scale_radius: f32, mass: f32, num_particles: usize) -> Self {
+        debug_assert!(scale_radius > 0.0);
+        debug_assert!(mass > 0.0);
+        debug_assert!(num_particles > 0);
+
+        self.halo = Some(Halo::Hernquist {
+            scale_radius,
+            mass,
+            num_particles,
+        });
+        self
+    }
+
+    pub fn nfw_halo(
+        mut self,
+        scale_radius: f32,
+        concentration: f32,
+        mass: f32,
+        num_particles: usize,
+    ) -> Self {
+        debug_assert!(scale_radius > 0.0);
+        debug_assert!(concentration > 0.0);
+        debug_assert!(mass > 0.0);
+        debug_assert!(num_particles > 0);
+
+        self.halo = Some(Halo::Nfw {
+            scale_radius,
+            concentration,
+            mass,
+            num_particles,
+        });
+        self
+    }
+
+    // Ghosts of `kind` drawn from the same distribution as the disc (`add_to` panics without a disc)
+    pub fn disc_ghosts(mut self, num_particles: usize, kind: f32) -> Self {
+        debug_assert!(num_particles > 0);
+
+        self.ghosts = Some(Ghosts {
+            num_particles,
+            kind,
+        });
+        self
+    }
+
+    pub fn total_mass(&self) -> f32 {
+        let halo_mass = match self.halo {
+            Some(Halo::Hernquist { mass, .. } | Halo::Nfw { mass, .. }) => mass,
+            None => 0.0,
+        };
+        self.bulge.map_or(0.0, |bulge| bulge.mass)
+            + self.disc.map_or(0.0, |disc| disc.mass)
+            + halo_mass
+    }
+
+    pub fn add_to(&self, rng: &mut impl Rng, grav_const: f32, nbody: &mut NBody) {
+        assert!(
+            self.ghosts.is_none() || self.disc.is_some(),
+            "disc ghosts need a disc to be drawn from"
+        );
+
+        let mut bulge = self.bulge.map(|bulge| Sphere {
+            model: SphericalModel::hernquist(),
+            scale_radius: bulge.scale_radius,
+            mass: bulge.mass,
+            num_particles: bulge.num_particles,
+        });
+        let mut halo = self.halo.map(|halo| match halo {
+            Halo::Hernquist {
+                scale_radius,
+                mass,
+                num_particles,
+            } => Sphere {
+                model: SphericalModel::hernquist(),
+                scale_radius,
+                mass,
+                num_particles,
+            },
+            Halo::Nfw {
+                scale_radius,
+                concentration,
+                mass,
+                num_particles,
+            } => Sphere {
+                model: SphericalModel::nfw(concentration as f64),
+                scale_radius,
+                mass,
+                num_particles,
+            },
+        });
+
+        // Mass profiles within spherical radius r, the disc's approximated by its mass within cylindrical radius r
+        let disc_mass = |r: f64| {
+            self.disc.map_or(0.0, |disc| {
+                let x = r / disc.scale_length as f64;
+                disc.mass as f64 * (1.0 - (1.0 + x) * (-x).exp())
+            })
+        };
+        let sphere_mass = |sphere: &Option<Sphere>, r: f64| {
+            sphere
+                .as_ref()
+                .map_or(0.0, |sphere| sphere.enclosed_mass(r))
+        };
+
+        // The enclosed masses do not depend on the potentials, so each sphere can be solved in turn
+        if let Some(bulge) = &mut bulge {
+            bulge.set_external_mass(|r| disc_mass(r) + sphere_mass(&halo, r));
+        }
+        if let Some(halo) = &mut halo {
+            halo.set_external_mass(|r| disc_mass(r) + sphere_mass(&bulge, r));
+        }
+
+        // Components sampled about the origin, with the mass of each of their particles
+        let mut components: Vec<(Samples, f32)> = Vec::new();
+        if let Some(bulge) = &bulge {
+            components.push((
+                bulge.sample(rng, grav_const),
+                bulge.mass / bulge.num_particles as f32,
+            ));
+        }
+        let mut tracers = None;
+        if let Some(disc) = self.disc {
+            let spheres_mass =
+                |r: f32| (sphere_mass(&bulge, r as f64) + sphere_mass(&halo, r as f64)) as f32;
+            let mut sample = |num_particles| {
+                sample_exponential_disc(
+                    rng,
+                    grav_const,
+                    disc.scale_length,
+                    disc.scale_height,
+                    disc.mass,
+                    disc.toomre_q,
+                    spheres_mass,
+                    num_particles,
+                )
+            };
+            components.push((
+                sample(disc.num_particles),
+                disc.mass / disc.num_particles as f32,
+            ));
+            tracers = self
+                .ghosts
+                .map(|ghosts| (sample(ghosts.num_particles), ghosts.kind));
+        }
+        if let Some(halo) = &halo {
+            components.push((
+                halo.sample(rng, grav_const),
+                halo.mass / halo.num_particles as f32,
+            ));
+        }
+
+        // The components stay concentric about `centre` (a centre of mass would follow the far tails of the
+        // spheres), and the galaxy as a whole moves with `velocity`
+        let mut momentum = [0.0; 3];
+        for ((_, velocities), mass) in &components {
+            for velocity in velocities {
+                (0..3).for_each(|k| momentum[k] += *mass as f64 * velocity[k]);
+            }
+        }
+        let mean_velocity = momentum.map(|p| p / self.total_mass() as f64);
+
+        // Particles about the origin turned to the galaxy's orientation and moved to its centre and velocity
+        let place = |position: &[f64; 3], velocity: &[f64; 3]| {
+            let offset = self.orientation.position(position.map(|x| x as f32));
+            let relative = self
+                .orientation
+                .velocity([0, 1, 2].map(|k| (velocity[k] - mean_velocity[k]) as f32));
+            (
+                [0, 1, 2].map(|k| self.centre[k] + offset[k]),
+                [0, 1, 2].map(|k| self.velocity[k] + relative[k]),
+            )
+        };
+
+        for ((positions, velocities), mass) in &components {
+            for (position, velocity) in positions.iter().zip(velocities) {
+                let (position, velocity) = place(position, velocity);
+                nbody.add_massive_particle(position, velocity, *mass);
+            }
+        }
+        if let Some(((positions, velocities), kind)) = &tracers {
+            for (position, velocity) in positions.iter().zip(velocities) {
+                let (position, velocity) = place(position, velocity);
+                nbody.add_ghost_particle(position, velocity, *kind);
+            }
+        }
+    }
+}
@@ -1,50 +1,125 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de::value::StrDeserializer, Deserialize};
+
 use wgpu::{Device, Queue, Surface, SurfaceConfiguration};
 use winit::window::Window;
 
+// Graphics API used to find an adapter
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    // Whichever backend offers the best adapter
+    #[default]
+    Any,
+    Vulkan,
+    Metal,
+    Dx12,
+    // OpenGL (or GLES), which includes most software rasterisers
+    Gl,
+}
+
+impl Backend {
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Self::Any => wgpu::Backends::all(),
+            Self::Vulkan => wgpu::Backends::VULKAN,
+            Self::Metal => wgpu::Backends::METAL,
+            Self::Dx12 => wgpu::Backends::DX12,
+            Self::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+// Names are those of the serde variants, in any case
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.to_ascii_lowercase();
+        Self::deserialize(StrDeserializer::<serde::de::value::Error>::new(&name))
+            .map_err(|error| format!("Unknown backend: {}", error))
+    }
+}
+
+#[derive(Debug)]
+pub enum HardwareError {
+    Surface(wgpu::CreateSurfaceError),
+    NoAdapter {
+        backend: Backend,
+        force_fallback_adapter: bool,
+    },
+    Device(wgpu::RequestDeviceError),
+}
+
+impl fmt::Display for HardwareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Surface(error) => write!(f, "Failed to create the window surface: {}", error),
+            Self::NoAdapter {
+                backend,
+                force_fallback_adapter,
+            } => {
+                write!(f, "No {:?} adapter found", backend)?;
+                if *force_fallback_adapter {
+                    write!(f, " (only the fallback adapter was requested)")?;
+                }
+                Ok(())
+            }
+            Self::Device(error) => write!(f, "Failed to open the device: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for HardwareError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Surface(error) => Some(error),
+            Self::NoAdapter { .. } => None,
+            Self::Device(error) => Some(error),
+        }
+    }
+}
+
+// Every adapter the backend can see, with its limits
+pub fn adapters(backend: Backend) -> Vec<(wgpu::AdapterInfo, wgpu::Limits)> {
+    Hardware::create_instance(backend)
+        .enumerate_adapters(backend.backends())
+        .map(|adapter| (adapter.get_info(), adapter.limits()))
+        .collect()
+}
+
 pub struct Hardware {
     pub device: Device,
     pub queue: Queue,
     pub config: SurfaceConfiguration,
-    pub surface: Surface,
-    pub window: Window,
+    pub surface: Option<Surface>,
+    pub window: Option<Window>,
 }
 
 impl Hardware {
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(
+        window: Window,
+        backend: Backend,
+        force_fallback_adapter: bool,
+    ) -> Result<Self, HardwareError> {
         // Window size.
         let size = window.inner_size();
 
         // Hardware.
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: Default::default(),
-        });
+        let instance = Self::create_instance(backend);
 
         // Window surface.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let surface =
+            unsafe { instance.create_surface(&window) }.map_err(HardwareError::Surface)?;
 
         // GPU handle.
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
+        let adapter =
+            Self::request_adapter(&instance, Some(&surface), backend, force_fallback_adapter)
+                .await?;
 
         // Command queue.
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (device, queue) = Self::request_device(&adapter).await?;
 
         // Surface configuration.
         let surface_caps = surface.get_capabilities(&adapter);
@@ -65,12 +140,105 @@ impl Hardware {
         };
         surface.configure(&device, &config);
 
-        Self {
-            surface,
+        Ok(Self {
+            surface: Some(surface),
             device,
             queue,
             config,
-            window,
-        }
+            window: Some(window),
+        })
+    }
+
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        backend: Backend,
+        force_fallback_adapter: bool,
+    ) -> Result<Self, HardwareError> {
+        // Hardware.
+        let instance = Self::create_instance(backend);
+
+        // GPU handle.
+        let adapter =
+            Self::request_adapter(&instance, None, backend, force_fallback_adapter).await?;
+
+        // Command queue.
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        // Target configuration (there is no surface to configure).
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+
+        Ok(Self {
+            surface: None,
+            device,
+            queue,
+            config,
+            window: None,
+        })
+    }
+
+    pub fn window(&self) -> &Window {
+        self.window
+            .as_ref()
+            .expect("Headless hardware has no window")
+    }
+
+    fn create_instance(backend: Backend) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: backend.backends(),
+            dx12_shader_compiler: Default::default(),
+        })
+    }
+
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&Surface>,
+        backend: Backend,
+        force_fallback_adapter: bool,
+    ) -> Result<wgpu::Adapter, HardwareError> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface,
+                force_fallback_adapter,
+            })
+            .await
+            .ok_or(HardwareError::NoAdapter {
+                backend,
+                force_fallback_adapter,
+            })?;
+
+        let info = adapter.get_info();
+        log::info!(
+            "Using adapter {} ({:?}, {:?})",
+            info.name,
+            info.backend,
+            info.device_type
+        );
+        log::info!("Adapter limits: {:?}", adapter.limits());
+
+        Ok(adapter)
+    }
+
+    async fn request_device(adapter: &wgpu::Adapter) -> Result<(Device, Queue), HardwareError> {
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .map_err(HardwareError::Device)
     }
 }
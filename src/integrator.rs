@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    // Forces -> velocities -> positions (first order, kept for legacy runs)
+    Euler,
+    // Kick-drift-kick: half kick, drift, forces, half kick (symplectic, second order)
+    #[default]
+    Leapfrog,
+    // Positions from the current accelerations, velocities from the average of old and new (second order)
+    VelocityVerlet,
+    // Jerk-based predictor-corrector (fourth order, not symplectic)
+    Hermite,
+    // Three leapfrog sub-steps with Yoshida (1990) weights (symplectic, fourth order)
+    Yoshida,
+}
+
+impl Integrator {
+    // Fractions of the time step taken by each Yoshida leapfrog sub-step: [w1, w0, w1]
+    pub const YOSHIDA_WEIGHTS: [f32; 3] = [
+        1.351_207_2,  // 1 / (2 - 2^(1/3))
+        -1.702_414_4, // -2^(1/3) / (2 - 2^(1/3))
+        1.351_207_2,
+    ];
+}
@@ -0,0 +1,243 @@
+use std::f64::consts::TAU;
+
+use nalgebra::{Rotation3, Vector3};
+
+use crate::NBody;
+
+// Keplerian orbit of a body about its primary, with angles in radians:
+//   semi_major_axis: negative for hyperbolic orbits (eccentricity above 1)
+//   inclination: from the xy reference plane
+//   longitude_of_ascending_node: from the x axis to the ascending node
+//   argument_of_periapsis: from the ascending node to periapsis
+//   mean_anomaly: at the epoch, from periapsis
+// Equatorial orbits measure their node from the x axis (so the node longitude is 0) and circular orbits their
+// periapsis from the node (so the argument of periapsis is 0).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalElements {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub longitude_of_ascending_node: f32,
+    pub argument_of_periapsis: f32,
+    pub mean_anomaly: f32,
+}
+
+// Below this an eccentricity, or the sine of an inclination, counts as zero (single-precision states leave
+// noise of about 1e-7 in both)
+const DEGENERATE: f64 = 1.0e-6;
+
+impl OrbitalElements {
+    // The first malformed element, described for the user (parabolic orbits have no semi-major axis, so they are
+    // not supported)
+    pub fn validate(&self) -> Result<(), String> {
+        let (a, e) = (self.semi_major_axis, self.eccentricity);
+        let angles = [
+            self.inclination,
+            self.longitude_of_ascending_node,
+            self.argument_of_periapsis,
+            self.mean_anomaly,
+        ];
+        if !(a.is_finite() && a != 0.0) {
+            return Err("semi_major_axis must be finite and non-zero".to_string());
+        }
+        if !(e.is_finite() && e >= 0.0) || e == 1.0 {
+            return Err("eccentricity must be at least 0 and not 1".to_string());
+        }
+        if (e < 1.0) != (a > 0.0) {
+            return Err(
+                "semi_major_axis must be positive below an eccentricity of 1 and negative above it"
+                    .to_string(),
+            );
+        }
+        if !angles.iter().all(|angle| angle.is_finite()) {
+            return Err("angles must be finite".to_string());
+        }
+        Ok(())
+    }
+
+    // Position and velocity relative to the primary, for the gravitational parameter mu = G (M + m)
+    pub fn to_state(&self, mu: f32) -> Result<([f32; 3], [f32; 3]), String> {
+        self.validate()?;
+        let (position, velocity) = self.state(mu as f64);
+        Ok((to_f32(position), to_f32(velocity)))
+    }
+
+    // Elements of the orbit through a position and velocity relative to the primary
+    pub fn from_state(mu: f32, position: [f32; 3], velocity: [f32; 3]) -> Self {
+        let mu = mu as f64;
+        let (r, v) = (to_f64(position), to_f64(velocity));
+        let (distance, speed2) = (r.norm(), v.norm_squared());
+
+        let h = r.cross(&v);
+        let node = Vector3::new(-h.y, h.x, 0.0);
+        let e = ((speed2 - mu / distance) * r - r.dot(&v) * v) / mu;
+        let eccentricity = e.norm();
+        let semi_major_axis = 1.0 / (2.0 / distance - speed2 / mu);
+        let inclination = (h.z / h.norm()).clamp(-1.0, 1.0).acos();
+
+        // Angle from a to b measured about the orbit normal
+        let angle =
+            |a: &Vector3<f64>, b: &Vector3<f64>| a.cross(b).dot(&h).atan2(a.dot(b) * h.norm());
+
+        let equatorial = node.norm() <= DEGENERATE * h.norm();
+        let reference = if equatorial { Vector3::x() } else { node };
+        let longitude_of_ascending_node = if equatorial {
+            0.0
+        } else {
+            node.y.atan2(node.x)
+        };
+        let (argument_of_periapsis, true_anomaly) = if eccentricity <= DEGENERATE {
+            (0.0, angle(&reference, &r))
+        } else {
+            (angle(&reference, &e), angle(&e, &r))
+        };
+
+        let mean_anomaly = if eccentricity < 1.0 {
+            let eccentric_anomaly = 2.0
+                * ((1.0 - eccentricity).sqrt() * (0.5 * true_anomaly).sin())
+                    .atan2((1.0 + eccentricity).sqrt() * (0.5 * true_anomaly).cos());
+            (eccentric_anomaly - eccentricity * eccentric_anomaly.sin()).rem_euclid(TAU)
+        } else {
+            let hyperbolic_anomaly = 2.0
+                * (((eccentricity - 1.0) / (eccentricity + 1.0)).sqrt()
+                    * (0.5 * true_anomaly).tan())
+                .atanh();
+            eccentricity * hyperbolic_anomaly.sinh() - hyperbolic_anomaly
+        };
+
+        Self {
+            semi_major_axis: semi_major_axis as f32,
+            eccentricity: eccentricity as f32,
+            inclination: inclination as f32,
+            longitude_of_ascending_node: longitude_of_ascending_node.rem_euclid(TAU) as f32,
+            argument_of_periapsis: argument_of_periapsis.rem_euclid(TAU) as f32,
+            mean_anomaly: mean_anomaly as f32,
+        }
+    }
+
+    // Only for validated elements
+    fn state(&self, mu: f64) -> (Vector3<f64>, Vector3<f64>) {
+        let a = self.semi_major_axis as f64;
+        let e = self.eccentricity as f64;
+        let mean_anomaly = self.mean_anomaly as f64;
+
+        // True anomaly from Kepler's equation, solved by Newton's method
+        let true_anomaly = if e < 1.0 {
+            let m = mean_anomaly.rem_euclid(TAU);
+            let mut eccentric_anomaly = if e < 0.8 { m } else { std::f64::consts::PI };
+            for _ in 0..50 {
+                let step = (eccentric_anomaly - e * eccentric_anomaly.sin() - m)
+                    / (1.0 - e * eccentric_anomaly.cos());
+                eccentric_anomaly -= step;
+                if step.abs() < 1.0e-15 {
+                    break;
+                }
+            }
+            2.0 * ((1.0 + e).sqrt() * (0.5 * eccentric_anomaly).sin())
+                .atan2((1.0 - e).sqrt() * (0.5 * eccentric_anomaly).cos())
+        } else {
+            let mut hyperbolic_anomaly = (mean_anomaly / e).asinh();
+            for _ in 0..50 {
+                let step = (e * hyperbolic_anomaly.sinh() - hyperbolic_anomaly - mean_anomaly)
+                    / (e * hyperbolic_anomaly.cosh() - 1.0);
+                hyperbolic_anomaly -= step;
+                if step.abs() < 1.0e-15 * hyperbolic_anomaly.abs().max(1.0) {
+                    break;
+                }
+            }
+            2.0 * (((e + 1.0) / (e - 1.0)).sqrt() * (0.5 * hyperbolic_anomaly).tanh()).atan()
+        };
+
+        // In the perifocal frame, with periapsis along x
+        let semi_latus_rectum = a * (1.0 - e * e);
+        let (sin, cos) = true_anomaly.sin_cos();
+        let r = semi_latus_rectum / (1.0 + e * cos);
+        let speed = (mu / semi_latus_rectum).sqrt();
+        let position = Vector3::new(r * cos, r * sin, 0.0);
+        let velocity = Vector3::new(-speed * sin, speed * (e + cos), 0.0);
+
+        // Turned by the argument of periapsis, the inclination and the longitude of the ascending node
+        let rotation =
+            Rotation3::from_axis_angle(&Vector3::z_axis(), self.longitude_of_ascending_node as f64)
+                * Rotation3::from_axis_angle(&Vector3::x_axis(), self.inclination as f64)
+                * Rotation3::from_axis_angle(&Vector3::z_axis(), self.argument_of_periapsis as f64);
+        (rotation * position, rotation * velocity)
+    }
+}
+
+// A central body orbited by bodies on Keplerian orbits about it (each about the central body alone), moved to the
+// barycentric frame and then to `centre` and `drift`
+#[derive(Clone, Debug)]
+pub struct PlanetarySystemBuilder {
+    central_mass: f32,
+    bodies: Vec<(OrbitalElements, f32)>,
+}
+
+impl PlanetarySystemBuilder {
+    pub fn new(central_mass: f32) -> Self {
+        Self {
+            central_mass,
+            bodies: Vec::new(),
+        }
+    }
+
+    pub fn body(mut self, elements: OrbitalElements, mass: f32) -> Self {
+        self.bodies.push((elements, mass));
+        self
+    }
+
+    // The central body first, then the others in the order given (nothing is added if any mass or orbit is
+    // malformed)
+    pub fn add_to(
+        &self,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        nbody: &mut NBody,
+    ) -> Result<(), String> {
+        if self.central_mass.is_nan() || self.central_mass <= 0.0 {
+            return Err("central_mass must be positive".to_string());
+        }
+        let mut states = vec![(Vector3::zeros(), Vector3::zeros(), self.central_mass as f64)];
+        for (index, (elements, mass)) in self.bodies.iter().enumerate() {
+            if mass.is_nan() || *mass <= 0.0 {
+                return Err(format!("bodies[{}].mass must be positive", index));
+            }
+            elements
+                .validate()
+                .map_err(|message| format!("bodies[{}].{}", index, message))?;
+            let mu = grav_const as f64 * (self.central_mass as f64 + *mass as f64);
+            let (position, velocity) = elements.state(mu);
+            states.push((position, velocity, *mass as f64));
+        }
+
+        let total_mass = states.iter().map(|(_, _, m)| m).sum::<f64>();
+        let position = states
+            .iter()
+            .map(|(r, _, m)| r * (m / total_mass))
+            .sum::<Vector3<f64>>();
+        let velocity = states
+            .iter()
+            .map(|(_, v, m)| v * (m / total_mass))
+            .sum::<Vector3<f64>>();
+
+        let (centre, drift) = (to_f64(centre), to_f64(drift));
+        for (r, v, mass) in &states {
+            nbody.add_massive_particle(
+                to_f32(centre + r - position),
+                to_f32(drift + v - velocity),
+                *mass as f32,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn to_f64(v: [f32; 3]) -> Vector3<f64> {
+    Vector3::new(v[0] as f64, v[1] as f64, v[2] as f64)
+}
+
+fn to_f32(v: Vector3<f64>) -> [f32; 3] {
+    [v.x as f32, v.y as f32, v.z as f32]
+}
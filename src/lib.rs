@@ -1,40 +1,106 @@
 mod camera;
+mod checkpoint;
+mod cpu;
+mod csv;
+mod encounter;
+mod gadget;
+mod galaxy;
 mod hardware;
+mod integrator;
+mod kepler;
 mod memory;
 mod nbody;
+mod numpy;
+mod orientation;
 mod pipelines;
+mod scenario;
 mod settings;
 mod simulation;
+mod snapshot;
+mod solver;
+mod spherical;
+mod tipsy;
+mod transform;
+mod virial;
 
 pub use camera::Camera;
+pub use checkpoint::{checkpoint_path, latest_checkpoint, Checkpoint};
+pub use cpu::CpuSimulation;
+pub use encounter::{DiscOrientation, Encounter};
+pub use gadget::{GadgetFormat, GadgetMapping, GadgetRole};
+pub use galaxy::GalaxyBuilder;
+pub use hardware::{adapters, Backend, HardwareError};
+pub use integrator::Integrator;
+pub use kepler::{OrbitalElements, PlanetarySystemBuilder};
 pub use nbody::NBody;
+pub use orientation::{Orientation, Spin};
+pub use scenario::{Component, DiscPlane, HaloProfile, OrbitingBody, Scenario, ScenarioError};
 pub use settings::Settings;
+pub use simulation::Simulation;
+pub use snapshot::{Snapshot, SnapshotFormat};
+pub use solver::{MassAssignment, MeshBoundary, Solver};
+pub use tipsy::{Endianness, TipsyGas};
 
 use hardware::Hardware;
-use log;
 use memory::{Memory, Vertex};
 use pipelines::Pipelines;
-use simulation::Simulation;
+use std::path::{Path, PathBuf};
 use winit::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Window, WindowBuilder},
 };
 
-pub async fn run(settings: Settings, camera: Camera, init_conditions: NBody) {
+pub async fn run(
+    settings: Settings,
+    camera: Camera,
+    init_conditions: NBody,
+) -> Result<(), HardwareError> {
     debug_assert!(init_conditions.is_valid());
 
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
+    let window = create_window(&event_loop, &settings);
+    let mut simulation = Simulation::new(window, settings, camera, init_conditions).await?;
+    simulation.relax(settings.relaxation_steps, settings.relaxation_damping);
+
+    run_event_loop(event_loop, simulation)
+}
+
+// Continue an interactive run from a checkpoint file
+pub async fn resume(checkpoint_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let checkpoint = Checkpoint::load(checkpoint_path)?;
+
+    let event_loop = EventLoop::new();
+    let window = create_window(&event_loop, &checkpoint.settings);
+    let mut simulation = Simulation::new(
+        window,
+        checkpoint.settings,
+        checkpoint.camera,
+        checkpoint.state(),
+    )
+    .await?;
+    simulation.restore(&checkpoint);
+    log::info!(
+        "Resumed {} at step {}",
+        checkpoint_path.display(),
+        checkpoint.steps
+    );
+
+    run_event_loop(event_loop, simulation)
+}
+
+fn create_window(event_loop: &EventLoop<()>, settings: &Settings) -> Window {
+    WindowBuilder::new()
         .with_title("Heavens")
         .with_inner_size(winit::dpi::LogicalSize::new(
             settings.display_width * settings.pixel_size,
             settings.display_height * settings.pixel_size,
         ))
-        .build(&event_loop)
-        .unwrap();
+        .build(event_loop)
+        .unwrap()
+}
 
-    let mut simulation = Simulation::new(window, settings, camera, init_conditions).await;
+fn run_event_loop(event_loop: EventLoop<()>, mut simulation: Simulation) -> ! {
     let mut azimuthal_delta = 0.0;
     let mut polar_delta = 0.0;
     let mut zoom_delta = 1.0;
@@ -48,102 +114,108 @@ pub async fn run(settings: Settings, camera: Camera, init_conditions: NBody) {
             Event::WindowEvent {
                 window_id,
                 ref event,
-            } if window_id == simulation.hardware.window.id() => {
-                if !simulation.input(event) {
-                    match event {
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => {
-                            log::info!("Escape pressed, closing");
-                            *control_flow = ControlFlow::Exit
-                        }
-                        WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(key_code),
-                                    ..
-                                },
-                            ..
-                        } => match key_code {
-                            VirtualKeyCode::Minus => {
-                                simulation.settings.time_step /= 2.0;
-                            }
-                            VirtualKeyCode::Equals => {
-                                simulation.settings.time_step *= 2.0;
-                            }
-                            VirtualKeyCode::Q => {
-                                zoom_delta += 1.0e-3;
-                            }
-                            VirtualKeyCode::E => {
-                                zoom_delta -= 1.0e-3;
-                            }
-                            VirtualKeyCode::Z => {
-                                simulation.settings.blur_radius /= 2.0;
-                            }
-                            VirtualKeyCode::X => {
-                                simulation.settings.blur_radius *= 2.0;
-                            }
-                            VirtualKeyCode::F => {
-                                simulation.settings.gravitational_constant /= 2.0;
-                            }
-                            VirtualKeyCode::G => {
-                                simulation.settings.gravitational_constant *= 2.0;
-                            }
-                            VirtualKeyCode::A => {
-                                azimuthal_delta -= 1.0e-3;
-                            }
-                            VirtualKeyCode::D => {
-                                azimuthal_delta += 1.0e-3;
-                            }
-                            VirtualKeyCode::W => {
-                                polar_delta -= 1.0e-3;
-                            }
-                            VirtualKeyCode::S => {
-                                polar_delta += 1.0e-3;
-                            }
-                            VirtualKeyCode::O => {
-                                simulation.settings.ghost_stack_visible_limit /= 2.0;
-                            }
-                            VirtualKeyCode::P => {
-                                simulation.settings.ghost_stack_visible_limit *= 2.0;
-                            }
-                            VirtualKeyCode::Space => {
-                                polar_delta = 0.0;
-                                azimuthal_delta = 0.0;
-                                zoom_delta = 1.0;
-                                pause_time = !pause_time;
-                                println!("Time paused: {}", pause_time);
-                            }
-                            _ => {
-                                println!("Unbound key pressed: {:?}", key_code);
+            } if window_id == simulation.hardware.window().id() && !simulation.input(event) => {
+                match event {
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } => {
+                        log::info!("Escape pressed, closing");
+                        *control_flow = ControlFlow::Exit
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key_code),
+                                ..
+                            },
+                        ..
+                    } => match key_code {
+                        VirtualKeyCode::Minus => {
+                            simulation.settings.time_step /= 2.0;
+                        }
+                        VirtualKeyCode::Equals => {
+                            simulation.settings.time_step *= 2.0;
+                        }
+                        VirtualKeyCode::Q => {
+                            zoom_delta += 1.0e-3;
+                        }
+                        VirtualKeyCode::E => {
+                            zoom_delta -= 1.0e-3;
+                        }
+                        VirtualKeyCode::Z => {
+                            simulation.settings.blur_radius /= 2.0;
+                        }
+                        VirtualKeyCode::X => {
+                            simulation.settings.blur_radius *= 2.0;
+                        }
+                        VirtualKeyCode::F => {
+                            simulation.settings.gravitational_constant /= 2.0;
+                        }
+                        VirtualKeyCode::G => {
+                            simulation.settings.gravitational_constant *= 2.0;
+                        }
+                        VirtualKeyCode::A => {
+                            azimuthal_delta -= 1.0e-3;
+                        }
+                        VirtualKeyCode::D => {
+                            azimuthal_delta += 1.0e-3;
+                        }
+                        VirtualKeyCode::W => {
+                            polar_delta -= 1.0e-3;
+                        }
+                        VirtualKeyCode::S => {
+                            polar_delta += 1.0e-3;
+                        }
+                        VirtualKeyCode::O => {
+                            simulation.settings.ghost_stack_visible_limit /= 2.0;
+                        }
+                        VirtualKeyCode::P => {
+                            simulation.settings.ghost_stack_visible_limit *= 2.0;
+                        }
+                        VirtualKeyCode::C => {
+                            if let Err(error) = save_checkpoint(&simulation) {
+                                log::error!("Failed to save checkpoint: {}", error);
                             }
-                        },
-                        WindowEvent::Resized(physical_size) => {
-                            simulation.resize(*physical_size);
                         }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            simulation.resize(**new_inner_size);
+                        VirtualKeyCode::Space => {
+                            polar_delta = 0.0;
+                            azimuthal_delta = 0.0;
+                            zoom_delta = 1.0;
+                            pause_time = !pause_time;
+                            println!("Time paused: {}", pause_time);
                         }
-                        _ => {}
+                        _ => {
+                            println!("Unbound key pressed: {:?}", key_code);
+                        }
+                    },
+                    WindowEvent::Resized(physical_size) => {
+                        simulation.resize(*physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        simulation.resize(**new_inner_size);
                     }
+                    _ => {}
                 }
             }
             Event::MainEventsCleared => {
                 log::debug!("Main events cleared");
-                // simulation.hardware.window.request_redraw();
+                // simulation.hardware.window().request_redraw();
             }
-            Event::RedrawRequested(window_id) if window_id == simulation.hardware.window.id() => {
+            Event::RedrawRequested(window_id) if window_id == simulation.hardware.window().id() => {
                 log::debug!("Redraw requested");
                 if !pause_time {
                     simulation.update();
+                    if let Err(error) = checkpoint_if_due(&simulation) {
+                        log::error!("Failed to save checkpoint: {}", error);
+                    }
                 }
 
                 simulation.camera.rotate_azimuthal(azimuthal_delta);
@@ -156,7 +228,7 @@ pub async fn run(settings: Settings, camera: Camera, init_conditions: NBody) {
                     }
                     // Reconfigure the surface if it's lost or outdated
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                        simulation.resize(simulation.hardware.window.inner_size())
+                        simulation.resize(simulation.hardware.window().inner_size())
                     }
                     // The system is out of memory, we should probably quit
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
@@ -165,9 +237,178 @@ pub async fn run(settings: Settings, camera: Camera, init_conditions: NBody) {
             }
             Event::RedrawEventsCleared => {
                 log::debug!("Redraw events cleared");
-                simulation.hardware.window.request_redraw();
+                simulation.hardware.window().request_redraw();
             }
             _ => (),
         }
     });
 }
+
+// Report the pairwise interaction rate of both direct-sum kernels on the given initial conditions
+pub async fn benchmark(
+    settings: Settings,
+    camera: Camera,
+    init_conditions: NBody,
+) -> Result<(), HardwareError> {
+    debug_assert!(init_conditions.is_valid());
+
+    const NUM_EVALUATIONS: u32 = 10;
+
+    let mut simulation = Simulation::new_headless(settings, camera, init_conditions).await?;
+    for solver in [Solver::Direct, Solver::TiledDirect] {
+        let interactions_per_second = simulation.benchmark_forces(solver, NUM_EVALUATIONS);
+        println!(
+            "{:?}: {:.3e} interactions per second",
+            solver, interactions_per_second
+        );
+    }
+
+    Ok(())
+}
+
+// Render without a window, saving a numbered PNG frame to `output_dir` every `frame_interval` steps
+pub async fn capture(
+    settings: Settings,
+    camera: Camera,
+    init_conditions: NBody,
+    num_steps: u32,
+    frame_interval: u32,
+    output_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    debug_assert!(init_conditions.is_valid());
+    if frame_interval == 0 {
+        return Err("frame_interval must be positive".into());
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let frame_path = |step: u32| output_dir.join(format!("frame_{:06}.png", step / frame_interval));
+
+    let mut simulation = Simulation::new_offscreen(settings, camera, init_conditions).await?;
+    simulation.relax(settings.relaxation_steps, settings.relaxation_damping);
+    simulation.save_frame(&frame_path(0))?;
+    for step in 1..=num_steps {
+        simulation.update();
+        checkpoint_if_due(&simulation)?;
+        if step % frame_interval == 0 {
+            let path = frame_path(step);
+            simulation.save_frame(&path)?;
+            log::info!("Saved {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+// Run without rendering, saving a numbered snapshot to `output_dir` every `snapshot_interval` steps
+pub async fn simulate(
+    settings: Settings,
+    camera: Camera,
+    init_conditions: NBody,
+    num_steps: u32,
+    snapshot_interval: u32,
+    output_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    debug_assert!(init_conditions.is_valid());
+    if snapshot_interval == 0 {
+        return Err("snapshot_interval must be positive".into());
+    }
+
+    // Without an adapter, fall back to the CPU
+    let mut simulation =
+        match Simulation::new_headless(settings, camera, init_conditions.clone()).await {
+            Ok(simulation) => simulation,
+            Err(error @ HardwareError::NoAdapter { .. }) => {
+                log::warn!("{}, simulating on the CPU instead", error);
+                return simulate_on_cpu(
+                    settings,
+                    init_conditions,
+                    num_steps,
+                    snapshot_interval,
+                    output_dir,
+                );
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+    std::fs::create_dir_all(output_dir)?;
+    simulation.relax(settings.relaxation_steps, settings.relaxation_damping);
+    simulation
+        .snapshot()
+        .save(&snapshot_path(output_dir, 0, snapshot_interval))?;
+    for step in 1..=num_steps {
+        simulation.update();
+        checkpoint_if_due(&simulation)?;
+        if step % snapshot_interval == 0 {
+            let path = snapshot_path(output_dir, step, snapshot_interval);
+            simulation.snapshot().save(&path)?;
+            log::info!("Saved {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+// `simulate` with the direct sum of `CpuSimulation`, which needs no GPU (and saves no checkpoints)
+pub fn simulate_on_cpu(
+    settings: Settings,
+    init_conditions: NBody,
+    num_steps: u32,
+    snapshot_interval: u32,
+    output_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    debug_assert!(init_conditions.is_valid());
+    if snapshot_interval == 0 {
+        return Err("snapshot_interval must be positive".into());
+    }
+
+    if settings.checkpoint_interval > 0 {
+        log::warn!("Checkpoints are not saved when simulating on the CPU");
+    }
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut simulation = CpuSimulation::new(settings, init_conditions);
+    simulation.relax(settings.relaxation_steps, settings.relaxation_damping);
+    simulation
+        .snapshot()
+        .save(&snapshot_path(output_dir, 0, snapshot_interval))?;
+    for step in 1..=num_steps {
+        simulation.update();
+        if step % snapshot_interval == 0 {
+            let path = snapshot_path(output_dir, step, snapshot_interval);
+            simulation.snapshot().save(&path)?;
+            log::info!("Saved {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn snapshot_path(output_dir: &Path, step: u32, snapshot_interval: u32) -> PathBuf {
+    output_dir.join(format!("snapshot_{:06}.hvs", step / snapshot_interval))
+}
+
+// Save a checkpoint every `settings.checkpoint_interval` steps
+fn checkpoint_if_due(simulation: &Simulation) -> std::io::Result<()> {
+    let interval = simulation.settings.checkpoint_interval as u64;
+    if interval > 0 && simulation.steps() % interval == 0 {
+        save_checkpoint(simulation)?;
+    }
+
+    Ok(())
+}
+
+// Checkpoints (on `C` or every `settings.checkpoint_interval` steps) go in the working directory
+fn save_checkpoint(simulation: &Simulation) -> std::io::Result<()> {
+    // Write under another name first, so a crash mid-write cannot leave a truncated checkpoint
+    let path = checkpoint_path(Path::new("."), simulation.steps());
+    let partial_path = path.with_extension("hvc.partial");
+    simulation.checkpoint().save(&partial_path)?;
+    std::fs::rename(&partial_path, &path)?;
+    log::info!(
+        "Saved checkpoint at step {} to {}",
+        simulation.steps(),
+        path.display()
+    );
+
+    Ok(())
+}
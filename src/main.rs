@@ -1,19 +1,342 @@
-use heavens::{run, Camera, NBody, Settings};
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use heavens::{
+    adapters, benchmark, capture, latest_checkpoint, resume, run, simulate, simulate_on_cpu,
+    Backend, Camera, Integrator, MassAssignment, MeshBoundary, NBody, Scenario, Settings,
+    SnapshotFormat, Solver,
+};
+use serde::de::{value::StrDeserializer, DeserializeOwned};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[command(version, about = "N-body GPU simulations with rendering")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>, // Defaults to `run` with the built-in scenario
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Simulate interactively in a window")]
+    Run(Setup),
+    #[command(
+        about = "Render without a window, saving numbered PNG frames",
+        alias = "capture"
+    )]
+    Render {
+        #[command(flatten)]
+        setup: Setup,
+        #[arg(long, default_value_t = 1000, help = "Steps to simulate")]
+        steps: u32,
+        #[arg(
+            long,
+            default_value_t = 10,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "Steps between frames"
+        )]
+        every: u32,
+        #[arg(long, default_value = "frames", help = "Directory of the frames")]
+        output: PathBuf,
+    },
+    #[command(about = "Simulate without rendering, saving numbered snapshots")]
+    Simulate {
+        #[command(flatten)]
+        setup: Setup,
+        #[arg(long, default_value_t = 1000, help = "Steps to simulate")]
+        steps: u32,
+        #[arg(
+            long,
+            default_value_t = 100,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "Steps between snapshots"
+        )]
+        every: u32,
+        #[arg(long, default_value = "snapshots", help = "Directory of the snapshots")]
+        output: PathBuf,
+        #[arg(
+            long,
+            help = "Simulate on the CPU with a direct sum (the default without a GPU)"
+        )]
+        cpu: bool,
+    },
+    #[command(about = "Compare the interaction rates of the direct-sum force kernels")]
+    Benchmark(Setup),
+    #[command(about = "Continue an interactive run from a checkpoint")]
+    Resume {
+        #[arg(help = "Checkpoint file (the latest in the working directory if omitted)")]
+        checkpoint: Option<PathBuf>,
+    },
+    #[command(about = "List the adapters of a backend and their limits")]
+    Info {
+        #[arg(long, default_value = "any", value_parser = parse_value::<Backend>)]
+        backend: Backend,
+    },
+    #[command(about = "Convert a snapshot between formats (chosen by file extension)")]
+    Convert { input: PathBuf, output: PathBuf },
+}
+
+// Where the settings, camera and initial conditions come from
+#[derive(Args, Default)]
+struct Setup {
+    #[arg(help = "Scenario file (the built-in scenario if omitted)")]
+    scenario: Option<PathBuf>,
+    #[command(flatten)]
+    overrides: SettingsOverrides,
+}
+
+// One flag per `Settings` field, taking precedence over the scenario
+#[derive(Args, Default)]
+#[command(next_help_heading = "Settings")]
+struct SettingsOverrides {
+    #[arg(long, help = "Window width [pixels]")]
+    display_width: Option<f32>,
+    #[arg(long, help = "Window height [pixels]")]
+    display_height: Option<f32>,
+    #[arg(long, help = "Size of a rendered particle [pixels]")]
+    pixel_size: Option<f32>,
+    #[arg(long, help = "Gravitational constant")]
+    gravitational_constant: Option<f32>,
+    #[arg(long, help = "Time step of the integrator")]
+    time_step: Option<f32>,
+    #[arg(long, help = "Softening length of the force")]
+    smoothing_length: Option<f32>,
+    #[arg(long, help = "Mass of a ghost particle when rendered")]
+    ghost_mass: Option<f32>,
+    #[arg(long, help = "Stacked ghosts needed for full brightness")]
+    ghost_stack_visible_limit: Option<f32>,
+    #[arg(long, help = "Radius of the bloom blur [pixels]")]
+    blur_radius: Option<f32>,
+    #[arg(long, help = "Barnes-Hut opening angle (tree solver)")]
+    opening_angle: Option<f32>,
+    #[arg(long, value_parser = parse_value::<Integrator>, help = "euler, leapfrog, velocity_verlet, hermite or yoshida")]
+    integrator: Option<Integrator>,
+    #[arg(long, value_parser = parse_value::<Solver>, help = "direct, tiled_direct, tree or particle_mesh")]
+    solver: Option<Solver>,
+    #[arg(long, help = "Bodies per tile of the tiled direct sum")]
+    tile_size: Option<u32>,
+    #[arg(long, help = "Cells per side of the particle mesh")]
+    mesh_size: Option<u32>,
+    #[arg(long, value_parser = parse_value::<MassAssignment>, help = "ngp, cic or tsc (particle mesh)")]
+    mass_assignment: Option<MassAssignment>,
+    #[arg(long, value_parser = parse_value::<MeshBoundary>, help = "isolated or periodic (particle mesh)")]
+    mesh_boundary: Option<MeshBoundary>,
+    #[arg(
+        long,
+        help = "Side of the particle mesh's cube about the origin (0 to fit the particles)"
+    )]
+    mesh_box_size: Option<f32>,
+    #[arg(long, value_parser = parse_value::<Backend>, help = "any, vulkan, metal, dx12 or gl")]
+    backend: Option<Backend>,
+    #[arg(
+        long,
+        alias = "fallback",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Only accept a software adapter (`=false` to override a scenario)"
+    )]
+    force_fallback_adapter: Option<bool>,
+    #[arg(long, help = "Steps between automatic checkpoints (0 to disable)")]
+    checkpoint_interval: Option<u32>,
+    #[arg(
+        long,
+        help = "Damped steps run before the simulation starts (0 to disable)"
+    )]
+    relaxation_steps: Option<u32>,
+    #[arg(
+        long,
+        help = "Rate at which velocities about the centre of mass decay while relaxing [1/s]"
+    )]
+    relaxation_damping: Option<f32>,
+}
+
+impl SettingsOverrides {
+    fn apply(&self, settings: &mut Settings) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(value) = self.$field {
+                        settings.$field = value;
+                    }
+                )*
+            };
+        }
+        apply!(
+            display_width,
+            display_height,
+            pixel_size,
+            gravitational_constant,
+            time_step,
+            smoothing_length,
+            ghost_mass,
+            ghost_stack_visible_limit,
+            blur_radius,
+            opening_angle,
+            integrator,
+            solver,
+            tile_size,
+            mesh_size,
+            mass_assignment,
+            mesh_boundary,
+            mesh_box_size,
+            backend,
+            force_fallback_adapter,
+            checkpoint_interval,
+            relaxation_steps,
+            relaxation_damping
+        );
+    }
+}
+
+// Option values are spelled as in scenario files (e.g. `velocity_verlet`)
+fn parse_value<T: DeserializeOwned>(value: &str) -> std::result::Result<T, String> {
+    T::deserialize(StrDeserializer::<serde::de::value::Error>::new(value))
+        .map_err(|error| error.to_string())
+}
 
 fn main() {
     env_logger::init();
-    pollster::block_on(start());
+    let cli = Cli::parse();
+    if let Err(error) = pollster::block_on(start(cli)) {
+        eprintln!("Error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+async fn start(cli: Cli) -> Result<()> {
+    match cli.command.unwrap_or(Command::Run(Setup::default())) {
+        Command::Run(setup) => {
+            let (settings, camera, init_conditions) = setup.load()?;
+            println!("Running simulation...");
+            run(settings, camera, init_conditions).await?;
+        }
+        Command::Render {
+            setup,
+            steps,
+            every,
+            output,
+        } => {
+            let (settings, camera, init_conditions) = setup.load()?;
+            println!("Rendering frames...");
+            capture(settings, camera, init_conditions, steps, every, &output).await?;
+        }
+        Command::Simulate {
+            setup,
+            steps,
+            every,
+            output,
+            cpu,
+        } => {
+            let (settings, camera, init_conditions) = setup.load()?;
+            if cpu {
+                println!("Simulating on the CPU...");
+                simulate_on_cpu(settings, init_conditions, steps, every, &output)?;
+            } else {
+                println!("Simulating...");
+                simulate(settings, camera, init_conditions, steps, every, &output).await?;
+            }
+        }
+        Command::Benchmark(setup) => {
+            let (settings, camera, init_conditions) = setup.load()?;
+            println!("Benchmarking force kernels...");
+            benchmark(settings, camera, init_conditions).await?;
+        }
+        Command::Resume { checkpoint } => {
+            let checkpoint = match checkpoint {
+                Some(checkpoint) => checkpoint,
+                None => latest_checkpoint(Path::new("."))?
+                    .ok_or("No checkpoint in the working directory")?,
+            };
+            println!("Resuming from {}...", checkpoint.display());
+            resume(&checkpoint).await?;
+        }
+        Command::Info { backend } => print_adapters(backend)?,
+        Command::Convert { input, output } => {
+            let snapshot = snapshot_format(&input)?.load(&input)?;
+            snapshot_format(&output)?.save(&snapshot, &output)?;
+            println!(
+                "Converted {} to {} ({} massive and {} ghost particles)",
+                input.display(),
+                output.display(),
+                snapshot.state.num_massive_particles(),
+                snapshot.state.num_ghost_particles()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+impl Setup {
+    fn load(&self) -> Result<(Settings, Camera, NBody)> {
+        let (mut settings, camera, init_conditions) = match &self.scenario {
+            Some(path) => {
+                println!("Loading scenario {}...", path.display());
+                let scenario = Scenario::load(path)?;
+                println!("Generating initial conditions...");
+                let init_conditions = scenario.initial_conditions();
+                (scenario.settings, scenario.camera, init_conditions)
+            }
+            None => {
+                println!("Initialising settings...");
+                let settings = init_settings();
+                println!("Initialising camera...");
+                let camera = init_camera();
+                println!("Generating initial conditions...");
+                let init_conditions = init_conditions(settings.gravitational_constant);
+                (settings, camera, init_conditions)
+            }
+        };
+
+        self.overrides.apply(&mut settings);
+        settings
+            .validate()
+            .map_err(|message| format!("Invalid settings: {}", message))?;
+
+        Ok((settings, camera, init_conditions))
+    }
+}
+
+fn print_adapters(backend: Backend) -> Result<()> {
+    let adapters = adapters(backend);
+    if adapters.is_empty() {
+        return Err(format!("No {:?} adapter found", backend).into());
+    }
+
+    for (info, limits) in adapters {
+        println!("{} ({:?}, {:?})", info.name, info.backend, info.device_type);
+        println!("    Driver: {} {}", info.driver, info.driver_info);
+        println!("    Max buffer size: {} bytes", limits.max_buffer_size);
+        println!(
+            "    Max storage buffer binding size: {} bytes",
+            limits.max_storage_buffer_binding_size
+        );
+        println!(
+            "    Max storage buffers per shader stage: {}",
+            limits.max_storage_buffers_per_shader_stage
+        );
+        println!(
+            "    Max compute workgroup storage size: {} bytes",
+            limits.max_compute_workgroup_storage_size
+        );
+        println!(
+            "    Max compute invocations per workgroup: {}",
+            limits.max_compute_invocations_per_workgroup
+        );
+        println!(
+            "    Max compute workgroups per dimension: {}",
+            limits.max_compute_workgroups_per_dimension
+        );
+    }
+
+    Ok(())
 }
 
-async fn start() {
-    println!("Initialising settings...");
-    let settings = init_settings();
-    println!("Initialising camera...");
-    let camera = init_camera();
-    println!("Generating initial conditions...");
-    let init_conditions = init_conditions(settings.gravitational_constant);
-    println!("Initial conditions generated.\nRunning simulation...");
-    run(settings, camera, init_conditions).await;
+fn snapshot_format(path: &Path) -> Result<SnapshotFormat> {
+    SnapshotFormat::from_path(path)
+        .ok_or_else(|| format!("Unknown snapshot format of {}", path.display()).into())
 }
 
 fn init_settings() -> Settings {
@@ -27,6 +350,19 @@ fn init_settings() -> Settings {
         ghost_mass: 1.0,                // [kg]
         ghost_stack_visible_limit: 4.0, // This many ghosts on top of each other will have an alpha of 1.0
         blur_radius: 5.0,               // [pixels]
+        opening_angle: 0.5,             // Barnes-Hut tree opening angle [radians]
+        integrator: Integrator::Leapfrog,
+        solver: Solver::Direct,
+        tile_size: 64,
+        mesh_size: 64, // [cells per side]
+        mass_assignment: MassAssignment::Cic,
+        mesh_boundary: MeshBoundary::Isolated,
+        mesh_box_size: 0.0, // Fit the mesh to the particles
+        backend: Backend::Any,
+        force_fallback_adapter: false, // Only use a software adapter
+        checkpoint_interval: 0,        // Steps between checkpoints (0 to disable)
+        relaxation_steps: 0,           // Damped steps before the simulation starts (0 to disable)
+        relaxation_damping: 0.0, // Rate at which velocities about the centre of mass decay while relaxing [1/s]
     }
 }
 
@@ -1,6 +1,6 @@
 use wgpu::util::DeviceExt;
 
-use crate::{Camera, NBody, Settings};
+use crate::{Camera, MeshBoundary, NBody, Settings, Solver};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -33,25 +33,79 @@ const INDICES: &[u16] = &[
     1, 2, 3, //
 ];
 
+// The 30-bit Morton keys are sorted four bits at a time
+pub const RADIX_SORT_PASSES: usize = 8;
+
+// FFTs of the mesh, each made of one pass per axis: (inverse, of the Green's function)
+pub const MESH_FFT_PASSES: [(bool, bool); 3] = [(false, false), (true, false), (false, true)];
+
 pub struct Memory {
     // Counts
     pub num_massive_particles: u32,
     pub num_ghost_particles: u32,
     pub num_indices: u32,
+    pub tile_size: u32, // Bodies held in workgroup memory by the tiled direct sum
 
     // Uniforms
     pub settings_uniform: wgpu::Buffer,
     pub camera_uniform: wgpu::Buffer,
+    pub step_uniform: wgpu::Buffer,
+    pub counts_uniform: wgpu::Buffer,
 
     // Particles
     pub massive_positions_and_masses_buffer: wgpu::Buffer,
     pub massive_velocities_and_masses_buffer: wgpu::Buffer,
     pub massive_forces_and_masses_buffer: wgpu::Buffer,
+    pub massive_jerks_buffer: wgpu::Buffer,
+
+    // Particles at the start of the step (used by the multi-stage integrators)
+    pub massive_previous_positions_and_masses_buffer: wgpu::Buffer,
+    pub massive_previous_velocities_and_masses_buffer: wgpu::Buffer,
+    pub massive_previous_forces_buffer: wgpu::Buffer,
+    pub massive_previous_jerks_buffer: wgpu::Buffer,
 
     // Ghosts
     pub ghost_positions_and_kinds_buffer: wgpu::Buffer,
     pub ghost_velocities_and_kinds_buffer: wgpu::Buffer,
     pub ghost_forces_and_kinds_buffer: wgpu::Buffer,
+    pub ghost_jerks_buffer: wgpu::Buffer,
+
+    // Ghosts at the start of the step (used by the multi-stage integrators)
+    pub ghost_previous_positions_and_kinds_buffer: wgpu::Buffer,
+    pub ghost_previous_velocities_and_kinds_buffer: wgpu::Buffer,
+    pub ghost_previous_forces_buffer: wgpu::Buffer,
+    pub ghost_previous_jerks_buffer: wgpu::Buffer,
+
+    // Solver whose buffers below were allocated (the others' are placeholders)
+    pub solver: Solver,
+
+    // Barnes-Hut tree (rebuilt from the massive particles before every tree force evaluation)
+    pub tree_bounds_buffer: wgpu::Buffer,
+    pub tree_keys_buffer: wgpu::Buffer,
+    pub tree_indices_buffer: wgpu::Buffer,
+    pub tree_swap_keys_buffer: wgpu::Buffer,
+    pub tree_swap_indices_buffer: wgpu::Buffer,
+    pub tree_histograms_buffer: wgpu::Buffer,
+    pub tree_radix_passes_uniform: wgpu::Buffer,
+    pub tree_radix_pass_stride: wgpu::BufferAddress,
+    pub tree_nodes_buffer: wgpu::Buffer,
+    pub tree_parents_buffer: wgpu::Buffer, // Parent of every internal node, then of every leaf
+    pub tree_visits_buffer: wgpu::Buffer,  // Children of each internal node whose moments are done
+    pub tree_moments_buffer: wgpu::Buffer,
+    pub tree_sizes_buffer: wgpu::Buffer,
+
+    // Particle mesh (masses, then potential, on a grid zero-padded to twice the mesh size unless it is periodic)
+    pub mesh_size: u32,
+    pub mesh_boundary: MeshBoundary,
+    pub mesh_padded_size: u32,
+    pub mesh_uniform: wgpu::Buffer,
+    pub mesh_fft_passes_uniform: wgpu::Buffer,
+    pub mesh_fft_pass_stride: wgpu::BufferAddress,
+    pub mesh_bounds_buffer: wgpu::Buffer,
+    pub mesh_frame_buffer: wgpu::Buffer, // Cell size, G and smoothing length of the current Green's function, and origin
+    pub mesh_masses_buffer: wgpu::Buffer,
+    pub mesh_potential_buffer: wgpu::Buffer,
+    pub mesh_green_buffer: wgpu::Buffer,
 
     // Textures
     pub display_texture: wgpu::Texture,
@@ -61,11 +115,12 @@ pub struct Memory {
     pub display_sampler: wgpu::Sampler,
 
     // Rendering
+    pub ghost_pixels_buffer: wgpu::Buffer, // Ghosts per pixel and colour channel
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
 }
 
-impl<'a> Memory {
+impl Memory {
     pub fn new(
         settings: &Settings,
         camera: &Camera,
@@ -85,42 +140,83 @@ impl<'a> Memory {
             contents: bytemuck::cast_slice(&camera.as_slice()),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        let step_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Step Uniform"),
+            // [kick fraction, drift fraction, damping, -, damping frame velocity xyz, -]
+            contents: bytemuck::cast_slice(&[1.0f32, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
 
-        // Particle data
+        // Particle data (the current buffers are copied from by the multi-stage integrators and read back for output)
         let num_massive_particles = initial_conditions.num_massive_particles() as u32;
         let init_massive_positions_and_masses_data = initial_conditions
             .massive_positions()
             .iter()
             .zip(initial_conditions.massive_masses().iter())
-            .map(|([px, py, pz], mass)| [*px, *py, *pz, *mass])
-            .flatten()
+            .flat_map(|([px, py, pz], mass)| [*px, *py, *pz, *mass])
             .collect::<Vec<f32>>();
         let massive_positions_and_masses_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Positions and Masses Buffer"),
-                contents: bytemuck::cast_slice(&init_massive_positions_and_masses_data),
-                usage: wgpu::BufferUsages::STORAGE,
+                contents: bytemuck::cast_slice(&Self::pad_particle_data(
+                    init_massive_positions_and_masses_data,
+                )),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
         let init_massive_velocities_and_masses_data = initial_conditions
             .massive_velocities()
             .iter()
             .zip(initial_conditions.massive_masses().iter())
-            .map(|([vx, vy, vz], mass)| [*vx, *vy, *vz, *mass])
-            .flatten()
+            .flat_map(|([vx, vy, vz], mass)| [*vx, *vy, *vz, *mass])
             .collect::<Vec<f32>>();
         let massive_velocities_and_masses_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Velocities and Masses Buffer"),
-                contents: bytemuck::cast_slice(&init_massive_velocities_and_masses_data),
-                usage: wgpu::BufferUsages::STORAGE,
+                contents: bytemuck::cast_slice(&Self::pad_particle_data(
+                    init_massive_velocities_and_masses_data,
+                )),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
-        let init_massive_forces_and_masses_data = vec![0.0; (num_massive_particles * 4) as usize];
+        let init_massive_forces_and_masses_data =
+            vec![0.0f32; (num_massive_particles.max(1) * 4) as usize];
         let massive_forces_and_masses_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Forces and Masses Buffer"),
                 contents: bytemuck::cast_slice(&init_massive_forces_and_masses_data),
-                usage: wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
+        let massive_jerks_buffer =
+            Self::init_storage_buffer(device, "Massive Jerks Buffer", num_massive_particles, true);
+        let massive_previous_positions_and_masses_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Positions and Masses Buffer",
+            num_massive_particles,
+            false,
+        );
+        let massive_previous_velocities_and_masses_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Velocities and Masses Buffer",
+            num_massive_particles,
+            false,
+        );
+        let massive_previous_forces_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Forces Buffer",
+            num_massive_particles,
+            false,
+        );
+        let massive_previous_jerks_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Jerks Buffer",
+            num_massive_particles,
+            false,
+        );
 
         // Ghost data
         let num_ghost_particles = initial_conditions.num_ghost_particles() as u32;
@@ -128,35 +224,179 @@ impl<'a> Memory {
             .ghost_positions()
             .iter()
             .zip(initial_conditions.ghost_kinds().iter())
-            .map(|([px, py, pz], kind)| [*px, *py, *pz, *kind])
-            .flatten()
+            .flat_map(|([px, py, pz], kind)| [*px, *py, *pz, *kind])
             .collect::<Vec<f32>>();
         let ghost_positions_and_kinds_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Positions and Kinds Buffer"),
-                contents: bytemuck::cast_slice(&init_ghost_positions_and_kinds_data),
-                usage: wgpu::BufferUsages::STORAGE,
+                contents: bytemuck::cast_slice(&Self::pad_particle_data(
+                    init_ghost_positions_and_kinds_data,
+                )),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
         let init_ghost_velocities_and_kinds_data = initial_conditions
             .ghost_velocities()
             .iter()
             .zip(initial_conditions.ghost_kinds().iter())
-            .map(|([vx, vy, vz], kind)| [*vx, *vy, *vz, *kind])
-            .flatten()
+            .flat_map(|([vx, vy, vz], kind)| [*vx, *vy, *vz, *kind])
             .collect::<Vec<f32>>();
         let ghost_velocities_and_kinds_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Velocities and Kinds Buffer"),
-                contents: bytemuck::cast_slice(&init_ghost_velocities_and_kinds_data),
-                usage: wgpu::BufferUsages::STORAGE,
+                contents: bytemuck::cast_slice(&Self::pad_particle_data(
+                    init_ghost_velocities_and_kinds_data,
+                )),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
-        let init_ghost_forces_and_kinds_data = vec![0.0; (num_ghost_particles * 4) as usize];
+        let init_ghost_forces_and_kinds_data =
+            vec![0.0f32; (num_ghost_particles.max(1) * 4) as usize];
         let ghost_forces_and_kinds_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Forces and Kinds Buffer"),
                 contents: bytemuck::cast_slice(&init_ghost_forces_and_kinds_data),
-                usage: wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
             });
+        let ghost_jerks_buffer =
+            Self::init_storage_buffer(device, "Ghost Jerks Buffer", num_ghost_particles, true);
+        let ghost_previous_positions_and_kinds_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Positions and Kinds Buffer",
+            num_ghost_particles,
+            false,
+        );
+        let ghost_previous_velocities_and_kinds_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Velocities and Kinds Buffer",
+            num_ghost_particles,
+            false,
+        );
+        let ghost_previous_forces_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Ghost Forces Buffer",
+            num_ghost_particles,
+            false,
+        );
+        let ghost_previous_jerks_buffer = Self::init_storage_buffer(
+            device,
+            "Previous Ghost Jerks Buffer",
+            num_ghost_particles,
+            false,
+        );
+
+        // Shaders bounds-check against the real counts (the buffers hold at least one particle)
+        let counts_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Counts Uniform"),
+            contents: bytemuck::cast_slice(&[num_massive_particles, num_ghost_particles, 0, 0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        // Tree data (only sized for the tree solver)
+        let num_tree_bodies = match settings.solver {
+            Solver::Tree => num_massive_particles as usize,
+            _ => 0,
+        };
+        let num_tree_nodes = num_tree_bodies.max(2) - 1;
+        let num_radix_blocks = num_tree_bodies.div_ceil(64);
+        let tree_bounds_buffer = Self::init_scratch_buffer(device, "Tree Bounds Buffer", 8 * 4);
+        let tree_keys_buffer =
+            Self::init_scratch_buffer(device, "Tree Keys Buffer", num_tree_bodies * 4);
+        let tree_indices_buffer =
+            Self::init_scratch_buffer(device, "Tree Indices Buffer", num_tree_bodies * 4);
+        let tree_swap_keys_buffer =
+            Self::init_scratch_buffer(device, "Tree Swap Keys Buffer", num_tree_bodies * 4);
+        let tree_swap_indices_buffer =
+            Self::init_scratch_buffer(device, "Tree Swap Indices Buffer", num_tree_bodies * 4);
+        let tree_histograms_buffer =
+            Self::init_scratch_buffer(device, "Tree Histograms Buffer", 16 * num_radix_blocks * 4);
+        let tree_radix_pass_stride = (device.limits().min_uniform_buffer_offset_alignment as u64)
+            .max(16) as wgpu::BufferAddress;
+        let init_tree_radix_passes_data = (0..RADIX_SORT_PASSES)
+            .flat_map(|pass| {
+                let mut entry = vec![0u32; tree_radix_pass_stride as usize / 4];
+                entry[0] = 4 * pass as u32; // Shift
+                entry
+            })
+            .collect::<Vec<u32>>();
+        let tree_radix_passes_uniform =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Tree Radix Passes Uniform"),
+                contents: bytemuck::cast_slice(&init_tree_radix_passes_data),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let tree_nodes_buffer =
+            Self::init_scratch_buffer(device, "Tree Nodes Buffer", num_tree_nodes * 16);
+        let tree_parents_buffer = Self::init_scratch_buffer(
+            device,
+            "Tree Parents Buffer",
+            (num_tree_nodes + num_tree_bodies) * 4,
+        );
+        let tree_visits_buffer =
+            Self::init_scratch_buffer(device, "Tree Visits Buffer", num_tree_nodes * 4);
+        let tree_moments_buffer =
+            Self::init_scratch_buffer(device, "Tree Moments Buffer", num_tree_nodes * 16);
+        let tree_sizes_buffer =
+            Self::init_scratch_buffer(device, "Tree Sizes Buffer", num_tree_nodes * 4);
+
+        // Mesh data (only sized for the particle-mesh solver)
+        let mesh_size = settings.mesh_size;
+        let periodic = settings.mesh_boundary == MeshBoundary::Periodic;
+        let mesh_padded_size = if periodic { mesh_size } else { 2 * mesh_size };
+        let (mesh_cells, padded_mesh_cells) = match settings.solver {
+            Solver::ParticleMesh => (
+                (mesh_size as usize).pow(3),
+                (mesh_padded_size as usize).pow(3),
+            ),
+            _ => (0, 0),
+        };
+        let mesh_mass_scale =
+            Self::mesh_mass_scale(initial_conditions.massive_masses().iter().copied());
+        let mesh_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Uniform"),
+            contents: bytemuck::cast_slice(&[
+                mesh_size,
+                mesh_padded_size,
+                settings.mass_assignment as u32,
+                mesh_mass_scale.to_bits(),
+                settings.mesh_box_size.to_bits(),
+                periodic as u32,
+                0,
+                0,
+            ]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let mesh_fft_pass_stride = tree_radix_pass_stride;
+        let init_mesh_fft_passes_data = MESH_FFT_PASSES
+            .into_iter()
+            .flat_map(|(inverse, green)| {
+                (0..3).flat_map(move |axis| {
+                    let mut entry = vec![0u32; mesh_fft_pass_stride as usize / 4];
+                    entry[0] = axis;
+                    entry[1] = inverse as u32;
+                    entry[2] = green as u32;
+                    entry
+                })
+            })
+            .collect::<Vec<u32>>();
+        let mesh_fft_passes_uniform =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Mesh FFT Passes Uniform"),
+                contents: bytemuck::cast_slice(&init_mesh_fft_passes_data),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let mesh_bounds_buffer = Self::init_scratch_buffer(device, "Mesh Bounds Buffer", 8 * 4);
+        let mesh_frame_buffer = Self::init_scratch_buffer(device, "Mesh Frame Buffer", 8 * 4);
+        let mesh_masses_buffer =
+            Self::init_scratch_buffer(device, "Mesh Masses Buffer", mesh_cells * 4);
+        let mesh_potential_buffer =
+            Self::init_scratch_buffer(device, "Mesh Potential Buffer", padded_mesh_cells * 8);
+        let mesh_green_buffer =
+            Self::init_scratch_buffer(device, "Mesh Green Buffer", padded_mesh_cells * 8);
 
         // Display texture
         let texture_size = wgpu::Extent3d {
@@ -204,6 +444,11 @@ impl<'a> Memory {
         });
 
         // Rendering data
+        let ghost_pixels_buffer = Self::init_scratch_buffer(
+            device,
+            "Ghost Pixels Buffer",
+            (texture_size.width * texture_size.height) as usize * 3 * 4,
+        );
         let num_indices = INDICES.len() as u32;
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
@@ -220,21 +465,115 @@ impl<'a> Memory {
             num_massive_particles,
             num_ghost_particles,
             num_indices,
+            tile_size: settings.tile_size,
             settings_uniform,
             camera_uniform,
+            step_uniform,
+            counts_uniform,
             massive_positions_and_masses_buffer,
             massive_velocities_and_masses_buffer,
             massive_forces_and_masses_buffer,
+            massive_jerks_buffer,
+            massive_previous_positions_and_masses_buffer,
+            massive_previous_velocities_and_masses_buffer,
+            massive_previous_forces_buffer,
+            massive_previous_jerks_buffer,
             ghost_positions_and_kinds_buffer,
             ghost_velocities_and_kinds_buffer,
             ghost_forces_and_kinds_buffer,
+            ghost_jerks_buffer,
+            ghost_previous_positions_and_kinds_buffer,
+            ghost_previous_velocities_and_kinds_buffer,
+            ghost_previous_forces_buffer,
+            ghost_previous_jerks_buffer,
+            solver: settings.solver,
+            tree_bounds_buffer,
+            tree_keys_buffer,
+            tree_indices_buffer,
+            tree_swap_keys_buffer,
+            tree_swap_indices_buffer,
+            tree_histograms_buffer,
+            tree_radix_passes_uniform,
+            tree_radix_pass_stride,
+            tree_nodes_buffer,
+            tree_parents_buffer,
+            tree_visits_buffer,
+            tree_moments_buffer,
+            tree_sizes_buffer,
+            mesh_size,
+            mesh_boundary: settings.mesh_boundary,
+            mesh_padded_size,
+            mesh_uniform,
+            mesh_fft_passes_uniform,
+            mesh_fft_pass_stride,
+            mesh_bounds_buffer,
+            mesh_frame_buffer,
+            mesh_masses_buffer,
+            mesh_potential_buffer,
+            mesh_green_buffer,
             display_texture,
             secondary_texture,
             display_view,
             secondary_view,
             display_sampler,
+            ghost_pixels_buffer,
             vertex_buffer,
             index_buffer,
         }
     }
+
+    // Fixed-point units per unit mass of the deposited masses, so that the whole mass sums to 2^31 (which leaves
+    // headroom for rounding in the u32 sums). Masses never change during a run, so this only has to be recomputed
+    // when a checkpoint replaces them
+    pub fn mesh_mass_scale(masses: impl Iterator<Item = f32>) -> f32 {
+        let total_mass = masses.map(|m| m as f64).sum::<f64>();
+        if total_mass > 0.0 {
+            (2.0f64.powi(31) / total_mass) as f32
+        } else {
+            1.0
+        }
+    }
+
+    // Zero-sized bindings are invalid, so empty particle sets are stored as one unused zero particle
+    fn pad_particle_data(mut data: Vec<f32>) -> Vec<f32> {
+        if data.is_empty() {
+            data.resize(4, 0.0);
+        }
+        data
+    }
+
+    // Zero-initialised buffer of `num_particles` vec4s (at least one), copied from only if `copy_source`
+    fn init_storage_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        num_particles: u32,
+        copy_source: bool,
+    ) -> wgpu::Buffer {
+        let mut usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+        if copy_source {
+            usage |= wgpu::BufferUsages::COPY_SRC;
+        }
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (num_particles.max(1) as usize * std::mem::size_of::<[f32; 4]>())
+                as wgpu::BufferAddress,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Whether the buffers of `solver` were allocated (the direct sums need none of their own)
+    pub fn has_solver_buffers(&self, solver: Solver) -> bool {
+        matches!(solver, Solver::Direct | Solver::TiledDirect) || solver == self.solver
+    }
+
+    // Zero-initialised scratch buffer for the solvers and renderer (at least one vec4, the largest element bound)
+    fn init_scratch_buffer(device: &wgpu::Device, label: &str, size: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size.max(16) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
 }
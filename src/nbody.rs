@@ -1,15 +1,24 @@
 use rand::Rng;
 
+use crate::{spherical::SphericalModel, Orientation};
+
+#[derive(Clone, Debug)]
 pub struct NBody {
     // Massive particles
-    massive_positions: Vec<[f32; 3]>,
-    massive_velocities: Vec<[f32; 3]>,
-    massive_masses: Vec<f32>,
+    pub(crate) massive_positions: Vec<[f32; 3]>,
+    pub(crate) massive_velocities: Vec<[f32; 3]>,
+    pub(crate) massive_masses: Vec<f32>,
 
     // Ghost particles
-    ghost_positions: Vec<[f32; 3]>,
-    ghost_velocities: Vec<[f32; 3]>,
-    ghost_kinds: Vec<f32>,
+    pub(crate) ghost_positions: Vec<[f32; 3]>,
+    pub(crate) ghost_velocities: Vec<[f32; 3]>,
+    pub(crate) ghost_kinds: Vec<f32>,
+}
+
+impl Default for NBody {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NBody {
@@ -26,26 +35,6 @@ impl NBody {
     }
 
     pub fn is_valid(&self) -> bool {
-        let num_particles = self.massive_positions.len();
-        let num_ghosts = self.ghost_positions.len();
-
-        if self.massive_positions.is_empty() {
-            return false;
-        }
-        if self.ghost_positions.is_empty() {
-            return false;
-        }
-
-        // GPU buffer size must be a multiple of 64
-        if num_particles % 64 != 0 {
-            return false;
-        }
-
-        // GPU buffer size must be a multiple of 64
-        if num_ghosts % 64 != 0 {
-            return false;
-        }
-
         // Check that the massive particle arrays are the same length
         if self.massive_positions.len() != self.massive_masses.len()
             || self.massive_velocities.len() != self.massive_masses.len()
@@ -106,6 +95,13 @@ impl NBody {
         self.massive_masses.push(mass);
     }
 
+    pub fn add_ghost_particle(&mut self, position: [f32; 3], velocity: [f32; 3], kind: f32) {
+        self.ghost_positions.push(position);
+        self.ghost_velocities.push(velocity);
+        self.ghost_kinds.push(kind);
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn add_massive_disc(
         &mut self,
         rng: &mut impl Rng,
@@ -115,6 +111,31 @@ impl NBody {
         radius: f32,
         disc_mass: f32,
         num_particles: usize,
+    ) {
+        self.add_massive_disc_oriented(
+            rng,
+            grav_const,
+            centre,
+            drift,
+            Orientation::default(),
+            radius,
+            disc_mass,
+            num_particles,
+        );
+    }
+
+    // `add_massive_disc` turned to `orientation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_massive_disc_oriented(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        orientation: Orientation,
+        radius: f32,
+        disc_mass: f32,
+        num_particles: usize,
     ) {
         debug_assert!(radius > 0.0);
         debug_assert!(num_particles > 0);
@@ -131,11 +152,13 @@ impl NBody {
 
             let dx = r * theta.cos();
             let dy = r * theta.sin();
-            let position = [centre[0] + dx, centre[1] + dy, centre[2]];
+            let offset = orientation.position([dx, dy, 0.0]);
+            let position = [0, 1, 2].map(|k| centre[k] + offset[k]);
 
             let vx = angular_velocity * theta.sin();
             let vy = angular_velocity * -theta.cos();
-            let velocity = [vx + drift[0], vy + drift[1], drift[2]];
+            let relative = orientation.velocity([vx, vy, 0.0]);
+            let velocity = [0, 1, 2].map(|k| drift[k] + relative[k]);
 
             self.massive_positions.push(position);
             self.massive_velocities.push(velocity);
@@ -143,6 +166,7 @@ impl NBody {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_massive_system(
         &mut self,
         rng: &mut impl Rng,
@@ -153,6 +177,33 @@ impl NBody {
         centre_mass: f32,
         disc_mass: f32,
         num_particles: usize,
+    ) {
+        self.add_massive_system_oriented(
+            rng,
+            _grav_const,
+            centre,
+            drift,
+            Orientation::default(),
+            radius,
+            centre_mass,
+            disc_mass,
+            num_particles,
+        );
+    }
+
+    // `add_massive_system` turned to `orientation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_massive_system_oriented(
+        &mut self,
+        rng: &mut impl Rng,
+        _grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        orientation: Orientation,
+        radius: f32,
+        centre_mass: f32,
+        disc_mass: f32,
+        num_particles: usize,
     ) {
         debug_assert!(radius > 0.0);
         debug_assert!(num_particles > 0);
@@ -182,8 +233,10 @@ impl NBody {
             let vx = angular_velocity * theta.sin();
             let vy = angular_velocity * -theta.cos();
 
-            let position = [centre[0] + dx, centre[1] + dy, centre[2]];
-            let velocity = [vx + drift[0], vy + drift[1], drift[2]];
+            let offset = orientation.position([dx, dy, 0.0]);
+            let position = [0, 1, 2].map(|k| centre[k] + offset[k]);
+            let relative = orientation.velocity([vx, vy, 0.0]);
+            let velocity = [0, 1, 2].map(|k| drift[k] + relative[k]);
 
             self.massive_positions.push(position);
             self.massive_velocities.push(velocity);
@@ -191,6 +244,7 @@ impl NBody {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_massive_system2(
         &mut self,
         rng: &mut impl Rng,
@@ -200,6 +254,31 @@ impl NBody {
         radius: f32,
         disc_mass: f32,
         num_particles: usize,
+    ) {
+        self.add_massive_system2_oriented(
+            rng,
+            _grav_const,
+            centre,
+            drift,
+            Orientation::default(),
+            radius,
+            disc_mass,
+            num_particles,
+        );
+    }
+
+    // `add_massive_system2` turned to `orientation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_massive_system2_oriented(
+        &mut self,
+        rng: &mut impl Rng,
+        _grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        orientation: Orientation,
+        radius: f32,
+        disc_mass: f32,
+        num_particles: usize,
     ) {
         debug_assert!(radius > 0.0);
         debug_assert!(num_particles > 0);
@@ -226,8 +305,10 @@ impl NBody {
             let vx = angular_velocity * theta.sin();
             let vy = angular_velocity * -theta.cos();
 
-            let position = [centre[0] + dx, centre[1] + dy, centre[2]];
-            let velocity = [vx + drift[0], vy + drift[1], drift[2]];
+            let offset = orientation.position([dx, dy, 0.0]);
+            let position = [0, 1, 2].map(|k| centre[k] + offset[k]);
+            let relative = orientation.velocity([vx, vy, 0.0]);
+            let velocity = [0, 1, 2].map(|k| drift[k] + relative[k]);
 
             self.massive_positions.push(position);
             self.massive_velocities.push(velocity);
@@ -235,6 +316,222 @@ impl NBody {
         }
     }
 
+    // Plummer (1911) sphere sampled with the method of Aarseth, Henon & Wielen (1974): radii from the
+    // cumulative mass profile and speeds from the exact isotropic distribution function by rejection,
+    // then shifted so that the sampled centre of mass sits at `centre` moving with `drift`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_plummer_sphere(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        scale_radius: f32,
+        total_mass: f32,
+        num_particles: usize,
+    ) {
+        debug_assert!(scale_radius > 0.0);
+        debug_assert!(total_mass > 0.0);
+        debug_assert!(num_particles > 0);
+
+        let a = scale_radius as f64;
+        let velocity_scale = (grav_const as f64 * total_mass as f64 / a).sqrt();
+
+        let mut positions = Vec::with_capacity(num_particles);
+        let mut velocities = Vec::with_capacity(num_particles);
+        for _ in 0..num_particles {
+            // Radius enclosing a uniformly drawn fraction of the mass, short of the last 0.1% (r < 38.7 a) whose
+            // few far-flung bodies would stretch a fitted mesh or tree
+            let mass_fraction: f64 = rng.gen_range(f64::EPSILON..0.999);
+            let r = a / (mass_fraction.powf(-2.0 / 3.0) - 1.0).sqrt();
+
+            // Fraction q of the escape speed, drawn from g(q) = q^2 (1 - q^2)^(7/2) (at most 0.092)
+            let q = loop {
+                let q: f64 = rng.gen_range(0.0..1.0);
+                if rng.gen_range(0.0..0.1) < q * q * (1.0 - q * q).powf(3.5) {
+                    break q;
+                }
+            };
+            let escape_speed =
+                std::f64::consts::SQRT_2 * velocity_scale * (1.0 + r * r / (a * a)).powf(-0.25);
+
+            positions.push(random_direction(rng).map(|x| x * r));
+            velocities.push(random_direction(rng).map(|x| x * q * escape_speed));
+        }
+
+        self.add_centred(
+            centre,
+            drift,
+            Orientation::default(),
+            total_mass,
+            &positions,
+            &velocities,
+        );
+    }
+
+    // Hernquist (1990) sphere, rho ~ 1 / (r (r + a)^3), a model of galactic bulges and halos
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_hernquist_sphere(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        scale_radius: f32,
+        total_mass: f32,
+        num_particles: usize,
+    ) {
+        let model = SphericalModel::hernquist();
+        self.add_spherical_model(
+            rng,
+            &model,
+            grav_const,
+            centre,
+            drift,
+            scale_radius,
+            total_mass,
+            num_particles,
+        );
+    }
+
+    // Navarro-Frenk-White dark matter halo with a virial radius of `concentration` scale radii, beyond which the
+    // density is tapered exponentially (`total_mass` includes the taper)
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_nfw_halo(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        scale_radius: f32,
+        concentration: f32,
+        total_mass: f32,
+        num_particles: usize,
+    ) {
+        debug_assert!(concentration > 0.0);
+
+        let model = SphericalModel::nfw(concentration as f64);
+        self.add_spherical_model(
+            rng,
+            &model,
+            grav_const,
+            centre,
+            drift,
+            scale_radius,
+            total_mass,
+            num_particles,
+        );
+    }
+
+    // King (1966) model of a tidally truncated globular cluster, with `central_potential` W0 setting the
+    // concentration (tidal radius over King radius, from about 3 at W0 = 1 to 300 at W0 = 12)
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_king_cluster(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        king_radius: f32,
+        central_potential: f32,
+        total_mass: f32,
+        num_particles: usize,
+    ) {
+        debug_assert!(central_potential > 0.0);
+
+        let model = SphericalModel::king(central_potential as f64);
+        self.add_spherical_model(
+            rng,
+            &model,
+            grav_const,
+            centre,
+            drift,
+            king_radius,
+            total_mass,
+            num_particles,
+        );
+    }
+
+    // Exponential disc, Sigma ~ exp(-R / scale_length), with a sech^2 vertical profile of `scale_height`, rotating
+    // like the other discs about the z axis. The rotation curve comes from the disc mass enclosed within each radius
+    // plus `external_mass(r)`, the mass of any other spherical components (such as a halo) within r of the centre,
+    // and the velocity dispersions follow the epicyclic approximation:
+    //   radial from the Toomre stability parameter, Q = sigma_R kappa / (3.36 G Sigma)
+    //   azimuthal from sigma_phi^2 / sigma_R^2 = kappa^2 / (4 Omega^2)
+    //   vertical from the isothermal sheet, sigma_z^2 = pi G Sigma z0
+    // with the mean rotation lagging the circular speed by the asymmetric drift
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_exponential_disc(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        scale_length: f32,
+        scale_height: f32,
+        disc_mass: f32,
+        toomre_q: f32,
+        external_mass: impl Fn(f32) -> f32,
+        num_particles: usize,
+    ) {
+        self.add_exponential_disc_oriented(
+            rng,
+            grav_const,
+            centre,
+            drift,
+            Orientation::default(),
+            scale_length,
+            scale_height,
+            disc_mass,
+            toomre_q,
+            external_mass,
+            num_particles,
+        );
+    }
+
+    // `add_exponential_disc` turned to `orientation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_exponential_disc_oriented(
+        &mut self,
+        rng: &mut impl Rng,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        orientation: Orientation,
+        scale_length: f32,
+        scale_height: f32,
+        disc_mass: f32,
+        toomre_q: f32,
+        external_mass: impl Fn(f32) -> f32,
+        num_particles: usize,
+    ) {
+        debug_assert!(scale_length > 0.0);
+        debug_assert!(scale_height > 0.0);
+        debug_assert!(disc_mass > 0.0);
+        debug_assert!(toomre_q > 0.0);
+        debug_assert!(num_particles > 0);
+
+        let (positions, velocities) = sample_exponential_disc(
+            rng,
+            grav_const,
+            scale_length,
+            scale_height,
+            disc_mass,
+            toomre_q,
+            external_mass,
+            num_particles,
+        );
+        self.add_centred(
+            centre,
+            drift,
+            orientation,
+            disc_mass,
+            &positions,
+            &velocities,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn add_ghost_field(
         &mut self,
         rng: &mut impl Rng,
@@ -244,6 +541,31 @@ impl NBody {
         centre_mass: f32,
         num_particles: usize,
         kind: f32,
+    ) {
+        self.add_ghost_field_oriented(
+            rng,
+            centre,
+            drift,
+            Orientation::default(),
+            radius,
+            centre_mass,
+            num_particles,
+            kind,
+        );
+    }
+
+    // `add_ghost_field` turned to `orientation`
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_ghost_field_oriented(
+        &mut self,
+        rng: &mut impl Rng,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        orientation: Orientation,
+        radius: f32,
+        centre_mass: f32,
+        num_particles: usize,
+        kind: f32,
     ) {
         debug_assert!(radius > 0.0);
         debug_assert!(num_particles > 0);
@@ -258,14 +580,16 @@ impl NBody {
 
             let dx = r * theta.cos();
             let dy = r * theta.sin();
-            let position = [centre[0] + dx, centre[1] + dy, centre[2]];
+            let offset = orientation.position([dx, dy, 0.0]);
+            let position = [0, 1, 2].map(|k| centre[k] + offset[k]);
 
             let f = r / radius;
             let angular_velocity = ((centre_mass * f) / r).sqrt();
             let vx = angular_velocity * theta.sin();
             let vy = angular_velocity * -theta.cos();
 
-            let velocity = [vx + drift[0], vy + drift[1], drift[2]];
+            let relative = orientation.velocity([vx, vy, 0.0]);
+            let velocity = [0, 1, 2].map(|k| drift[k] + relative[k]);
 
             self.ghost_positions.push(position);
             self.ghost_velocities.push(velocity);
@@ -273,3 +597,179 @@ impl NBody {
         }
     }
 }
+
+impl NBody {
+    #[allow(clippy::too_many_arguments)]
+    fn add_spherical_model(
+        &mut self,
+        rng: &mut impl Rng,
+        model: &SphericalModel,
+        grav_const: f32,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        scale_radius: f32,
+        total_mass: f32,
+        num_particles: usize,
+    ) {
+        debug_assert!(scale_radius > 0.0);
+        debug_assert!(total_mass > 0.0);
+        debug_assert!(num_particles > 0);
+
+        let (positions, velocities) = sample_spherical_model(
+            rng,
+            model,
+            grav_const,
+            scale_radius,
+            total_mass,
+            num_particles,
+        );
+        self.add_centred(
+            centre,
+            drift,
+            Orientation::default(),
+            total_mass,
+            &positions,
+            &velocities,
+        );
+    }
+
+    // Equal-mass particles turned to `orientation` and shifted so that their centre of mass sits at `centre` moving
+    // with `drift`
+    fn add_centred(
+        &mut self,
+        centre: [f32; 3],
+        drift: [f32; 3],
+        orientation: Orientation,
+        total_mass: f32,
+        positions: &[[f64; 3]],
+        velocities: &[[f64; 3]],
+    ) {
+        let num_particles = positions.len();
+        let mean = |vectors: &[[f64; 3]]| {
+            [0, 1, 2].map(|k| vectors.iter().map(|v| v[k]).sum::<f64>() / num_particles as f64)
+        };
+        let (mean_position, mean_velocity) = (mean(positions), mean(velocities));
+
+        self.massive_positions.reserve_exact(num_particles);
+        self.massive_velocities.reserve_exact(num_particles);
+        self.massive_masses.reserve_exact(num_particles);
+
+        for (position, velocity) in positions.iter().zip(velocities) {
+            let offset =
+                orientation.position([0, 1, 2].map(|k| (position[k] - mean_position[k]) as f32));
+            let relative =
+                orientation.velocity([0, 1, 2].map(|k| (velocity[k] - mean_velocity[k]) as f32));
+            self.massive_positions
+                .push([0, 1, 2].map(|k| centre[k] + offset[k]));
+            self.massive_velocities
+                .push([0, 1, 2].map(|k| drift[k] + relative[k]));
+            self.massive_masses.push(total_mass / num_particles as f32);
+        }
+    }
+}
+
+// Positions and velocities of particles about the origin
+pub(crate) type Samples = (Vec<[f64; 3]>, Vec<[f64; 3]>);
+
+// Isotropic positions and velocities drawn from a tabulated model (in units of G = M = scale radius = 1)
+pub(crate) fn sample_spherical_model(
+    rng: &mut impl Rng,
+    model: &SphericalModel,
+    grav_const: f32,
+    scale_radius: f32,
+    total_mass: f32,
+    num_particles: usize,
+) -> Samples {
+    let a = scale_radius as f64;
+    let velocity_scale = (grav_const as f64 * total_mass as f64 / a).sqrt();
+
+    let mut positions = Vec::with_capacity(num_particles);
+    let mut velocities = Vec::with_capacity(num_particles);
+    for _ in 0..num_particles {
+        let r = model.sample_radius(rng);
+        let speed = model.sample_speed(rng, r);
+        positions.push(random_direction(rng).map(|x| x * r * a));
+        velocities.push(random_direction(rng).map(|x| x * speed * velocity_scale));
+    }
+
+    (positions, velocities)
+}
+
+// The exponential disc of `NBody::add_exponential_disc`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn sample_exponential_disc(
+    rng: &mut impl Rng,
+    grav_const: f32,
+    scale_length: f32,
+    scale_height: f32,
+    disc_mass: f32,
+    toomre_q: f32,
+    external_mass: impl Fn(f32) -> f32,
+    num_particles: usize,
+) -> Samples {
+    let (g, h, z0, q) = (
+        grav_const as f64,
+        scale_length as f64,
+        scale_height as f64,
+        toomre_q as f64,
+    );
+    let central_density = disc_mass as f64 / (2.0 * std::f64::consts::PI * h * h);
+    let surface_density = |r: f64| central_density * (-r / h).exp();
+    let enclosed_mass = |r: f64| {
+        disc_mass as f64 * (1.0 - (1.0 + r / h) * (-r / h).exp()) + external_mass(r as f32) as f64
+    };
+
+    // kappa^2 = d(R^2 v_c^2) / dR / R^3, with R^2 v_c^2 = G M(R) R
+    let epicyclic_frequency2 = |r: f64| {
+        let dr = 1.0e-3 * r;
+        let l2 = |r: f64| g * enclosed_mass(r) * r;
+        (l2(r + dr) - l2(r - dr)) / (2.0 * dr * r.powi(3))
+    };
+    let radial_dispersion2 =
+        |r: f64| (3.36 * q * g * surface_density(r)).powi(2) / epicyclic_frequency2(r);
+
+    let mut positions = Vec::with_capacity(num_particles);
+    let mut velocities = Vec::with_capacity(num_particles);
+    for _ in 0..num_particles {
+        // R e^(-R / h) is a gamma distribution, the sum of two exponential deviates
+        let r = -h * (rng.gen_range(f64::EPSILON..1.0) * rng.gen_range(f64::EPSILON..1.0)).ln();
+        let theta = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+        let z = z0 * (2.0 * rng.gen_range(f64::EPSILON..1.0) - 1.0).atanh();
+
+        let circular_speed2 = g * enclosed_mass(r) / r;
+        let angular_frequency2 = circular_speed2 / (r * r);
+        let sigma_r2 = radial_dispersion2(r);
+        let sigma_phi2 = sigma_r2 * epicyclic_frequency2(r) / (4.0 * angular_frequency2);
+        let sigma_z2 = std::f64::consts::PI * g * surface_density(r) * z0;
+
+        // v_phi^2 = v_c^2 + sigma_R^2 (1 - sigma_phi^2 / sigma_R^2 + d ln(Sigma sigma_R^2) / d ln R)
+        let pressure = |r: f64| (surface_density(r) * radial_dispersion2(r)).ln();
+        let pressure_gradient = (pressure(r * 1.001) - pressure(r / 1.001)) / (2.0 * 1.001f64.ln());
+        let mean_speed2 = circular_speed2 + sigma_r2 - sigma_phi2 + sigma_r2 * pressure_gradient;
+
+        let v_r = sigma_r2.sqrt() * gaussian(rng);
+        let v_phi = mean_speed2.max(0.0).sqrt() + sigma_phi2.sqrt() * gaussian(rng);
+        let v_z = sigma_z2.sqrt() * gaussian(rng);
+
+        let (sin, cos) = theta.sin_cos();
+        positions.push([r * cos, r * sin, z]);
+        velocities.push([v_r * cos + v_phi * sin, v_r * sin - v_phi * cos, v_z]);
+    }
+
+    (positions, velocities)
+}
+
+// Unit vector uniformly distributed over the sphere
+fn random_direction(rng: &mut impl Rng) -> [f64; 3] {
+    let z: f64 = rng.gen_range(-1.0..1.0);
+    let phi = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+    let s = (1.0 - z * z).sqrt();
+    [s * phi.cos(), s * phi.sin(), z]
+}
+
+// Standard normal deviate by the Box-Muller transform
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let phi = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+    (-2.0 * u.ln()).sqrt() * phi.cos()
+}
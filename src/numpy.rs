@@ -0,0 +1,286 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Seek, Write},
+    path::Path,
+};
+
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    snapshot::{read_bytes, write_f32s},
+    NBody,
+};
+
+// NumPy arrays of particles:
+//   .npy: one float32 table of shape (n, 8) with the CSV columns x, y, z, vx, vy, vz, m and kind
+//   .npz: float32 arrays massive_positions (n, 3), massive_velocities (n, 3), massive_masses (n,),
+//         ghost_positions (g, 3), ghost_velocities (g, 3) and ghost_kinds (g,)
+// Arrays are written as little-endian version 1.0 .npy files, and read back from float32 or float64 of either
+// endianness and in C or Fortran order (compressed .npz archives included).
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+// An array in C order
+struct Array {
+    shape: Vec<usize>,
+    values: Vec<f32>,
+}
+
+impl NBody {
+    pub fn load_npy(path: &Path) -> Result<Self> {
+        Self::read_npy(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_npy(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_npy(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn read_npy(reader: &mut impl Read) -> Result<Self> {
+        let array = read_array(reader)?;
+        if array.shape.len() != 2 || array.shape[1] != 8 {
+            return Err(invalid_data(format!(
+                "Expected a particle table of shape (n, 8) but found {:?}",
+                array.shape
+            )));
+        }
+
+        Self::from_rows(
+            array
+                .values
+                .chunks_exact(8)
+                .map(|row| row.try_into().unwrap()),
+        )
+    }
+
+    pub fn write_npy(&self, writer: &mut impl Write) -> Result<()> {
+        let rows: Vec<[f32; 8]> = self.rows().collect();
+        write_array(writer, &[rows.len(), 8], rows.iter().flatten())
+    }
+
+    pub fn load_npz(path: &Path) -> Result<Self> {
+        Self::read_npz(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save_npz(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_npz(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn read_npz(reader: impl Read + Seek) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader)?;
+        let mut array = |name: &str, columns: Option<usize>| -> Result<Array> {
+            let array = read_array(&mut archive.by_name(&format!("{}.npy", name))?)?;
+            let valid = match columns {
+                Some(columns) => array.shape.len() == 2 && array.shape[1] == columns,
+                None => array.shape.len() == 1,
+            };
+            if !valid {
+                return Err(invalid_data(format!(
+                    "Unexpected shape {:?} of {}",
+                    array.shape, name
+                )));
+            }
+            Ok(array)
+        };
+
+        let nbody = NBody {
+            massive_positions: vec3s(array("massive_positions", Some(3))?),
+            massive_velocities: vec3s(array("massive_velocities", Some(3))?),
+            massive_masses: array("massive_masses", None)?.values,
+            ghost_positions: vec3s(array("ghost_positions", Some(3))?),
+            ghost_velocities: vec3s(array("ghost_velocities", Some(3))?),
+            ghost_kinds: array("ghost_kinds", None)?.values,
+        };
+        if !nbody.is_valid() {
+            return Err(invalid_data("Particle arrays differ in length".to_string()));
+        }
+        if nbody
+            .massive_masses
+            .iter()
+            .any(|mass| mass.is_nan() || *mass <= 0.0)
+        {
+            return Err(invalid_data(
+                "Arrays contain a non-positive mass".to_string(),
+            ));
+        }
+
+        Ok(nbody)
+    }
+
+    // Stored uncompressed, like `numpy.savez`
+    pub fn write_npz(&self, writer: &mut (impl Write + Seek)) -> Result<()> {
+        let mut archive = ZipWriter::new(writer);
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+        let (num_massive, num_ghost) = (self.num_massive_particles(), self.num_ghost_particles());
+        let arrays: [(&str, &[usize], Vec<f32>); 6] = [
+            (
+                "massive_positions",
+                &[num_massive, 3],
+                self.massive_positions.concat(),
+            ),
+            (
+                "massive_velocities",
+                &[num_massive, 3],
+                self.massive_velocities.concat(),
+            ),
+            (
+                "massive_masses",
+                &[num_massive],
+                self.massive_masses.clone(),
+            ),
+            (
+                "ghost_positions",
+                &[num_ghost, 3],
+                self.ghost_positions.concat(),
+            ),
+            (
+                "ghost_velocities",
+                &[num_ghost, 3],
+                self.ghost_velocities.concat(),
+            ),
+            ("ghost_kinds", &[num_ghost], self.ghost_kinds.clone()),
+        ];
+        for (name, shape, values) in arrays {
+            archive.start_file(format!("{}.npy", name), options)?;
+            write_array(&mut archive, shape, &values)?;
+        }
+
+        archive.finish()?;
+        Ok(())
+    }
+}
+
+fn write_array<'a>(
+    writer: &mut impl Write,
+    shape: &[usize],
+    values: impl IntoIterator<Item = &'a f32>,
+) -> Result<()> {
+    let shape = match shape {
+        [n] => format!("({},)", n),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+        shape
+    );
+
+    // Spaces and a newline take the data to a 64-byte boundary
+    let unpadded = MAGIC.len() + 2 + 2 + header.len() + 1;
+    header.extend(std::iter::repeat(' ').take((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    write_f32s(writer, values)
+}
+
+fn read_array(reader: &mut impl Read) -> Result<Array> {
+    let magic: [u8; 6] = read_bytes(reader)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a NumPy array".to_string()));
+    }
+    let [major, _minor] = read_bytes(reader)?;
+    let header_len = match major {
+        1 => u16::from_le_bytes(read_bytes(reader)?) as usize,
+        2 | 3 => u32::from_le_bytes(read_bytes(reader)?) as usize,
+        _ => {
+            return Err(invalid_data(format!(
+                "Unsupported NumPy format version {}",
+                major
+            )))
+        }
+    };
+    let mut header = vec![0; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+
+    let descr = header_value(&header, "descr")?;
+    let fortran_order = header_value(&header, "fortran_order")? == "True";
+    let shape = header_value(&header, "shape")?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| {
+            n.parse()
+                .map_err(|_| invalid_data(format!("Invalid NumPy shape dimension '{}'", n)))
+        })
+        .collect::<Result<Vec<usize>>>()?;
+
+    let count = shape.iter().product::<usize>();
+    let values = match descr.trim_matches('\'') {
+        "<f4" => read_values(reader, count, |b: [u8; 4]| f32::from_le_bytes(b))?,
+        ">f4" => read_values(reader, count, |b: [u8; 4]| f32::from_be_bytes(b))?,
+        "<f8" => read_values(reader, count, |b: [u8; 8]| f64::from_le_bytes(b) as f32)?,
+        ">f8" => read_values(reader, count, |b: [u8; 8]| f64::from_be_bytes(b) as f32)?,
+        descr => {
+            return Err(invalid_data(format!(
+                "Unsupported NumPy type {} (expected float32 or float64)",
+                descr
+            )))
+        }
+    };
+
+    // Fortran order is C order with the dimensions reversed
+    let values = match (fortran_order, shape.as_slice()) {
+        (true, [rows, columns]) => (0..rows * columns)
+            .map(|i| values[(i % columns) * rows + i / columns])
+            .collect(),
+        (true, shape) if shape.len() > 2 => {
+            return Err(invalid_data(
+                "Unsupported Fortran-ordered NumPy array of over 2 dimensions".to_string(),
+            ))
+        }
+        _ => values,
+    };
+
+    Ok(Array { shape, values })
+}
+
+fn read_values<const N: usize>(
+    reader: &mut impl Read,
+    count: usize,
+    decode: impl Fn([u8; N]) -> f32,
+) -> Result<Vec<f32>> {
+    (0..count)
+        .map(|_| read_bytes(reader).map(&decode))
+        .collect()
+}
+
+// The text of a value in the header's Python dictionary literal
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+    let missing = || invalid_data(format!("NumPy header has no {}", key));
+    let start = header.find(&format!("'{}':", key)).ok_or_else(missing)? + key.len() + 3;
+    let value = header[start..].trim_start();
+
+    let end = match value.chars().next() {
+        Some('\'') => value[1..].find('\'').map(|end| end + 2),
+        Some('(') => value.find(')').map(|end| end + 1),
+        _ => value.find([',', '}']),
+    };
+    Ok(value[..end.ok_or_else(missing)?].trim())
+}
+
+fn vec3s(array: Array) -> Vec<[f32; 3]> {
+    array
+        .values
+        .chunks_exact(3)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect()
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,80 @@
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use serde::Deserialize;
+
+// Plane and spin of a disc, which the generators build in the xy plane turning clockwise seen from +z and then
+// turn to this orientation. The default leaves them as they are built.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    rotation: UnitQuaternion<f32>,
+    spin: Spin,
+}
+
+// Sense of rotation seen from the side of the disc its normal points to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Spin {
+    #[default]
+    Clockwise,
+    Anticlockwise,
+}
+
+impl Orientation {
+    // The disc perpendicular to `axis` (need not be normalised)
+    pub fn from_spin_axis(axis: [f32; 3], spin: Spin) -> Self {
+        debug_assert!(axis.iter().any(|x| *x != 0.0));
+
+        let axis = Vector3::from(axis).normalize();
+        let rotation =
+            UnitQuaternion::rotation_between(&Vector3::z(), &axis).unwrap_or_else(|| {
+                UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f32::consts::PI)
+            });
+        Self { rotation, spin }
+    }
+
+    // The disc as seen by an observer on the +z axis looking down it, with +y to the north and -x to the east
+    // (angles in radians):
+    //   inclination: from face-on (0) to edge-on (pi / 2), about the line of nodes
+    //   position_angle: of the line of nodes, from north through east
+    pub fn from_sky(inclination: f32, position_angle: f32, spin: Spin) -> Self {
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), position_angle)
+            * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), inclination);
+        Self { rotation, spin }
+    }
+
+    // The disc turned by the quaternion [w, x, y, z] (need not be normalised)
+    pub fn from_quaternion([w, x, y, z]: [f32; 4], spin: Spin) -> Self {
+        debug_assert!([w, x, y, z].iter().any(|x| *x != 0.0));
+
+        let rotation = UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z));
+        Self { rotation, spin }
+    }
+
+    // Unit normal of the disc's plane
+    pub fn normal(&self) -> [f32; 3] {
+        (self.rotation * Vector3::z()).into()
+    }
+
+    // Direction of the disc's angular momentum
+    pub fn angular_momentum_axis(&self) -> [f32; 3] {
+        match self.spin {
+            Spin::Clockwise => (-(self.rotation * Vector3::z())).into(),
+            Spin::Anticlockwise => self.normal(),
+        }
+    }
+
+    // A position relative to the centre of a disc built in the xy plane, turned to the disc's plane
+    pub(crate) fn position(&self, offset: [f32; 3]) -> [f32; 3] {
+        (self.rotation * Vector3::from(offset)).into()
+    }
+
+    // A velocity relative to the drift of a disc built turning clockwise, turned to the disc's plane and spin
+    // (reversing every velocity keeps an equilibrium disc in equilibrium)
+    pub(crate) fn velocity(&self, relative: [f32; 3]) -> [f32; 3] {
+        let relative = Vector3::from(relative);
+        let relative = match self.spin {
+            Spin::Clockwise => relative,
+            Spin::Anticlockwise => -relative,
+        };
+        (self.rotation * relative).into()
+    }
+}
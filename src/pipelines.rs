@@ -1,8 +1,17 @@
-use crate::{Hardware, Memory, Vertex};
+use crate::{memory::RADIX_SORT_PASSES, Hardware, Memory, Vertex};
 
 use wgpu::{BindGroup, ComputePipeline, Device, RenderPipeline};
 
-pub struct Pipelines {
+// Buffer bound to a compute shader, in binding order
+#[derive(Copy, Clone)]
+enum Binding<'a> {
+    Uniform(&'a wgpu::Buffer),
+    UniformRange(&'a wgpu::Buffer, wgpu::BufferAddress, wgpu::BufferAddress), // Buffer, offset, size
+    ReadOnly(&'a wgpu::Buffer),
+    ReadWrite(&'a wgpu::Buffer),
+}
+
+pub struct RenderPipelines {
     // Display bind group
     pub display_bind_group: BindGroup,
     pub display_pipeline: wgpu::RenderPipeline,
@@ -22,11 +31,82 @@ pub struct Pipelines {
     pub blur_horizontally_bind_group: wgpu::BindGroup,
     pub blur_vertically_pipeline: wgpu::ComputePipeline,
     pub blur_vertically_bind_group: wgpu::BindGroup,
+}
+
+pub struct TreePipelines {
+    // Morton keys of the massive particles within their bounding cube
+    pub compute_bounds_pipeline: wgpu::ComputePipeline,
+    pub compute_bounds_bind_group: wgpu::BindGroup,
+    pub compute_morton_codes_pipeline: wgpu::ComputePipeline,
+    pub compute_morton_codes_bind_group: wgpu::BindGroup,
+
+    // Radix sort (one histogram and scatter bind group per pass, ping-ponging between buffers)
+    pub radix_sort_histogram_pipeline: wgpu::ComputePipeline,
+    pub radix_sort_histogram_bind_groups: Vec<wgpu::BindGroup>,
+    pub radix_sort_scan_pipeline: wgpu::ComputePipeline,
+    pub radix_sort_scan_bind_group: wgpu::BindGroup,
+    pub radix_sort_scatter_pipeline: wgpu::ComputePipeline,
+    pub radix_sort_scatter_bind_groups: Vec<wgpu::BindGroup>,
+
+    // Tree construction
+    pub build_nodes_pipeline: wgpu::ComputePipeline,
+    pub build_nodes_bind_group: wgpu::BindGroup,
+    pub compute_moments_pipeline: wgpu::ComputePipeline,
+    pub compute_moments_bind_group: wgpu::BindGroup,
+
+    // Tree walks
+    pub calculate_massive_forces_pipeline: wgpu::ComputePipeline,
+    pub calculate_massive_forces_bind_group: wgpu::BindGroup,
+    pub calculate_ghost_forces_pipeline: wgpu::ComputePipeline,
+    pub calculate_ghost_forces_bind_group: wgpu::BindGroup,
+}
+
+pub struct MeshPipelines {
+    // Cube around the massive particles and ghosts
+    pub compute_bounds_pipeline: wgpu::ComputePipeline,
+    pub compute_bounds_bind_group: wgpu::BindGroup,
+
+    // Cube the mesh covers, rounded so that the Green's function is only rebuilt when it changes
+    pub frame_pipeline: wgpu::ComputePipeline,
+    pub frame_bind_group: wgpu::BindGroup,
+
+    // Potential from the FFT convolution of the masses with the Green's function
+    pub deposit_masses_pipeline: wgpu::ComputePipeline,
+    pub deposit_masses_bind_group: wgpu::BindGroup,
+    pub prepare_pipeline: wgpu::ComputePipeline,
+    pub prepare_bind_group: wgpu::BindGroup,
+    pub transform_pipeline: wgpu::ComputePipeline,
+    pub forward_transform_potential_bind_groups: Vec<wgpu::BindGroup>, // One per axis
+    pub forward_transform_green_bind_groups: Vec<wgpu::BindGroup>,
+    pub inverse_transform_potential_bind_groups: Vec<wgpu::BindGroup>,
+    pub convolve_pipeline: wgpu::ComputePipeline,
+    pub convolve_bind_group: wgpu::BindGroup,
+
+    // Force interpolation
+    pub calculate_massive_forces_pipeline: wgpu::ComputePipeline,
+    pub calculate_massive_forces_bind_group: wgpu::BindGroup,
+    pub calculate_ghost_forces_pipeline: wgpu::ComputePipeline,
+    pub calculate_ghost_forces_bind_group: wgpu::BindGroup,
+}
+
+pub struct Pipelines {
+    // Rendering (not built for compute-only simulations)
+    pub render: Option<RenderPipelines>,
+
+    // Barnes-Hut tree solver
+    pub tree: TreePipelines,
+
+    // Particle-mesh solver
+    pub mesh: MeshPipelines,
 
     // Calculate massive forces
     pub calculate_massive_forces_pipeline: wgpu::ComputePipeline,
     pub calculate_massive_forces_bind_group: wgpu::BindGroup,
 
+    // Calculate massive forces with workgroup memory tiles
+    pub calculate_massive_tiled_forces_pipeline: wgpu::ComputePipeline,
+    pub calculate_massive_tiled_forces_bind_group: wgpu::BindGroup,
+
     // Calculate massive velocities
     pub calculate_massive_velocities_pipeline: wgpu::ComputePipeline,
     pub calculate_massive_velocities_bind_group: wgpu::BindGroup,
@@ -39,6 +119,10 @@ pub struct Pipelines {
     pub calculate_ghost_forces_pipeline: wgpu::ComputePipeline,
     pub calculate_ghost_forces_bind_group: wgpu::BindGroup,
 
+    // Calculate ghost forces with workgroup memory tiles
+    pub calculate_ghost_tiled_forces_pipeline: wgpu::ComputePipeline,
+    pub calculate_ghost_tiled_forces_bind_group: wgpu::BindGroup,
+
     // Calculate ghost velocities
     pub calculate_ghost_velocities_pipeline: wgpu::ComputePipeline,
     pub calculate_ghost_velocities_bind_group: wgpu::BindGroup,
@@ -46,24 +130,37 @@ pub struct Pipelines {
     // Calculate ghost positions
     pub calculate_ghost_positions_pipeline: wgpu::ComputePipeline,
     pub calculate_ghost_positions_bind_group: wgpu::BindGroup,
+
+    // Velocity Verlet
+    pub calculate_massive_verlet_positions_pipeline: wgpu::ComputePipeline,
+    pub calculate_massive_verlet_positions_bind_group: wgpu::BindGroup,
+    pub calculate_massive_verlet_velocities_pipeline: wgpu::ComputePipeline,
+    pub calculate_massive_verlet_velocities_bind_group: wgpu::BindGroup,
+    pub calculate_ghost_verlet_positions_pipeline: wgpu::ComputePipeline,
+    pub calculate_ghost_verlet_positions_bind_group: wgpu::BindGroup,
+    pub calculate_ghost_verlet_velocities_pipeline: wgpu::ComputePipeline,
+    pub calculate_ghost_verlet_velocities_bind_group: wgpu::BindGroup,
+
+    // Hermite
+    pub calculate_massive_forces_and_jerks_pipeline: wgpu::ComputePipeline,
+    pub calculate_massive_forces_and_jerks_bind_group: wgpu::BindGroup,
+    pub calculate_ghost_forces_and_jerks_pipeline: wgpu::ComputePipeline,
+    pub calculate_ghost_forces_and_jerks_bind_group: wgpu::BindGroup,
+    pub predict_massive_particles_pipeline: wgpu::ComputePipeline,
+    pub predict_massive_particles_bind_group: wgpu::BindGroup,
+    pub predict_ghost_particles_pipeline: wgpu::ComputePipeline,
+    pub predict_ghost_particles_bind_group: wgpu::BindGroup,
+    pub correct_massive_particles_pipeline: wgpu::ComputePipeline,
+    pub correct_massive_particles_bind_group: wgpu::BindGroup,
+    pub correct_ghost_particles_pipeline: wgpu::ComputePipeline,
+    pub correct_ghost_particles_bind_group: wgpu::BindGroup,
 }
 
 impl Pipelines {
-    pub fn new(hardware: &Hardware, memory: &Memory) -> Self {
-        let (display_bind_group, display_pipeline) =
-            Self::init_display_bind_group_and_pipeline(&hardware.device, &hardware.config, memory);
-
-        let (render_massive_particles_pipeline, render_massive_particles_bind_group) =
-            Self::init_render_massive_particles_pipeline_and_bind_group(hardware, memory);
-        let (pre_render_ghost_particles_pipeline, pre_render_ghost_particles_bind_group) =
-            Self::init_pre_render_ghost_particles_pipeline_and_bind_group(hardware, memory);
-        let (render_ghost_particles_pipeline, render_ghost_particles_bind_group) =
-            Self::init_render_ghost_particles_pipeline_and_bind_group(hardware, memory);
-
-        let (blur_horizontally_pipeline, blur_horizontally_bind_group) =
-            Self::blur_horizontally_pipeline_and_bind_group(hardware, memory);
-        let (blur_vertically_pipeline, blur_vertically_bind_group) =
-            Self::blur_vertically_pipeline_and_bind_group(hardware, memory);
+    pub fn new(hardware: &Hardware, memory: &Memory, with_rendering: bool) -> Self {
+        let render = with_rendering.then(|| Self::init_render_pipelines(hardware, memory));
+        let tree = Self::init_tree_pipelines(hardware, memory);
+        let mesh = Self::init_mesh_pipelines(hardware, memory);
 
         let (calculate_massive_forces_pipeline, calculate_massive_forces_bind_group) =
             Self::init_calculate_massive_forces_pipeline_and_bind_group(hardware, memory);
@@ -79,7 +176,256 @@ impl Pipelines {
         let (calculate_ghost_positions_pipeline, calculate_ghost_positions_bind_group) =
             Self::init_calculate_ghost_positions_pipeline_and_bind_group(hardware, memory);
 
+        // The tile length is baked into the tiled shaders' workgroup arrays
+        let tile_size_declaration = format!("const TILE_SIZE: u32 = {}u;", memory.tile_size);
+        let (calculate_massive_tiled_forces_pipeline, calculate_massive_tiled_forces_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Calculate Massive Tiled Forces",
+                &include_str!("shaders/calculate_massive_tiled_forces.wgsl")
+                    .replace("const TILE_SIZE: u32 = 64u;", &tile_size_declaration),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadWrite(&memory.massive_forces_and_masses_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (calculate_ghost_tiled_forces_pipeline, calculate_ghost_tiled_forces_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Calculate Ghost Tiled Forces",
+                &include_str!("shaders/calculate_ghost_tiled_forces.wgsl")
+                    .replace("const TILE_SIZE: u32 = 64u;", &tile_size_declaration),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.ghost_positions_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadWrite(&memory.ghost_forces_and_kinds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        let (
+            calculate_massive_verlet_positions_pipeline,
+            calculate_massive_verlet_positions_bind_group,
+        ) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Calculate Massive Verlet Positions",
+            include_str!("shaders/calculate_massive_verlet_positions.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::ReadOnly(&memory.massive_velocities_and_masses_buffer),
+                Binding::ReadOnly(&memory.massive_forces_and_masses_buffer),
+                Binding::ReadWrite(&memory.massive_positions_and_masses_buffer),
+                Binding::Uniform(&memory.counts_uniform),
+            ],
+        );
+        let (
+            calculate_massive_verlet_velocities_pipeline,
+            calculate_massive_verlet_velocities_bind_group,
+        ) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Calculate Massive Verlet Velocities",
+            include_str!("shaders/calculate_massive_verlet_velocities.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::ReadOnly(&memory.massive_previous_forces_buffer),
+                Binding::ReadOnly(&memory.massive_forces_and_masses_buffer),
+                Binding::ReadWrite(&memory.massive_velocities_and_masses_buffer),
+                Binding::Uniform(&memory.counts_uniform),
+            ],
+        );
+        let (
+            calculate_ghost_verlet_positions_pipeline,
+            calculate_ghost_verlet_positions_bind_group,
+        ) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Calculate Ghost Verlet Positions",
+            include_str!("shaders/calculate_ghost_verlet_positions.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::ReadOnly(&memory.ghost_velocities_and_kinds_buffer),
+                Binding::ReadOnly(&memory.ghost_forces_and_kinds_buffer),
+                Binding::ReadWrite(&memory.ghost_positions_and_kinds_buffer),
+                Binding::Uniform(&memory.counts_uniform),
+            ],
+        );
+        let (
+            calculate_ghost_verlet_velocities_pipeline,
+            calculate_ghost_verlet_velocities_bind_group,
+        ) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Calculate Ghost Verlet Velocities",
+            include_str!("shaders/calculate_ghost_verlet_velocities.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::ReadOnly(&memory.ghost_previous_forces_buffer),
+                Binding::ReadOnly(&memory.ghost_forces_and_kinds_buffer),
+                Binding::ReadWrite(&memory.ghost_velocities_and_kinds_buffer),
+                Binding::Uniform(&memory.counts_uniform),
+            ],
+        );
+
+        let (
+            calculate_massive_forces_and_jerks_pipeline,
+            calculate_massive_forces_and_jerks_bind_group,
+        ) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Calculate Massive Forces and Jerks",
+            include_str!("shaders/calculate_massive_forces_and_jerks.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                Binding::ReadOnly(&memory.massive_velocities_and_masses_buffer),
+                Binding::ReadWrite(&memory.massive_forces_and_masses_buffer),
+                Binding::ReadWrite(&memory.massive_jerks_buffer),
+                Binding::Uniform(&memory.counts_uniform),
+            ],
+        );
+        let (
+            calculate_ghost_forces_and_jerks_pipeline,
+            calculate_ghost_forces_and_jerks_bind_group,
+        ) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Calculate Ghost Forces and Jerks",
+            include_str!("shaders/calculate_ghost_forces_and_jerks.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::ReadOnly(&memory.ghost_positions_and_kinds_buffer),
+                Binding::ReadOnly(&memory.ghost_velocities_and_kinds_buffer),
+                Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                Binding::ReadOnly(&memory.massive_velocities_and_masses_buffer),
+                Binding::ReadWrite(&memory.ghost_forces_and_kinds_buffer),
+                Binding::ReadWrite(&memory.ghost_jerks_buffer),
+                Binding::Uniform(&memory.counts_uniform),
+            ],
+        );
+        let (predict_massive_particles_pipeline, predict_massive_particles_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Predict Massive Particles",
+                include_str!("shaders/predict_massive_particles.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.massive_forces_and_masses_buffer),
+                    Binding::ReadOnly(&memory.massive_jerks_buffer),
+                    Binding::ReadWrite(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadWrite(&memory.massive_velocities_and_masses_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (predict_ghost_particles_pipeline, predict_ghost_particles_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Predict Ghost Particles",
+                include_str!("shaders/predict_ghost_particles.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.ghost_forces_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.ghost_jerks_buffer),
+                    Binding::ReadWrite(&memory.ghost_positions_and_kinds_buffer),
+                    Binding::ReadWrite(&memory.ghost_velocities_and_kinds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (correct_massive_particles_pipeline, correct_massive_particles_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Correct Massive Particles",
+                include_str!("shaders/correct_massive_particles.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.massive_previous_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.massive_previous_velocities_and_masses_buffer),
+                    Binding::ReadOnly(&memory.massive_previous_forces_buffer),
+                    Binding::ReadOnly(&memory.massive_previous_jerks_buffer),
+                    Binding::ReadOnly(&memory.massive_forces_and_masses_buffer),
+                    Binding::ReadOnly(&memory.massive_jerks_buffer),
+                    Binding::ReadWrite(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadWrite(&memory.massive_velocities_and_masses_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (correct_ghost_particles_pipeline, correct_ghost_particles_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Correct Ghost Particles",
+                include_str!("shaders/correct_ghost_particles.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.ghost_previous_positions_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.ghost_previous_velocities_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.ghost_previous_forces_buffer),
+                    Binding::ReadOnly(&memory.ghost_previous_jerks_buffer),
+                    Binding::ReadOnly(&memory.ghost_forces_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.ghost_jerks_buffer),
+                    Binding::ReadWrite(&memory.ghost_positions_and_kinds_buffer),
+                    Binding::ReadWrite(&memory.ghost_velocities_and_kinds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
         Self {
+            render,
+            tree,
+            mesh,
+            calculate_massive_forces_pipeline,
+            calculate_massive_forces_bind_group,
+            calculate_massive_tiled_forces_pipeline,
+            calculate_massive_tiled_forces_bind_group,
+            calculate_massive_velocities_pipeline,
+            calculate_massive_velocities_bind_group,
+            calculate_massive_positions_pipeline,
+            calculate_massive_positions_bind_group,
+            calculate_ghost_forces_pipeline,
+            calculate_ghost_forces_bind_group,
+            calculate_ghost_tiled_forces_pipeline,
+            calculate_ghost_tiled_forces_bind_group,
+            calculate_ghost_velocities_pipeline,
+            calculate_ghost_velocities_bind_group,
+            calculate_ghost_positions_pipeline,
+            calculate_ghost_positions_bind_group,
+            calculate_massive_verlet_positions_pipeline,
+            calculate_massive_verlet_positions_bind_group,
+            calculate_massive_verlet_velocities_pipeline,
+            calculate_massive_verlet_velocities_bind_group,
+            calculate_ghost_verlet_positions_pipeline,
+            calculate_ghost_verlet_positions_bind_group,
+            calculate_ghost_verlet_velocities_pipeline,
+            calculate_ghost_verlet_velocities_bind_group,
+            calculate_massive_forces_and_jerks_pipeline,
+            calculate_massive_forces_and_jerks_bind_group,
+            calculate_ghost_forces_and_jerks_pipeline,
+            calculate_ghost_forces_and_jerks_bind_group,
+            predict_massive_particles_pipeline,
+            predict_massive_particles_bind_group,
+            predict_ghost_particles_pipeline,
+            predict_ghost_particles_bind_group,
+            correct_massive_particles_pipeline,
+            correct_massive_particles_bind_group,
+            correct_ghost_particles_pipeline,
+            correct_ghost_particles_bind_group,
+        }
+    }
+
+    fn init_render_pipelines(hardware: &Hardware, memory: &Memory) -> RenderPipelines {
+        let (display_bind_group, display_pipeline) =
+            Self::init_display_bind_group_and_pipeline(&hardware.device, &hardware.config, memory);
+
+        let (render_massive_particles_pipeline, render_massive_particles_bind_group) =
+            Self::init_render_massive_particles_pipeline_and_bind_group(hardware, memory);
+        let (pre_render_ghost_particles_pipeline, pre_render_ghost_particles_bind_group) =
+            Self::init_pre_render_ghost_particles_pipeline_and_bind_group(hardware, memory);
+        let (render_ghost_particles_pipeline, render_ghost_particles_bind_group) =
+            Self::init_render_ghost_particles_pipeline_and_bind_group(hardware, memory);
+
+        let (blur_horizontally_pipeline, blur_horizontally_bind_group) =
+            Self::blur_horizontally_pipeline_and_bind_group(hardware, memory);
+        let (blur_vertically_pipeline, blur_vertically_bind_group) =
+            Self::blur_vertically_pipeline_and_bind_group(hardware, memory);
+
+        RenderPipelines {
             display_bind_group,
             display_pipeline,
             render_massive_particles_pipeline,
@@ -92,18 +438,375 @@ impl Pipelines {
             blur_horizontally_bind_group,
             blur_vertically_pipeline,
             blur_vertically_bind_group,
+        }
+    }
+
+    fn init_tree_pipelines(hardware: &Hardware, memory: &Memory) -> TreePipelines {
+        let (compute_bounds_pipeline, compute_bounds_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Compute Tree Bounds",
+                include_str!("shaders/compute_tree_bounds.wgsl"),
+                &[
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadWrite(&memory.tree_bounds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (compute_morton_codes_pipeline, compute_morton_codes_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Compute Morton Codes",
+                include_str!("shaders/compute_morton_codes.wgsl"),
+                &[
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.tree_bounds_buffer),
+                    Binding::ReadWrite(&memory.tree_keys_buffer),
+                    Binding::ReadWrite(&memory.tree_indices_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        // Even passes sort from the primary buffers into the swap buffers, odd passes sort back
+        let radix_pass_bindings = (0..RADIX_SORT_PASSES)
+            .map(|pass| {
+                let radix_pass = Binding::UniformRange(
+                    &memory.tree_radix_passes_uniform,
+                    pass as wgpu::BufferAddress * memory.tree_radix_pass_stride,
+                    16,
+                );
+                let buffers = [
+                    &memory.tree_keys_buffer,
+                    &memory.tree_indices_buffer,
+                    &memory.tree_swap_keys_buffer,
+                    &memory.tree_swap_indices_buffer,
+                ];
+                let [keys, values, sorted_keys, sorted_values] = if pass % 2 == 0 {
+                    buffers
+                } else {
+                    [buffers[2], buffers[3], buffers[0], buffers[1]]
+                };
+                (radix_pass, keys, values, sorted_keys, sorted_values)
+            })
+            .collect::<Vec<_>>();
+
+        let histogram_bindings = radix_pass_bindings
+            .iter()
+            .map(|(radix_pass, keys, _, _, _)| {
+                [
+                    *radix_pass,
+                    Binding::ReadOnly(keys),
+                    Binding::ReadWrite(&memory.tree_histograms_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let radix_sort_histogram_pipeline = Self::init_compute_pipeline(
+            hardware,
+            "Radix Sort Histogram",
+            include_str!("shaders/radix_sort_histogram.wgsl"),
+            &histogram_bindings[0],
+        );
+        let radix_sort_histogram_bind_groups = histogram_bindings
+            .iter()
+            .map(|bindings| {
+                Self::init_compute_bind_group(
+                    hardware,
+                    "Radix Sort Histogram",
+                    &radix_sort_histogram_pipeline,
+                    bindings,
+                )
+            })
+            .collect();
+
+        let (radix_sort_scan_pipeline, radix_sort_scan_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Radix Sort Scan",
+                include_str!("shaders/radix_sort_scan.wgsl"),
+                &[
+                    Binding::ReadWrite(&memory.tree_histograms_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        let scatter_bindings = radix_pass_bindings
+            .iter()
+            .map(|(radix_pass, keys, values, sorted_keys, sorted_values)| {
+                [
+                    *radix_pass,
+                    Binding::ReadOnly(keys),
+                    Binding::ReadOnly(values),
+                    Binding::ReadOnly(&memory.tree_histograms_buffer),
+                    Binding::ReadWrite(sorted_keys),
+                    Binding::ReadWrite(sorted_values),
+                    Binding::Uniform(&memory.counts_uniform),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let radix_sort_scatter_pipeline = Self::init_compute_pipeline(
+            hardware,
+            "Radix Sort Scatter",
+            include_str!("shaders/radix_sort_scatter.wgsl"),
+            &scatter_bindings[0],
+        );
+        let radix_sort_scatter_bind_groups = scatter_bindings
+            .iter()
+            .map(|bindings| {
+                Self::init_compute_bind_group(
+                    hardware,
+                    "Radix Sort Scatter",
+                    &radix_sort_scatter_pipeline,
+                    bindings,
+                )
+            })
+            .collect();
+
+        let (build_nodes_pipeline, build_nodes_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Build Tree Nodes",
+                include_str!("shaders/build_tree_nodes.wgsl"),
+                &[
+                    Binding::ReadOnly(&memory.tree_keys_buffer),
+                    Binding::ReadOnly(&memory.tree_bounds_buffer),
+                    Binding::ReadWrite(&memory.tree_nodes_buffer),
+                    Binding::ReadWrite(&memory.tree_parents_buffer),
+                    Binding::ReadWrite(&memory.tree_sizes_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (compute_moments_pipeline, compute_moments_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Compute Tree Moments",
+                include_str!("shaders/compute_tree_moments.wgsl"),
+                &[
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.tree_indices_buffer),
+                    Binding::ReadOnly(&memory.tree_nodes_buffer),
+                    Binding::ReadOnly(&memory.tree_parents_buffer),
+                    Binding::ReadWrite(&memory.tree_visits_buffer),
+                    Binding::ReadWrite(&memory.tree_moments_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        let (calculate_massive_forces_pipeline, calculate_massive_forces_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Calculate Massive Tree Forces",
+                include_str!("shaders/calculate_massive_tree_forces.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.tree_indices_buffer),
+                    Binding::ReadOnly(&memory.tree_nodes_buffer),
+                    Binding::ReadOnly(&memory.tree_moments_buffer),
+                    Binding::ReadOnly(&memory.tree_sizes_buffer),
+                    Binding::ReadWrite(&memory.massive_forces_and_masses_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (calculate_ghost_forces_pipeline, calculate_ghost_forces_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Calculate Ghost Tree Forces",
+                include_str!("shaders/calculate_ghost_tree_forces.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::ReadOnly(&memory.ghost_positions_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.tree_indices_buffer),
+                    Binding::ReadOnly(&memory.tree_nodes_buffer),
+                    Binding::ReadOnly(&memory.tree_moments_buffer),
+                    Binding::ReadOnly(&memory.tree_sizes_buffer),
+                    Binding::ReadWrite(&memory.ghost_forces_and_kinds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        TreePipelines {
+            compute_bounds_pipeline,
+            compute_bounds_bind_group,
+            compute_morton_codes_pipeline,
+            compute_morton_codes_bind_group,
+            radix_sort_histogram_pipeline,
+            radix_sort_histogram_bind_groups,
+            radix_sort_scan_pipeline,
+            radix_sort_scan_bind_group,
+            radix_sort_scatter_pipeline,
+            radix_sort_scatter_bind_groups,
+            build_nodes_pipeline,
+            build_nodes_bind_group,
+            compute_moments_pipeline,
+            compute_moments_bind_group,
+            calculate_massive_forces_pipeline,
+            calculate_massive_forces_bind_group,
+            calculate_ghost_forces_pipeline,
+            calculate_ghost_forces_bind_group,
+        }
+    }
+
+    fn init_mesh_pipelines(hardware: &Hardware, memory: &Memory) -> MeshPipelines {
+        let (compute_bounds_pipeline, compute_bounds_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Compute Mesh Bounds",
+                include_str!("shaders/compute_mesh_bounds.wgsl"),
+                &[
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.ghost_positions_and_kinds_buffer),
+                    Binding::ReadWrite(&memory.mesh_bounds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        let (deposit_masses_pipeline, deposit_masses_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Deposit Mesh Masses",
+                include_str!("shaders/deposit_mesh_masses.wgsl"),
+                &[
+                    Binding::Uniform(&memory.mesh_uniform),
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.mesh_frame_buffer),
+                    Binding::ReadWrite(&memory.mesh_masses_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (frame_pipeline, frame_bind_group) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Frame Mesh",
+            include_str!("shaders/frame_mesh.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::Uniform(&memory.mesh_uniform),
+                Binding::ReadOnly(&memory.mesh_bounds_buffer),
+                Binding::ReadWrite(&memory.mesh_frame_buffer),
+            ],
+        );
+
+        let (prepare_pipeline, prepare_bind_group) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Prepare Mesh",
+            include_str!("shaders/prepare_mesh.wgsl"),
+            &[
+                Binding::Uniform(&memory.settings_uniform),
+                Binding::Uniform(&memory.mesh_uniform),
+                Binding::ReadOnly(&memory.mesh_frame_buffer),
+                Binding::ReadOnly(&memory.mesh_masses_buffer),
+                Binding::ReadWrite(&memory.mesh_potential_buffer),
+                Binding::ReadWrite(&memory.mesh_green_buffer),
+            ],
+        );
+
+        // FFT pass uniforms hold the passes (x, y, z) of each of the `MESH_FFT_PASSES` in turn
+        let transform_bindings = |buffer, transform: u64| {
+            (0..3)
+                .map(|axis| {
+                    let pass = 3 * transform + axis;
+                    [
+                        Binding::Uniform(&memory.mesh_uniform),
+                        Binding::UniformRange(
+                            &memory.mesh_fft_passes_uniform,
+                            pass * memory.mesh_fft_pass_stride,
+                            16,
+                        ),
+                        Binding::ReadWrite(buffer),
+                        Binding::ReadOnly(&memory.mesh_frame_buffer),
+                    ]
+                })
+                .collect::<Vec<_>>()
+        };
+        let forward_potential_bindings = transform_bindings(&memory.mesh_potential_buffer, 0);
+        let transform_pipeline = Self::init_compute_pipeline(
+            hardware,
+            "Transform Mesh",
+            include_str!("shaders/transform_mesh.wgsl"),
+            &forward_potential_bindings[0],
+        );
+        let [forward_transform_potential_bind_groups, forward_transform_green_bind_groups, inverse_transform_potential_bind_groups] =
+            [
+                forward_potential_bindings,
+                transform_bindings(&memory.mesh_green_buffer, 2),
+                transform_bindings(&memory.mesh_potential_buffer, 1),
+            ]
+            .map(|bindings| {
+                bindings
+                    .iter()
+                    .map(|bindings| {
+                        Self::init_compute_bind_group(
+                            hardware,
+                            "Transform Mesh",
+                            &transform_pipeline,
+                            bindings,
+                        )
+                    })
+                    .collect()
+            });
+
+        let (convolve_pipeline, convolve_bind_group) = Self::init_compute_pipeline_and_bind_group(
+            hardware,
+            "Convolve Mesh",
+            include_str!("shaders/convolve_mesh.wgsl"),
+            &[
+                Binding::Uniform(&memory.mesh_uniform),
+                Binding::ReadOnly(&memory.mesh_green_buffer),
+                Binding::ReadWrite(&memory.mesh_potential_buffer),
+            ],
+        );
+
+        let (calculate_massive_forces_pipeline, calculate_massive_forces_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Calculate Massive Mesh Forces",
+                include_str!("shaders/calculate_massive_mesh_forces.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::Uniform(&memory.mesh_uniform),
+                    Binding::ReadOnly(&memory.mesh_frame_buffer),
+                    Binding::ReadOnly(&memory.massive_positions_and_masses_buffer),
+                    Binding::ReadOnly(&memory.mesh_potential_buffer),
+                    Binding::ReadWrite(&memory.massive_forces_and_masses_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+        let (calculate_ghost_forces_pipeline, calculate_ghost_forces_bind_group) =
+            Self::init_compute_pipeline_and_bind_group(
+                hardware,
+                "Calculate Ghost Mesh Forces",
+                include_str!("shaders/calculate_ghost_mesh_forces.wgsl"),
+                &[
+                    Binding::Uniform(&memory.settings_uniform),
+                    Binding::Uniform(&memory.mesh_uniform),
+                    Binding::ReadOnly(&memory.mesh_frame_buffer),
+                    Binding::ReadOnly(&memory.ghost_positions_and_kinds_buffer),
+                    Binding::ReadOnly(&memory.mesh_potential_buffer),
+                    Binding::ReadWrite(&memory.ghost_forces_and_kinds_buffer),
+                    Binding::Uniform(&memory.counts_uniform),
+                ],
+            );
+
+        MeshPipelines {
+            compute_bounds_pipeline,
+            compute_bounds_bind_group,
+            frame_pipeline,
+            frame_bind_group,
+            deposit_masses_pipeline,
+            deposit_masses_bind_group,
+            prepare_pipeline,
+            prepare_bind_group,
+            transform_pipeline,
+            forward_transform_potential_bind_groups,
+            forward_transform_green_bind_groups,
+            inverse_transform_potential_bind_groups,
+            convolve_pipeline,
+            convolve_bind_group,
             calculate_massive_forces_pipeline,
             calculate_massive_forces_bind_group,
-            calculate_massive_velocities_pipeline,
-            calculate_massive_velocities_bind_group,
-            calculate_massive_positions_pipeline,
-            calculate_massive_positions_bind_group,
             calculate_ghost_forces_pipeline,
             calculate_ghost_forces_bind_group,
-            calculate_ghost_velocities_pipeline,
-            calculate_ghost_velocities_bind_group,
-            calculate_ghost_positions_pipeline,
-            calculate_ghost_positions_bind_group,
         }
     }
 
@@ -266,12 +969,22 @@ impl Pipelines {
                             binding: 3,
                             visibility: wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadWrite,
+                                access: wgpu::StorageTextureAccess::WriteOnly,
                                 format: wgpu::TextureFormat::Rgba8Unorm,
                                 view_dimension: wgpu::TextureViewDimension::D2,
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -317,6 +1030,10 @@ impl Pipelines {
                         binding: 3,
                         resource: wgpu::BindingResource::TextureView(&memory.display_view),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
@@ -374,10 +1091,20 @@ impl Pipelines {
                         wgpu::BindGroupLayoutEntry {
                             binding: 3,
                             visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadWrite,
-                                format: wgpu::TextureFormat::Rgba8Unorm,
-                                view_dimension: wgpu::TextureViewDimension::D2,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
                             },
                             count: None,
                         },
@@ -422,7 +1149,11 @@ impl Pipelines {
                     },
                     wgpu::BindGroupEntry {
                         binding: 3,
-                        resource: wgpu::BindingResource::TextureView(&memory.display_view),
+                        resource: memory.ghost_pixels_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
                     },
                 ],
             });
@@ -462,7 +1193,7 @@ impl Pipelines {
                             binding: 1,
                             visibility: wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
                                 min_binding_size: None,
                                 has_dynamic_offset: false,
                             },
@@ -471,18 +1202,8 @@ impl Pipelines {
                         wgpu::BindGroupLayoutEntry {
                             binding: 2,
                             visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                                min_binding_size: None,
-                                has_dynamic_offset: false,
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 3,
-                            visibility: wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadWrite,
+                                access: wgpu::StorageTextureAccess::WriteOnly,
                                 format: wgpu::TextureFormat::Rgba8Unorm,
                                 view_dimension: wgpu::TextureViewDimension::D2,
                             },
@@ -521,14 +1242,10 @@ impl Pipelines {
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: memory.camera_uniform.as_entire_binding(),
+                        resource: memory.ghost_pixels_buffer.as_entire_binding(),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
-                        resource: memory.ghost_positions_and_kinds_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 3,
                         resource: wgpu::BindingResource::TextureView(&memory.display_view),
                     },
                 ],
@@ -568,10 +1285,10 @@ impl Pipelines {
                         wgpu::BindGroupLayoutEntry {
                             binding: 1,
                             visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadOnly,
-                                format: wgpu::TextureFormat::Rgba8Unorm,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
                                 view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
                             },
                             count: None,
                         },
@@ -579,7 +1296,7 @@ impl Pipelines {
                             binding: 2,
                             visibility: wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadWrite,
+                                access: wgpu::StorageTextureAccess::WriteOnly,
                                 format: wgpu::TextureFormat::Rgba8Unorm,
                                 view_dimension: wgpu::TextureViewDimension::D2,
                             },
@@ -661,10 +1378,10 @@ impl Pipelines {
                         wgpu::BindGroupLayoutEntry {
                             binding: 1,
                             visibility: wgpu::ShaderStages::COMPUTE,
-                            ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadOnly,
-                                format: wgpu::TextureFormat::Rgba8Unorm,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
                                 view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
                             },
                             count: None,
                         },
@@ -672,7 +1389,7 @@ impl Pipelines {
                             binding: 2,
                             visibility: wgpu::ShaderStages::COMPUTE,
                             ty: wgpu::BindingType::StorageTexture {
-                                access: wgpu::StorageTextureAccess::ReadWrite,
+                                access: wgpu::StorageTextureAccess::WriteOnly,
                                 format: wgpu::TextureFormat::Rgba8Unorm,
                                 view_dimension: wgpu::TextureViewDimension::D2,
                             },
@@ -771,6 +1488,16 @@ impl Pipelines {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -812,6 +1539,10 @@ impl Pipelines {
                         binding: 2,
                         resource: memory.massive_forces_and_masses_buffer.as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
@@ -866,6 +1597,26 @@ impl Pipelines {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -907,6 +1658,14 @@ impl Pipelines {
                             .massive_velocities_and_masses_buffer
                             .as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: memory.step_uniform.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
@@ -961,6 +1720,26 @@ impl Pipelines {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -1004,6 +1783,14 @@ impl Pipelines {
                             .massive_positions_and_masses_buffer
                             .as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: memory.step_uniform.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
@@ -1068,6 +1855,16 @@ impl Pipelines {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -1113,6 +1910,10 @@ impl Pipelines {
                         binding: 3,
                         resource: memory.ghost_forces_and_kinds_buffer.as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
@@ -1167,6 +1968,26 @@ impl Pipelines {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -1206,6 +2027,14 @@ impl Pipelines {
                         binding: 2,
                         resource: memory.ghost_velocities_and_kinds_buffer.as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: memory.step_uniform.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
@@ -1260,6 +2089,26 @@ impl Pipelines {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                min_binding_size: None,
+                                has_dynamic_offset: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -1299,9 +2148,128 @@ impl Pipelines {
                         binding: 2,
                         resource: memory.ghost_positions_and_kinds_buffer.as_entire_binding(),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: memory.step_uniform.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: memory.counts_uniform.as_entire_binding(),
+                    },
                 ],
             });
 
         (pipeline, bind_group)
     }
+
+    fn init_compute_pipeline_and_bind_group(
+        hardware: &Hardware,
+        label: &str,
+        shader_source: &str,
+        bindings: &[Binding],
+    ) -> (ComputePipeline, BindGroup) {
+        let pipeline = Self::init_compute_pipeline(hardware, label, shader_source, bindings);
+        let bind_group = Self::init_compute_bind_group(hardware, label, &pipeline, bindings);
+
+        (pipeline, bind_group)
+    }
+
+    fn init_compute_pipeline(
+        hardware: &Hardware,
+        label: &str,
+        shader_source: &str,
+        bindings: &[Binding],
+    ) -> ComputePipeline {
+        let shader_module = hardware
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(&format!("{} - Shader Module", label)),
+                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+            });
+
+        let layout_entries = bindings
+            .iter()
+            .enumerate()
+            .map(|(n, binding)| wgpu::BindGroupLayoutEntry {
+                binding: n as u32,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: match binding {
+                        Binding::Uniform(_) | Binding::UniformRange(..) => {
+                            wgpu::BufferBindingType::Uniform
+                        }
+                        Binding::ReadOnly(_) => {
+                            wgpu::BufferBindingType::Storage { read_only: true }
+                        }
+                        Binding::ReadWrite(_) => {
+                            wgpu::BufferBindingType::Storage { read_only: false }
+                        }
+                    },
+                    min_binding_size: None,
+                    has_dynamic_offset: false,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+        let bind_group_layout =
+            hardware
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(&format!("{} - Bind Group Layout", label)),
+                    entries: &layout_entries,
+                });
+
+        let pipeline_layout =
+            hardware
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some(&format!("{} - Pipeline Layout", label)),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        hardware
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(&format!("{} - Pipeline", label)),
+                layout: Some(&pipeline_layout),
+                module: &shader_module,
+                entry_point: "main",
+            })
+    }
+
+    fn init_compute_bind_group(
+        hardware: &Hardware,
+        label: &str,
+        pipeline: &ComputePipeline,
+        bindings: &[Binding],
+    ) -> BindGroup {
+        let entries = bindings
+            .iter()
+            .enumerate()
+            .map(|(n, binding)| wgpu::BindGroupEntry {
+                binding: n as u32,
+                resource: match binding {
+                    Binding::Uniform(buffer)
+                    | Binding::ReadOnly(buffer)
+                    | Binding::ReadWrite(buffer) => buffer.as_entire_binding(),
+                    Binding::UniformRange(buffer, offset, size) => {
+                        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer,
+                            offset: *offset,
+                            size: wgpu::BufferSize::new(*size),
+                        })
+                    }
+                },
+            })
+            .collect::<Vec<_>>();
+
+        hardware
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} - Bind Group", label)),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+    }
 }
@@ -0,0 +1,736 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Deserialize;
+
+use crate::{Camera, NBody, OrbitalElements, Orientation, PlanetarySystemBuilder, Settings, Spin};
+
+// A simulation described by a TOML file:
+//
+//   seed = 42
+//
+//   [settings]            # Any `Settings` field, the rest keep their defaults
+//   time_step = 10.0
+//   solver = "tiled_direct"
+//
+//   [camera]
+//   eye_position = [1.0e3, 0.0, 1.0e3]
+//   target_position = [0.0, 0.0, 0.0]
+//   field_of_view = 90.0  # [degrees]
+//   zoom = 1.0e3
+//
+//   [[components]]        # One per `NBody::add_*` generator call
+//   type = "massive_system2"
+//   radius = 1.0e3
+//   disc_mass = 1.0
+//   num_particles = 4096
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub settings: Settings,
+    pub camera: Camera,
+    pub seed: u64,
+    pub components: Vec<Component>,
+}
+
+// Initial-condition generators, tagged by `type`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Component {
+    MassiveParticle {
+        position: [f32; 3],
+        #[serde(default)]
+        velocity: [f32; 3],
+        mass: f32,
+    },
+    GhostParticle {
+        position: [f32; 3],
+        #[serde(default)]
+        velocity: [f32; 3],
+        #[serde(default)]
+        kind: f32,
+    },
+    MassiveDisc {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        #[serde(default)]
+        orientation: DiscPlane,
+        radius: f32,
+        disc_mass: f32,
+        num_particles: usize,
+    },
+    MassiveSystem {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        #[serde(default)]
+        orientation: DiscPlane,
+        radius: f32,
+        centre_mass: f32,
+        disc_mass: f32,
+        num_particles: usize,
+    },
+    MassiveSystem2 {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        #[serde(default)]
+        orientation: DiscPlane,
+        radius: f32,
+        disc_mass: f32,
+        num_particles: usize,
+    },
+    GhostField {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        #[serde(default)]
+        orientation: DiscPlane,
+        radius: f32,
+        centre_mass: f32,
+        num_particles: usize,
+        #[serde(default)]
+        kind: f32,
+    },
+    PlummerSphere {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        scale_radius: f32,
+        total_mass: f32,
+        num_particles: usize,
+    },
+    HernquistSphere {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        scale_radius: f32,
+        total_mass: f32,
+        num_particles: usize,
+    },
+    NfwHalo {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        scale_radius: f32,
+        concentration: f32,
+        total_mass: f32,
+        num_particles: usize,
+    },
+    KingCluster {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        king_radius: f32,
+        central_potential: f32,
+        total_mass: f32,
+        num_particles: usize,
+    },
+    ExponentialDisc {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        #[serde(default)]
+        orientation: DiscPlane,
+        scale_length: f32,
+        scale_height: f32,
+        disc_mass: f32,
+        toomre_q: f32,
+        #[serde(default)]
+        halo: Option<HaloProfile>,
+        num_particles: usize,
+    },
+    PlanetarySystem {
+        #[serde(default)]
+        centre: [f32; 3],
+        #[serde(default)]
+        drift: [f32; 3],
+        central_mass: f32,
+        bodies: Vec<OrbitingBody>,
+    },
+}
+
+// Hernquist halo whose mass shapes the rotation curve of a disc, as `halo = { mass = 5.0, scale_radius = 10.0 }`
+// (the halo's own particles come from a separate component)
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HaloProfile {
+    pub mass: f32,
+    pub scale_radius: f32,
+}
+
+// A body of a `planetary_system` on a Keplerian orbit about the central mass, with angles in degrees as in
+// catalogues:
+//   [[components.bodies]]
+//   mass = 5.972e24
+//   semi_major_axis = 1.496e11
+//   eccentricity = 0.0167
+//   longitude_of_ascending_node = -11.26
+//   argument_of_periapsis = 114.2
+//   mean_anomaly = 358.6
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrbitingBody {
+    pub mass: f32,
+    pub semi_major_axis: f32,
+    #[serde(default)]
+    pub eccentricity: f32,
+    #[serde(default)]
+    pub inclination: f32, // [degrees]
+    #[serde(default)]
+    pub longitude_of_ascending_node: f32, // [degrees]
+    #[serde(default)]
+    pub argument_of_periapsis: f32, // [degrees]
+    #[serde(default)]
+    pub mean_anomaly: f32, // [degrees]
+}
+
+impl OrbitingBody {
+    pub fn elements(&self) -> OrbitalElements {
+        OrbitalElements {
+            semi_major_axis: self.semi_major_axis,
+            eccentricity: self.eccentricity,
+            inclination: self.inclination.to_radians(),
+            longitude_of_ascending_node: self.longitude_of_ascending_node.to_radians(),
+            argument_of_periapsis: self.argument_of_periapsis.to_radians(),
+            mean_anomaly: self.mean_anomaly.to_radians(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !is_positive(self.mass) {
+            return Err("mass must be positive".to_string());
+        }
+        if !is_positive(self.semi_major_axis) {
+            return Err("semi_major_axis must be positive".to_string());
+        }
+        if !(0.0..1.0).contains(&self.eccentricity) {
+            return Err("eccentricity must be at least 0 and below 1".to_string());
+        }
+        self.elements().validate()
+    }
+}
+
+// Plane and spin of a disc component, as one of
+//   orientation = { spin_axis = [0.0, 1.0, 1.0] }
+//   orientation = { inclination = 30.0, position_angle = 45.0 }  # [degrees]
+//   orientation = { quaternion = [0.92, 0.38, 0.0, 0.0] }         # [w, x, y, z]
+// with `spin = "clockwise"` (the default) or "anticlockwise" seen from the side the disc's normal points to. See
+// `Orientation` for the conventions; without any of them the disc lies in the xy plane.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscPlane {
+    pub spin_axis: Option<[f32; 3]>,
+    pub inclination: Option<f32>,    // [degrees]
+    pub position_angle: Option<f32>, // [degrees]
+    pub quaternion: Option<[f32; 4]>,
+    #[serde(default)]
+    pub spin: Spin,
+}
+
+impl DiscPlane {
+    pub fn orientation(&self) -> Orientation {
+        if let Some(axis) = self.spin_axis {
+            Orientation::from_spin_axis(axis, self.spin)
+        } else if let Some(quaternion) = self.quaternion {
+            Orientation::from_quaternion(quaternion, self.spin)
+        } else {
+            Orientation::from_sky(
+                self.inclination.unwrap_or(0.0).to_radians(),
+                self.position_angle.unwrap_or(0.0).to_radians(),
+                self.spin,
+            )
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let sky = self.inclination.is_some() || self.position_angle.is_some();
+        let forms = [self.spin_axis.is_some(), sky, self.quaternion.is_some()];
+        if forms.iter().filter(|given| **given).count() > 1 {
+            return Err(
+                "orientation takes only one of spin_axis, inclination and position_angle, or quaternion"
+                    .to_string(),
+            );
+        }
+        if self
+            .spin_axis
+            .is_some_and(|axis| axis.iter().all(|x| *x == 0.0))
+        {
+            return Err("orientation.spin_axis must not be zero".to_string());
+        }
+        if self
+            .quaternion
+            .is_some_and(|quaternion| quaternion.iter().all(|x| *x == 0.0))
+        {
+            return Err("orientation.quaternion must not be zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl HaloProfile {
+    // Mass within radius r
+    pub fn enclosed_mass(&self, r: f32) -> f32 {
+        self.mass * (r / (r + self.scale_radius)).powi(2)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Failed to read the scenario: {}", error),
+            Self::Parse(error) => write!(f, "Failed to parse the scenario: {}", error),
+            Self::Invalid(message) => write!(f, "Invalid scenario: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Parse(error) => Some(error),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    settings: Settings,
+    camera: CameraFile,
+    #[serde(default)]
+    components: Vec<Component>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraFile {
+    eye_position: [f32; 3],
+    #[serde(default)]
+    target_position: [f32; 3],
+    field_of_view: f32, // [degrees]
+    zoom: f32,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        std::fs::read_to_string(path)
+            .map_err(ScenarioError::Io)?
+            .parse()
+    }
+
+    // Run every generator in order from a generator seeded with `seed`
+    pub fn initial_conditions(&self) -> NBody {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let grav_const = self.settings.gravitational_constant;
+
+        let mut nbody = NBody::new();
+        for component in &self.components {
+            match *component {
+                Component::MassiveParticle {
+                    position,
+                    velocity,
+                    mass,
+                } => nbody.add_massive_particle(position, velocity, mass),
+                Component::GhostParticle {
+                    position,
+                    velocity,
+                    kind,
+                } => nbody.add_ghost_particle(position, velocity, kind),
+                Component::MassiveDisc {
+                    centre,
+                    drift,
+                    orientation,
+                    radius,
+                    disc_mass,
+                    num_particles,
+                } => nbody.add_massive_disc_oriented(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    orientation.orientation(),
+                    radius,
+                    disc_mass,
+                    num_particles,
+                ),
+                Component::MassiveSystem {
+                    centre,
+                    drift,
+                    orientation,
+                    radius,
+                    centre_mass,
+                    disc_mass,
+                    num_particles,
+                } => nbody.add_massive_system_oriented(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    orientation.orientation(),
+                    radius,
+                    centre_mass,
+                    disc_mass,
+                    num_particles,
+                ),
+                Component::MassiveSystem2 {
+                    centre,
+                    drift,
+                    orientation,
+                    radius,
+                    disc_mass,
+                    num_particles,
+                } => nbody.add_massive_system2_oriented(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    orientation.orientation(),
+                    radius,
+                    disc_mass,
+                    num_particles,
+                ),
+                Component::GhostField {
+                    centre,
+                    drift,
+                    orientation,
+                    radius,
+                    centre_mass,
+                    num_particles,
+                    kind,
+                } => nbody.add_ghost_field_oriented(
+                    &mut rng,
+                    centre,
+                    drift,
+                    orientation.orientation(),
+                    radius,
+                    centre_mass,
+                    num_particles,
+                    kind,
+                ),
+                Component::PlummerSphere {
+                    centre,
+                    drift,
+                    scale_radius,
+                    total_mass,
+                    num_particles,
+                } => nbody.add_plummer_sphere(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    scale_radius,
+                    total_mass,
+                    num_particles,
+                ),
+                Component::HernquistSphere {
+                    centre,
+                    drift,
+                    scale_radius,
+                    total_mass,
+                    num_particles,
+                } => nbody.add_hernquist_sphere(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    scale_radius,
+                    total_mass,
+                    num_particles,
+                ),
+                Component::NfwHalo {
+                    centre,
+                    drift,
+                    scale_radius,
+                    concentration,
+                    total_mass,
+                    num_particles,
+                } => nbody.add_nfw_halo(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    scale_radius,
+                    concentration,
+                    total_mass,
+                    num_particles,
+                ),
+                Component::KingCluster {
+                    centre,
+                    drift,
+                    king_radius,
+                    central_potential,
+                    total_mass,
+                    num_particles,
+                } => nbody.add_king_cluster(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    king_radius,
+                    central_potential,
+                    total_mass,
+                    num_particles,
+                ),
+                Component::ExponentialDisc {
+                    centre,
+                    drift,
+                    orientation,
+                    scale_length,
+                    scale_height,
+                    disc_mass,
+                    toomre_q,
+                    halo,
+                    num_particles,
+                } => nbody.add_exponential_disc_oriented(
+                    &mut rng,
+                    grav_const,
+                    centre,
+                    drift,
+                    orientation.orientation(),
+                    scale_length,
+                    scale_height,
+                    disc_mass,
+                    toomre_q,
+                    |r| halo.map_or(0.0, |halo| halo.enclosed_mass(r)),
+                    num_particles,
+                ),
+                Component::PlanetarySystem {
+                    centre,
+                    drift,
+                    central_mass,
+                    ref bodies,
+                } => bodies
+                    .iter()
+                    .fold(
+                        PlanetarySystemBuilder::new(central_mass),
+                        |builder, body| builder.body(body.elements(), body.mass),
+                    )
+                    .add_to(grav_const, centre, drift, &mut nbody)
+                    .expect(
+                        "planetary systems must be valid, as checked when a scenario is parsed",
+                    ),
+            }
+        }
+
+        nbody
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let file: ScenarioFile = toml::from_str(text).map_err(ScenarioError::Parse)?;
+
+        file.settings
+            .validate()
+            .map_err(|message| ScenarioError::Invalid(format!("settings.{}", message)))?;
+
+        let camera = &file.camera;
+        if !(camera.field_of_view > 0.0 && camera.field_of_view < 180.0) {
+            return Err(ScenarioError::Invalid(
+                "camera.field_of_view must be between 0 and 180 degrees".to_string(),
+            ));
+        }
+        if !is_positive(camera.zoom) {
+            return Err(ScenarioError::Invalid(
+                "camera.zoom must be positive".to_string(),
+            ));
+        }
+
+        for (index, component) in file.components.iter().enumerate() {
+            component.validate().map_err(|message| {
+                ScenarioError::Invalid(format!("components[{}].{}", index, message))
+            })?;
+        }
+
+        Ok(Self {
+            settings: file.settings,
+            camera: Camera::new(
+                camera.eye_position,
+                camera.target_position,
+                camera.field_of_view.to_radians(),
+                camera.zoom,
+            ),
+            seed: file.seed,
+            components: file.components,
+        })
+    }
+}
+
+impl Component {
+    // The first invalid parameter, described for the user
+    fn validate(&self) -> Result<(), String> {
+        if let Self::MassiveDisc { orientation, .. }
+        | Self::MassiveSystem { orientation, .. }
+        | Self::MassiveSystem2 { orientation, .. }
+        | Self::GhostField { orientation, .. }
+        | Self::ExponentialDisc { orientation, .. } = self
+        {
+            orientation.validate()?;
+        }
+
+        let (num_particles, positive): (Option<usize>, &[(&str, f32)]) = match self {
+            Self::MassiveParticle { mass, .. } => (None, &[("mass", *mass)]),
+            Self::GhostParticle { .. } => (None, &[]),
+            Self::MassiveDisc {
+                radius,
+                disc_mass,
+                num_particles,
+                ..
+            }
+            | Self::MassiveSystem2 {
+                radius,
+                disc_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[("radius", *radius), ("disc_mass", *disc_mass)],
+            ),
+            Self::MassiveSystem {
+                radius,
+                centre_mass,
+                disc_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[
+                    ("radius", *radius),
+                    ("centre_mass", *centre_mass),
+                    ("disc_mass", *disc_mass),
+                ],
+            ),
+            Self::GhostField {
+                radius,
+                centre_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[("radius", *radius), ("centre_mass", *centre_mass)],
+            ),
+            Self::PlummerSphere {
+                scale_radius,
+                total_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[("scale_radius", *scale_radius), ("total_mass", *total_mass)],
+            ),
+            Self::HernquistSphere {
+                scale_radius,
+                total_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[("scale_radius", *scale_radius), ("total_mass", *total_mass)],
+            ),
+            Self::NfwHalo {
+                scale_radius,
+                concentration,
+                total_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[
+                    ("scale_radius", *scale_radius),
+                    ("concentration", *concentration),
+                    ("total_mass", *total_mass),
+                ],
+            ),
+            Self::KingCluster {
+                king_radius,
+                central_potential,
+                total_mass,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                &[
+                    ("king_radius", *king_radius),
+                    ("central_potential", *central_potential),
+                    ("total_mass", *total_mass),
+                ],
+            ),
+            Self::ExponentialDisc {
+                scale_length,
+                scale_height,
+                disc_mass,
+                toomre_q,
+                halo,
+                num_particles,
+                ..
+            } => (
+                Some(*num_particles),
+                match halo {
+                    Some(halo) => &[
+                        ("scale_length", *scale_length),
+                        ("scale_height", *scale_height),
+                        ("disc_mass", *disc_mass),
+                        ("toomre_q", *toomre_q),
+                        ("halo.mass", halo.mass),
+                        ("halo.scale_radius", halo.scale_radius),
+                    ],
+                    None => &[
+                        ("scale_length", *scale_length),
+                        ("scale_height", *scale_height),
+                        ("disc_mass", *disc_mass),
+                        ("toomre_q", *toomre_q),
+                    ],
+                },
+            ),
+            Self::PlanetarySystem {
+                central_mass,
+                bodies,
+                ..
+            } => {
+                for (index, body) in bodies.iter().enumerate() {
+                    body.validate()
+                        .map_err(|message| format!("bodies[{}].{}", index, message))?;
+                }
+                (None, &[("central_mass", *central_mass)])
+            }
+        };
+
+        if num_particles == Some(0) {
+            return Err("num_particles must be positive".to_string());
+        }
+        if let Some((name, _)) = positive.iter().find(|(_, value)| !is_positive(*value)) {
+            return Err(format!("{} must be positive", name));
+        }
+
+        Ok(())
+    }
+}
+
+// False for NaN as well as for zero and negative values
+fn is_positive(value: f32) -> bool {
+    value > 0.0
+}
@@ -1,5 +1,10 @@
+use serde::Deserialize;
+
+use crate::{Backend, Integrator, MassAssignment, MeshBoundary, Solver};
+
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Settings {
     pub display_width: f32,
     pub display_height: f32,
@@ -13,22 +18,119 @@ pub struct Settings {
     pub ghost_stack_visible_limit: f32,
 
     pub blur_radius: f32,
+
+    pub opening_angle: f32, // Barnes-Hut theta (tree solver only)
+
+    // Host-only options (not uploaded to the GPU)
+    pub integrator: Integrator,
+    pub solver: Solver, // Tree and particle-mesh buffers only exist for the solver selected at creation
+    pub tile_size: u32, // Bodies per workgroup memory tile of the tiled direct sum (fixed when the simulation is created)
+    pub mesh_size: u32, // Particle-mesh cells per side (fixed when the simulation is created)
+    pub mass_assignment: MassAssignment,
+    pub mesh_boundary: MeshBoundary, // Fixed when the simulation is created
+    pub mesh_box_size: f32, // Side of the mesh's cube about the origin (0 to fit it to the particles every step)
+    pub backend: Backend,
+    pub force_fallback_adapter: bool, // Only accept a software adapter (fixed when the simulation is created)
+    pub checkpoint_interval: u32,     // Steps between automatic checkpoints (0 to disable)
+    pub relaxation_steps: u32, // Damped leapfrog steps run before the simulation starts (0 to disable)
+    pub relaxation_damping: f32, // Rate at which velocities about the centre of mass decay during the relaxation [1/s]
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            display_width: 1300.0,
+            display_height: 1300.0,
+            pixel_size: 1.0,
+            gravitational_constant: 1.0,
+            time_step: 1.0e1,
+            smoothing_length: 1.0,
+            ghost_mass: 1.0,
+            ghost_stack_visible_limit: 4.0,
+            blur_radius: 5.0,
+            opening_angle: 0.5,
+            integrator: Integrator::default(),
+            solver: Solver::default(),
+            tile_size: 64,
+            mesh_size: 64,
+            mass_assignment: MassAssignment::default(),
+            mesh_boundary: MeshBoundary::default(),
+            mesh_box_size: 0.0,
+            backend: Backend::default(),
+            force_fallback_adapter: false,
+            checkpoint_interval: 0,
+            relaxation_steps: 0,
+            relaxation_damping: 0.0,
+        }
+    }
 }
 
 impl Settings {
     pub fn is_valid(&self) -> bool {
-        self.display_width > 0.0
-            && self.display_height > 0.0
-            && self.pixel_size > 0.0
-            && self.gravitational_constant > 0.0
-            && self.time_step > 0.0
-            && self.smoothing_length > 0.0
-            && self.ghost_mass > 0.0
-            && self.ghost_stack_visible_limit >= 1.0
-            && self.blur_radius >= 0.0
+        self.validate().is_ok()
+    }
+
+    // The first invalid field, described for the user
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [
+            (self.display_width > 0.0, "display_width must be positive"),
+            (self.display_height > 0.0, "display_height must be positive"),
+            (self.pixel_size > 0.0, "pixel_size must be positive"),
+            (
+                self.gravitational_constant > 0.0,
+                "gravitational_constant must be positive",
+            ),
+            (self.time_step > 0.0, "time_step must be positive"),
+            (
+                self.smoothing_length > 0.0,
+                "smoothing_length must be positive",
+            ),
+            (self.ghost_mass > 0.0, "ghost_mass must be positive"),
+            (
+                self.ghost_stack_visible_limit >= 1.0,
+                "ghost_stack_visible_limit must be at least 1",
+            ),
+            (self.blur_radius >= 0.0, "blur_radius must not be negative"),
+            (
+                self.opening_angle >= 0.0,
+                "opening_angle must not be negative",
+            ),
+            // Jerks come from the direct sum, so an approximate solver would cost as much and mix two force models
+            (
+                self.integrator != Integrator::Hermite
+                    || matches!(self.solver, Solver::Direct | Solver::TiledDirect),
+                "the hermite integrator requires the direct or tiled_direct solver",
+            ),
+            (
+                self.relaxation_damping >= 0.0,
+                "relaxation_damping must not be negative",
+            ),
+            (
+                self.tile_size % 64 == 0 && (64..=1024).contains(&self.tile_size),
+                "tile_size must be a multiple of 64 from 64 to 1024",
+            ),
+            (
+                self.mesh_size.is_power_of_two() && (16..=128).contains(&self.mesh_size),
+                "mesh_size must be a power of two from 16 to 128",
+            ),
+            (
+                self.mesh_box_size >= 0.0,
+                "mesh_box_size must not be negative",
+            ),
+            (
+                self.mesh_boundary != MeshBoundary::Periodic || self.mesh_box_size > 0.0,
+                "periodic mesh boundaries require a positive mesh_box_size",
+            ),
+        ];
+
+        match checks.iter().find(|(valid, _)| !valid) {
+            Some((_, message)) => Err(message.to_string()),
+            None => Ok(()),
+        }
     }
 
     pub fn as_slice(&self) -> &[f32] {
-        unsafe { std::slice::from_raw_parts(self as *const _ as *const f32, 9) }
+        // Only the leading f32 fields are shared with the shaders.
+        unsafe { std::slice::from_raw_parts(self as *const _ as *const f32, 10) }
     }
 }
@@ -0,0 +1,142 @@
+// Binary radix tree over the sorted Morton keys (Karras 2012): internal node i splits the
+// keys where its common prefix ends. Children with LEAF_FLAG set index sorted particles.
+// Each node also records itself as the parent of its children, for the bottom-up moments.
+
+const LEAF_FLAG: u32 = 0x80000000u;
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<storage, read> tree_keys: array<u32>;
+
+@group(0)
+@binding(1)
+var<storage, read> tree_bounds: array<u32>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> tree_nodes: array<vec4<u32>>; // [left, right, first, last]
+
+@group(0)
+@binding(3)
+var<storage, read_write> tree_parents: array<u32>; // Internal nodes, then leaves
+
+@group(0)
+@binding(4)
+var<storage, read_write> tree_sizes: array<f32>;
+
+@group(0)
+@binding(5)
+var<uniform> counts: Counts;
+
+fn from_ordered_key(key: u32) -> f32 {
+    if (key & 0x80000000u) != 0u {
+        return bitcast<f32>(key & 0x7FFFFFFFu);
+    }
+    return bitcast<f32>(~key);
+}
+
+// Length of the common prefix of keys i and j (duplicate keys are told apart by their index)
+fn delta(i: i32, j: i32) -> i32 {
+    if j < 0 || j >= i32(counts.num_massive_particles) {
+        return -1;
+    }
+    let key_i = tree_keys[i];
+    let key_j = tree_keys[j];
+    if key_i == key_j {
+        return 32 + i32(countLeadingZeros(u32(i) ^ u32(j)));
+    }
+    return i32(countLeadingZeros(key_i ^ key_j));
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let num_keys = counts.num_massive_particles;
+
+    if num_keys < 2u || global_id.x >= num_keys - 1u {
+        return;
+    }
+
+    let i = i32(global_id.x);
+
+    // Direction of the range and its far end
+    var d = 1;
+    if delta(i, i + 1) < delta(i, i - 1) {
+        d = -1;
+    }
+    let delta_min = delta(i, i - d);
+    var length_max = 2;
+    while delta(i, i + length_max * d) > delta_min {
+        length_max = length_max * 2;
+    }
+    var length = 0;
+    for (var t = length_max / 2; t >= 1; t = t / 2) {
+        if delta(i, i + (length + t) * d) > delta_min {
+            length = length + t;
+        }
+    }
+    let j = i + length * d;
+
+    // Split position
+    let delta_node = delta(i, j);
+    var split = 0;
+    var t = length;
+    loop {
+        t = (t + 1) / 2;
+        if delta(i, i + (split + t) * d) > delta_node {
+            split = split + t;
+        }
+        if t <= 1 {
+            break;
+        }
+    }
+    let gamma = i + split * d + min(d, 0);
+
+    let first = min(i, j);
+    let last = max(i, j);
+    var left = u32(gamma);
+    if first == gamma {
+        left = left | LEAF_FLAG;
+    }
+    var right = u32(gamma + 1);
+    if last == gamma + 1 {
+        right = right | LEAF_FLAG;
+    }
+    tree_nodes[i] = vec4<u32>(left, right, u32(first), u32(last));
+
+    // Leaves follow the num_keys - 1 internal nodes
+    var left_slot = u32(gamma);
+    if first == gamma {
+        left_slot = left_slot + num_keys - 1u;
+    }
+    var right_slot = u32(gamma + 1);
+    if last == gamma + 1 {
+        right_slot = right_slot + num_keys - 1u;
+    }
+    tree_parents[left_slot] = u32(i);
+    tree_parents[right_slot] = u32(i);
+
+    // Side of the octree cell holding the shared prefix (the keys start with two unused bits)
+    let upper = vec3<f32>(
+        from_ordered_key(tree_bounds[0]),
+        from_ordered_key(tree_bounds[1]),
+        from_ordered_key(tree_bounds[2]),
+    );
+    let lower = vec3<f32>(
+        from_ordered_key(~tree_bounds[3]),
+        from_ordered_key(~tree_bounds[4]),
+        from_ordered_key(~tree_bounds[5]),
+    );
+    let extent = upper - lower;
+    var side = max(extent.x, max(extent.y, extent.z));
+    if side <= 0.0 {
+        side = 1.0;
+    }
+    let prefix_bits = u32(clamp(delta_node - 2, 0, 30));
+    tree_sizes[i] = side / f32(1u << (prefix_bits / 3u));
+}
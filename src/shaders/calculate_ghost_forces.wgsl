@@ -11,6 +11,13 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
 };
 
 @group(0)
@@ -29,12 +36,20 @@ var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
 @binding(3)
 var<storage, read_write> ghost_forces_and_kinds: array<vec4<f32>>;
 
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
-    let num_massive_bodies = arrayLength(&massive_positions_and_masses);
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
+    let num_massive_bodies = counts.num_massive_particles;
 
     let p0x = ghost_positions_and_kinds[n].x;
     let p0y = ghost_positions_and_kinds[n].y;
@@ -42,10 +57,6 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     var total_force = vec4<f32>(0.0, 0.0, 0.0, 0.0);
     for (var i = 0u; i < num_massive_bodies; i = i + 1u) {
-        if i == n {
-            continue;
-        }
-
         let p1x = massive_positions_and_masses[i].x;
         let p1y = massive_positions_and_masses[i].y;
         let p1z = massive_positions_and_masses[i].z;
@@ -11,6 +11,18 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Step {
+    kick_fraction: f32,
+    drift_fraction: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
 };
 
 @group(0)
@@ -25,16 +37,28 @@ var<storage, read> ghost_velocities_and_kinds: array<vec4<f32>>;
 @binding(2)
 var<storage, read_write> ghost_positions_and_kinds: array<vec4<f32>>;
 
+@group(0)
+@binding(3)
+var<uniform> step: Step;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
     let vx = ghost_velocities_and_kinds[n].x;
     let vy = ghost_velocities_and_kinds[n].y;
     let vz = ghost_velocities_and_kinds[n].z;
 
-    ghost_positions_and_kinds[n].x += vx * settings.time_step;
-    ghost_positions_and_kinds[n].y += vy * settings.time_step;
-    ghost_positions_and_kinds[n].z += vz * settings.time_step;
+    ghost_positions_and_kinds[n].x += vx * settings.time_step * step.drift_fraction;
+    ghost_positions_and_kinds[n].y += vy * settings.time_step * step.drift_fraction;
+    ghost_positions_and_kinds[n].z += vz * settings.time_step * step.drift_fraction;
 }
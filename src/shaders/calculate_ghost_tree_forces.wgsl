@@ -0,0 +1,118 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> ghost_positions_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read> tree_indices: array<u32>;
+
+@group(0)
+@binding(4)
+var<storage, read> tree_nodes: array<vec4<u32>>;
+
+@group(0)
+@binding(5)
+var<storage, read> tree_moments: array<vec4<f32>>;
+
+@group(0)
+@binding(6)
+var<storage, read> tree_sizes: array<f32>;
+
+@group(0)
+@binding(7)
+var<storage, read_write> ghost_forces_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(8)
+var<uniform> counts: Counts;
+
+const LEAF_FLAG: u32 = 0x80000000u;
+const STACK_SIZE: u32 = 64u; // Tree depth is bounded by the 62 bits of key plus index
+
+fn attraction(dx: vec3<f32>, m0: f32, m1: f32) -> vec3<f32> {
+    let r2 = dot(dx, dx) + (settings.smoothing_length * settings.smoothing_length);
+    let r = sqrt(r2);
+    let f = (settings.gravitational_constant * m0 * m1) / r2;
+
+    return f * dx / r;
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+    let num_massive_bodies = counts.num_massive_particles;
+
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
+    let p0 = ghost_positions_and_kinds[n].xyz;
+    let m0 = settings.ghost_mass;
+    let theta2 = settings.opening_angle * settings.opening_angle;
+
+    var stack: array<u32, 64>;
+    var top = 1u;
+    if num_massive_bodies == 1u {
+        stack[0] = LEAF_FLAG;
+    } else {
+        stack[0] = 0u;
+    }
+
+    var total_force = vec3<f32>(0.0, 0.0, 0.0);
+    while top > 0u {
+        top = top - 1u;
+        let node = stack[top];
+
+        if (node & LEAF_FLAG) != 0u {
+            let p1 = massive_positions_and_masses[tree_indices[node & ~LEAF_FLAG]];
+            total_force = total_force + attraction(p1.xyz - p0, m0, p1.w);
+            continue;
+        }
+
+        let moments = tree_moments[node];
+        let dx = moments.xyz - p0;
+        let size = tree_sizes[node];
+        if (size * size > theta2 * dot(dx, dx)) && (top + 2u <= STACK_SIZE) {
+            let children = tree_nodes[node];
+            stack[top] = children.x;
+            stack[top + 1u] = children.y;
+            top = top + 2u;
+            continue;
+        }
+
+        total_force = total_force + attraction(dx, m0, moments.w);
+    }
+
+    ghost_forces_and_kinds[n] = vec4<f32>(total_force, 0.0);
+}
@@ -11,6 +11,20 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Step {
+    kick_fraction: f32,
+    drift_fraction: f32,
+    damping: f32, // Fraction of the velocity relative to `frame_velocity` removed before the kick (only while relaxing)
+    frame_velocity: vec3<f32>, // Centre of mass velocity while relaxing
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
 };
 
 @group(0)
@@ -25,16 +39,32 @@ var<storage, read> ghost_forces_and_kinds: array<vec4<f32>>;
 @binding(2)
 var<storage, read_write> ghost_velocities_and_kinds: array<vec4<f32>>;
 
+@group(0)
+@binding(3)
+var<uniform> step: Step;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
     let fx = ghost_forces_and_kinds[n].x;
     let fy = ghost_forces_and_kinds[n].y;
     let fz = ghost_forces_and_kinds[n].z;
 
-    ghost_velocities_and_kinds[n].x += fx * settings.time_step / settings.ghost_mass;
-    ghost_velocities_and_kinds[n].y += fy * settings.time_step / settings.ghost_mass;
-    ghost_velocities_and_kinds[n].z += fz * settings.time_step / settings.ghost_mass;
+    let retained = 1.0 - step.damping;
+    let velocity = ghost_velocities_and_kinds[n].xyz;
+    let damped = step.frame_velocity + (velocity - step.frame_velocity) * retained;
+    let kicked = damped + vec3<f32>(fx, fy, fz) * settings.time_step * step.kick_fraction / settings.ghost_mass;
+    ghost_velocities_and_kinds[n].x = kicked.x;
+    ghost_velocities_and_kinds[n].y = kicked.y;
+    ghost_velocities_and_kinds[n].z = kicked.z;
 }
@@ -0,0 +1,61 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> ghost_previous_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> ghost_forces_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> ghost_velocities_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
+    let dt = settings.time_step;
+    let mass = settings.ghost_mass;
+
+    // Average of the accelerations at the start and end of the step
+    let dv = (ghost_previous_forces[n].xyz + ghost_forces_and_kinds[n].xyz) * dt / (2.0 * mass);
+
+    ghost_velocities_and_kinds[n].x += dv.x;
+    ghost_velocities_and_kinds[n].y += dv.y;
+    ghost_velocities_and_kinds[n].z += dv.z;
+}
@@ -11,6 +11,13 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
 };
 
 @group(0)
@@ -25,12 +32,20 @@ var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
 @binding(2)
 var<storage, read_write> massive_forces: array<vec4<f32>>;
 
+@group(0)
+@binding(3)
+var<uniform> counts: Counts;
+
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
-    let num_massive_bodies = arrayLength(&massive_positions_and_masses);
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let num_massive_bodies = counts.num_massive_particles;
 
     let p0x = massive_positions_and_masses[n].x;
     let p0y = massive_positions_and_masses[n].y;
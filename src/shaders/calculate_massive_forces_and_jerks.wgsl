@@ -0,0 +1,87 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> massive_velocities_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> massive_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> massive_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<uniform> counts: Counts;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let num_massive_bodies = counts.num_massive_particles;
+
+    let p0 = massive_positions_and_masses[n].xyz;
+    let v0 = massive_velocities_and_masses[n].xyz;
+    let m0 = massive_positions_and_masses[n].w;
+
+    var total_force = vec3<f32>(0.0, 0.0, 0.0);
+    var total_jerk = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i = 0u; i < num_massive_bodies; i = i + 1u) {
+        if i == n {
+            continue;
+        }
+
+        let m1 = massive_positions_and_masses[i].w;
+
+        let dp = massive_positions_and_masses[i].xyz - p0;
+        let dv = massive_velocities_and_masses[i].xyz - v0;
+
+        let r2 = dot(dp, dp) + (settings.smoothing_length * settings.smoothing_length);
+        let inv_r = 1.0 / sqrt(r2);
+        let inv_r3 = inv_r * inv_r * inv_r;
+        let gmm = settings.gravitational_constant * m0 * m1;
+
+        // Force and its time derivative for the softened potential
+        let rv = dot(dp, dv) * inv_r * inv_r;
+        total_force += gmm * inv_r3 * dp;
+        total_jerk += gmm * inv_r3 * (dv - 3.0 * rv * dp);
+    }
+
+    massive_forces[n] = vec4<f32>(total_force, 0.0);
+    massive_jerks[n] = vec4<f32>(total_jerk, 0.0);
+}
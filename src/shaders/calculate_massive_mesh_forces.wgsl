@@ -0,0 +1,156 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+struct Mesh {
+    size: u32,
+    padded_size: u32, // Twice the size for isolated boundaries, the size for periodic ones
+    assignment: u32, // 0: NGP, 1: CIC, 2: TSC
+    mass_scale: f32, // Fixed-point units per unit mass
+    box_size: f32, // Side of the cube about the origin (0 to fit the particles)
+    periodic: u32,
+};
+
+@group(0)
+@binding(1)
+var<uniform> mesh: Mesh;
+
+@group(0)
+@binding(2)
+var<storage, read> mesh_frame: array<u32>; // [cell size, G, smoothing length, stale, origin xyz, -]
+
+@group(0)
+@binding(3)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read> mesh_potential: array<vec2<f32>>;
+
+@group(0)
+@binding(5)
+var<storage, read_write> massive_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(6)
+var<uniform> counts: Counts;
+
+fn mesh_cell_size() -> f32 {
+    return bitcast<f32>(mesh_frame[0]);
+}
+
+// Grid coordinates of a position, wrapped into the mesh for periodic boundaries
+fn grid_coordinates(position: vec3<f32>) -> vec3<f32> {
+    let origin = vec3<f32>(bitcast<f32>(mesh_frame[4]), bitcast<f32>(mesh_frame[5]), bitcast<f32>(mesh_frame[6]));
+    let u = (position - origin) / mesh_cell_size();
+    if mesh.periodic != 0u {
+        let size = f32(mesh.size);
+        return u - size * floor(u / size);
+    }
+    return u;
+}
+
+// An isolated mesh only holds the particles within its four cell margin (all of them unless its box is fixed)
+fn on_mesh(u: vec3<f32>) -> bool {
+    let upper = vec3<f32>(f32(mesh.size) - 3.0);
+    return mesh.periodic != 0u || (all(u >= vec3<f32>(3.0)) && all(u <= upper));
+}
+
+struct Stencil {
+    start: i32,
+    weights: vec3<f32>,
+};
+
+// Cells touched along one axis by a particle at grid coordinate u
+fn stencil(u: f32) -> Stencil {
+    switch mesh.assignment {
+        case 0u: {
+            return Stencil(i32(round(u)), vec3<f32>(1.0, 0.0, 0.0));
+        }
+        case 1u: {
+            let cell = floor(u);
+            let f = u - cell;
+            return Stencil(i32(cell), vec3<f32>(1.0 - f, f, 0.0));
+        }
+        default: {
+            let cell = round(u);
+            let d = u - cell;
+            return Stencil(
+                i32(cell) - 1,
+                vec3<f32>(0.5 * (0.5 - d) * (0.5 - d), 0.75 - d * d, 0.5 * (0.5 + d) * (0.5 + d)),
+            );
+        }
+    }
+}
+
+fn potential(x: i32, y: i32, z: i32) -> f32 {
+    let size = i32(mesh.padded_size);
+    let index = ((x + size) % size) + size * (((y + size) % size) + size * ((z + size) % size));
+    return mesh_potential[index].x;
+}
+
+// Fourth-order finite difference of the potential at a mesh point
+fn gradient(x: i32, y: i32, z: i32, cell_size: f32) -> vec3<f32> {
+    let gx = 8.0 * (potential(x + 1, y, z) - potential(x - 1, y, z)) - (potential(x + 2, y, z) - potential(x - 2, y, z));
+    let gy = 8.0 * (potential(x, y + 1, z) - potential(x, y - 1, z)) - (potential(x, y + 2, z) - potential(x, y - 2, z));
+    let gz = 8.0 * (potential(x, y, z + 1) - potential(x, y, z - 1)) - (potential(x, y, z + 2) - potential(x, y, z - 2));
+    return vec3<f32>(gx, gy, gz) / (12.0 * cell_size);
+}
+
+// Interpolate the mesh force back with the same stencil used for the mass assignment
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let u = grid_coordinates(massive_positions_and_masses[n].xyz);
+    if !on_mesh(u) {
+        massive_forces[n] = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return;
+    }
+    let cell_size = mesh_cell_size();
+
+    let sx = stencil(u.x);
+    let sy = stencil(u.y);
+    let sz = stencil(u.z);
+    var acceleration = vec3<f32>(0.0, 0.0, 0.0);
+    for (var k = 0; k < 3; k = k + 1) {
+        for (var j = 0; j < 3; j = j + 1) {
+            for (var i = 0; i < 3; i = i + 1) {
+                let weight = sx.weights[i] * sy.weights[j] * sz.weights[k];
+                if weight <= 0.0 {
+                    continue;
+                }
+                acceleration = acceleration - weight * gradient(sx.start + i, sy.start + j, sz.start + k, cell_size);
+            }
+        }
+    }
+
+    massive_forces[n] = vec4<f32>(massive_positions_and_masses[n].w * acceleration, 0.0);
+}
@@ -11,6 +11,18 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Step {
+    kick_fraction: f32,
+    drift_fraction: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
 };
 
 @group(0)
@@ -25,16 +37,28 @@ var<storage, read> massive_velocities_and_masses: array<vec4<f32>>;
 @binding(2)
 var<storage, read_write> massive_positions_and_masses: array<vec4<f32>>;
 
+@group(0)
+@binding(3)
+var<uniform> step: Step;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
     let vx = massive_velocities_and_masses[n].x;
     let vy = massive_velocities_and_masses[n].y;
     let vz = massive_velocities_and_masses[n].z;
 
-    massive_positions_and_masses[n].x += vx * settings.time_step;
-    massive_positions_and_masses[n].y += vy * settings.time_step;
-    massive_positions_and_masses[n].z += vz * settings.time_step;
+    massive_positions_and_masses[n].x += vx * settings.time_step * step.drift_fraction;
+    massive_positions_and_masses[n].y += vy * settings.time_step * step.drift_fraction;
+    massive_positions_and_masses[n].z += vz * settings.time_step * step.drift_fraction;
 }
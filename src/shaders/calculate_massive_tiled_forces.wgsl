@@ -0,0 +1,94 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> massive_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<uniform> counts: Counts;
+
+const TILE_SIZE: u32 = 64u; // Replaced with the configured tile size when the pipeline is built
+
+var<workgroup> tile: array<vec4<f32>, TILE_SIZE>;
+
+// Each workgroup stages blocks of bodies in workgroup memory and every thread sums over the block
+@compute
+@workgroup_size(64, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let n = global_id.x;
+
+    let num_massive_bodies = counts.num_massive_particles;
+
+    var p0 = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    if n < num_massive_bodies {
+        p0 = massive_positions_and_masses[n];
+    }
+
+    var total_force = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var start = 0u; start < num_massive_bodies; start = start + TILE_SIZE) {
+        for (var k = local_index; k < TILE_SIZE; k = k + 64u) {
+            if start + k < num_massive_bodies {
+                tile[k] = massive_positions_and_masses[start + k];
+            }
+        }
+        workgroupBarrier();
+
+        let tile_length = min(TILE_SIZE, num_massive_bodies - start);
+        for (var k = 0u; k < tile_length; k = k + 1u) {
+            if start + k == n {
+                continue;
+            }
+
+            let p1 = tile[k];
+
+            let dx = p1.x - p0.x;
+            let dy = p1.y - p0.y;
+            let dz = p1.z - p0.z;
+
+            let r2 = (dx * dx + dy * dy + dz * dz) + (settings.smoothing_length * settings.smoothing_length);
+            let r = sqrt(r2);
+            let f = (settings.gravitational_constant * p0.w * p1.w) / r2;
+
+            total_force.x = total_force.x + (f * dx / r);
+            total_force.y = total_force.y + (f * dy / r);
+            total_force.z = total_force.z + (f * dz / r);
+        }
+        workgroupBarrier();
+    }
+
+    if n < num_massive_bodies {
+        massive_forces[n] = total_force;
+    }
+}
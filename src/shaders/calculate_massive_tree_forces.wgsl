@@ -0,0 +1,144 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> tree_indices: array<u32>;
+
+@group(0)
+@binding(3)
+var<storage, read> tree_nodes: array<vec4<u32>>;
+
+@group(0)
+@binding(4)
+var<storage, read> tree_moments: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<storage, read> tree_sizes: array<f32>;
+
+@group(0)
+@binding(6)
+var<storage, read_write> massive_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(7)
+var<uniform> counts: Counts;
+
+const LEAF_FLAG: u32 = 0x80000000u;
+const STACK_SIZE: u32 = 64u; // Tree depth is bounded by the 62 bits of key plus index
+
+fn attraction(dx: vec3<f32>, m0: f32, m1: f32) -> vec3<f32> {
+    let r2 = dot(dx, dx) + (settings.smoothing_length * settings.smoothing_length);
+    let r = sqrt(r2);
+    let f = (settings.gravitational_constant * m0 * m1) / r2;
+
+    return f * dx / r;
+}
+
+// One thread per body in Morton order, so neighbouring threads walk similar paths
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let s = global_id.x;
+    let num_massive_bodies = counts.num_massive_particles;
+
+    if s >= num_massive_bodies {
+        return;
+    }
+
+    let n = tree_indices[s];
+    let p0 = massive_positions_and_masses[n].xyz;
+    let m0 = massive_positions_and_masses[n].w;
+    let theta2 = settings.opening_angle * settings.opening_angle;
+
+    var stack: array<u32, 64>;
+    var top = 1u;
+    if num_massive_bodies == 1u {
+        stack[0] = LEAF_FLAG;
+    } else {
+        stack[0] = 0u;
+    }
+
+    var total_force = vec3<f32>(0.0, 0.0, 0.0);
+    while top > 0u {
+        top = top - 1u;
+        let node = stack[top];
+
+        if (node & LEAF_FLAG) != 0u {
+            let leaf = node & ~LEAF_FLAG;
+            if leaf == s {
+                continue;
+            }
+            let p1 = massive_positions_and_masses[tree_indices[leaf]];
+            total_force = total_force + attraction(p1.xyz - p0, m0, p1.w);
+            continue;
+        }
+
+        // Open nodes containing this body or subtending more than theta
+        let moments = tree_moments[node];
+        let dx = moments.xyz - p0;
+        let size = tree_sizes[node];
+        let children = tree_nodes[node];
+        let contains_self = s >= children.z && s <= children.w;
+        if (contains_self || (size * size > theta2 * dot(dx, dx))) && (top + 2u <= STACK_SIZE) {
+            stack[top] = children.x;
+            stack[top + 1u] = children.y;
+            top = top + 2u;
+            continue;
+        }
+
+        // Out of stack (the pop left one slot): keep descending towards this body alone and take the other
+        // child whole, so the body never attracts itself
+        if contains_self {
+            var inner = children.y;
+            var outer = children.x;
+            if s <= (children.x & ~LEAF_FLAG) {
+                inner = children.x;
+                outer = children.y;
+            }
+            stack[top] = inner;
+            top = top + 1u;
+
+            var outer_moments: vec4<f32>;
+            if (outer & LEAF_FLAG) != 0u {
+                outer_moments = massive_positions_and_masses[tree_indices[outer & ~LEAF_FLAG]];
+            } else {
+                outer_moments = tree_moments[outer];
+            }
+            total_force = total_force + attraction(outer_moments.xyz - p0, m0, outer_moments.w);
+            continue;
+        }
+
+        total_force = total_force + attraction(dx, m0, moments.w);
+    }
+
+    massive_forces[n] = vec4<f32>(total_force, 0.0);
+}
@@ -11,6 +11,20 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Step {
+    kick_fraction: f32,
+    drift_fraction: f32,
+    damping: f32, // Fraction of the velocity relative to `frame_velocity` removed before the kick (only while relaxing)
+    frame_velocity: vec3<f32>, // Centre of mass velocity while relaxing
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
 };
 
 @group(0)
@@ -25,18 +39,34 @@ var<storage, read> massive_forces: array<vec4<f32>>;
 @binding(2)
 var<storage, read_write> massive_velocities_and_masses: array<vec4<f32>>;
 
+@group(0)
+@binding(3)
+var<uniform> step: Step;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
     let fx = massive_forces[n].x;
     let fy = massive_forces[n].y;
     let fz = massive_forces[n].z;
 
     let mass = massive_velocities_and_masses[n].w;
 
-    massive_velocities_and_masses[n].x += fx * settings.time_step / mass;
-    massive_velocities_and_masses[n].y += fy * settings.time_step / mass;
-    massive_velocities_and_masses[n].z += fz * settings.time_step / mass;
+    let retained = 1.0 - step.damping;
+    let velocity = massive_velocities_and_masses[n].xyz;
+    let damped = step.frame_velocity + (velocity - step.frame_velocity) * retained;
+    let kicked = damped + vec3<f32>(fx, fy, fz) * settings.time_step * step.kick_fraction / mass;
+    massive_velocities_and_masses[n].x = kicked.x;
+    massive_velocities_and_masses[n].y = kicked.y;
+    massive_velocities_and_masses[n].z = kicked.z;
 }
@@ -0,0 +1,54 @@
+// Bounds of the massive and ghost particles together, in the same order-preserving u32 keys as the tree bounds:
+// [max_x, max_y, max_z, ~min_x, ~min_y, ~min_z] (the buffer is cleared to zero beforehand)
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(1)
+var<storage, read> ghost_positions_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> mesh_bounds: array<atomic<u32>>;
+
+@group(0)
+@binding(3)
+var<uniform> counts: Counts;
+
+fn ordered_key(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    if (bits & 0x80000000u) != 0u {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+fn expand_bounds(p: vec4<f32>) {
+    atomicMax(&mesh_bounds[0], ordered_key(p.x));
+    atomicMax(&mesh_bounds[1], ordered_key(p.y));
+    atomicMax(&mesh_bounds[2], ordered_key(p.z));
+    atomicMax(&mesh_bounds[3], ~ordered_key(p.x));
+    atomicMax(&mesh_bounds[4], ~ordered_key(p.y));
+    atomicMax(&mesh_bounds[5], ~ordered_key(p.z));
+}
+
+// Dispatched over the larger of the two particle counts
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n < counts.num_massive_particles {
+        expand_bounds(massive_positions_and_masses[n]);
+    }
+    if n < counts.num_ghost_particles {
+        expand_bounds(ghost_positions_and_kinds[n]);
+    }
+}
@@ -0,0 +1,73 @@
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(1)
+var<storage, read> tree_bounds: array<u32>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> tree_keys: array<u32>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> tree_indices: array<u32>;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
+fn from_ordered_key(key: u32) -> f32 {
+    if (key & 0x80000000u) != 0u {
+        return bitcast<f32>(key & 0x7FFFFFFFu);
+    }
+    return bitcast<f32>(~key);
+}
+
+// Spread the lower 10 bits of x so there are two zero bits between each
+fn expand_bits(x: u32) -> u32 {
+    var v = x & 0x3FFu;
+    v = (v * 0x00010001u) & 0xFF0000FFu;
+    v = (v * 0x00000101u) & 0x0F00F00Fu;
+    v = (v * 0x00000011u) & 0xC30C30C3u;
+    v = (v * 0x00000005u) & 0x49249249u;
+    return v;
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let lower = vec3<f32>(
+        from_ordered_key(~tree_bounds[3]),
+        from_ordered_key(~tree_bounds[4]),
+        from_ordered_key(~tree_bounds[5]),
+    );
+    let upper = vec3<f32>(
+        from_ordered_key(tree_bounds[0]),
+        from_ordered_key(tree_bounds[1]),
+        from_ordered_key(tree_bounds[2]),
+    );
+    let extent = upper - lower;
+    var side = max(extent.x, max(extent.y, extent.z));
+    if side <= 0.0 {
+        side = 1.0;
+    }
+
+    let p = massive_positions_and_masses[n].xyz;
+    let cell = clamp((p - lower) * (1024.0 / side), vec3<f32>(0.0), vec3<f32>(1023.0));
+
+    tree_keys[n] = (expand_bits(u32(cell.x)) << 2u) | (expand_bits(u32(cell.y)) << 1u) | expand_bits(u32(cell.z));
+    tree_indices[n] = n;
+}
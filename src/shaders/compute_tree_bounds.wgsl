@@ -0,0 +1,46 @@
+// Bounds are stored as order-preserving u32 keys so they can be reduced with atomicMax:
+// [max_x, max_y, max_z, ~min_x, ~min_y, ~min_z] (the buffer is cleared to zero beforehand)
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(1)
+var<storage, read_write> tree_bounds: array<atomic<u32>>;
+
+@group(0)
+@binding(2)
+var<uniform> counts: Counts;
+
+fn ordered_key(value: f32) -> u32 {
+    let bits = bitcast<u32>(value);
+    if (bits & 0x80000000u) != 0u {
+        return ~bits;
+    }
+    return bits | 0x80000000u;
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let p = massive_positions_and_masses[n];
+
+    atomicMax(&tree_bounds[0], ordered_key(p.x));
+    atomicMax(&tree_bounds[1], ordered_key(p.y));
+    atomicMax(&tree_bounds[2], ordered_key(p.z));
+    atomicMax(&tree_bounds[3], ~ordered_key(p.x));
+    atomicMax(&tree_bounds[4], ~ordered_key(p.y));
+    atomicMax(&tree_bounds[5], ~ordered_key(p.z));
+}
@@ -0,0 +1,117 @@
+// Bottom-up reduction: a thread per leaf climbs towards the root. At each node the first of the two children to
+// arrive stops, and the second combines both children's moments and carries on to the parent, so every node is
+// combined once and no thread does more than the depth of the tree in work.
+
+const LEAF_FLAG: u32 = 0x80000000u;
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(1)
+var<storage, read> tree_indices: array<u32>;
+
+@group(0)
+@binding(2)
+var<storage, read> tree_nodes: array<vec4<u32>>;
+
+@group(0)
+@binding(3)
+var<storage, read> tree_parents: array<u32>; // Internal nodes, then leaves
+
+@group(0)
+@binding(4)
+var<storage, read_write> tree_visits: array<atomic<u32>>; // Cleared before every build
+
+// [centre of mass, mass] per node, as bits. Moments written by one thread are read by another in a different
+// workgroup, and relaxed atomics don't order them after the visit counter. The buffer is cleared before every build
+// and each word is written once with a value that is never zero, so a reader waits for its word to turn nonzero.
+@group(0)
+@binding(5)
+var<storage, read_write> tree_moments: array<atomic<u32>>;
+
+@group(0)
+@binding(6)
+var<uniform> counts: Counts;
+
+fn load_word(index: u32) -> f32 {
+    loop {
+        let bits = atomicLoad(&tree_moments[index]);
+        if bits != 0u {
+            return bitcast<f32>(bits);
+        }
+    }
+    return 0.0;
+}
+
+// Zero is stored as -0.0, which compares and sums the same
+fn store_word(index: u32, value: f32) {
+    var bits = bitcast<u32>(value);
+    if bits == 0u {
+        bits = 0x80000000u;
+    }
+    atomicStore(&tree_moments[index], bits);
+}
+
+fn load_moments(node: u32) -> vec4<f32> {
+    return vec4<f32>(
+        load_word(4u * node),
+        load_word(4u * node + 1u),
+        load_word(4u * node + 2u),
+        load_word(4u * node + 3u),
+    );
+}
+
+fn store_moments(node: u32, moments: vec4<f32>) {
+    store_word(4u * node, moments.x);
+    store_word(4u * node + 1u, moments.y);
+    store_word(4u * node + 2u, moments.z);
+    store_word(4u * node + 3u, moments.w);
+}
+
+fn child_moments(child: u32) -> vec4<f32> {
+    if (child & LEAF_FLAG) != 0u {
+        return massive_positions_and_masses[tree_indices[child & ~LEAF_FLAG]];
+    }
+    return load_moments(child);
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let s = global_id.x;
+    let num_bodies = counts.num_massive_particles;
+
+    if num_bodies < 2u || s >= num_bodies {
+        return;
+    }
+
+    var node = tree_parents[num_bodies - 1u + s];
+    loop {
+        if atomicAdd(&tree_visits[node], 1u) == 0u {
+            return; // The sibling's thread will combine this node
+        }
+
+        // Centre of mass taken from the first child's, to keep precision in distant nodes
+        let children = tree_nodes[node];
+        let a = child_moments(children.x);
+        let b = child_moments(children.y);
+        let mass = a.w + b.w;
+        var centre = a.xyz;
+        if mass > 0.0 {
+            centre = a.xyz + (b.w / mass) * (b.xyz - a.xyz);
+        }
+        store_moments(node, vec4<f32>(centre, mass));
+
+        if node == 0u {
+            return;
+        }
+        node = tree_parents[node];
+    }
+}
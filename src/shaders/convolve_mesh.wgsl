@@ -0,0 +1,38 @@
+struct Mesh {
+    size: u32,
+    padded_size: u32, // Twice the size for isolated boundaries, the size for periodic ones
+    assignment: u32, // 0: NGP, 1: CIC, 2: TSC
+    mass_scale: f32, // Fixed-point units per unit mass
+    box_size: f32, // Side of the cube about the origin (0 to fit the particles)
+    periodic: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> mesh: Mesh;
+
+@group(0)
+@binding(1)
+var<storage, read> mesh_green: array<vec2<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> mesh_potential: array<vec2<f32>>;
+
+// Multiply the transformed masses by the transformed Green's function (including the inverse FFT normalisation)
+@compute
+@workgroup_size(64, 1, 1)
+fn main(
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let size = mesh.padded_size;
+    let normalisation = 1.0 / (f32(size) * f32(size) * f32(size));
+
+    for (var x = local_index; x < size; x = x + 64u) {
+        let index = x + size * (workgroup_id.x + size * workgroup_id.y);
+        let a = mesh_potential[index];
+        let b = mesh_green[index];
+        mesh_potential[index] = vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x) * normalisation;
+    }
+}
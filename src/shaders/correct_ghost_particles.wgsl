@@ -0,0 +1,94 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> ghost_previous_positions_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> ghost_previous_velocities_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read> ghost_previous_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read> ghost_previous_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<storage, read> ghost_forces_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(6)
+var<storage, read> ghost_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(7)
+var<storage, read_write> ghost_positions_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(8)
+var<storage, read_write> ghost_velocities_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(9)
+var<uniform> counts: Counts;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
+    let dt = settings.time_step;
+    let mass = settings.ghost_mass;
+
+    let a0 = ghost_previous_forces[n].xyz / mass;
+    let j0 = ghost_previous_jerks[n].xyz / mass;
+    let a1 = ghost_forces_and_kinds[n].xyz / mass;
+    let j1 = ghost_jerks[n].xyz / mass;
+
+    let p0 = ghost_previous_positions_and_kinds[n].xyz;
+    let v0 = ghost_previous_velocities_and_kinds[n].xyz;
+
+    // Fourth-order Hermite corrector
+    let v1 = v0 + ((a0 + a1) / 2.0 + (j0 - j1) * dt / 12.0) * dt;
+    let p1 = p0 + ((v0 + v1) / 2.0 + (a0 - a1) * dt / 12.0) * dt;
+
+    ghost_positions_and_kinds[n].x = p1.x;
+    ghost_positions_and_kinds[n].y = p1.y;
+    ghost_positions_and_kinds[n].z = p1.z;
+
+    ghost_velocities_and_kinds[n].x = v1.x;
+    ghost_velocities_and_kinds[n].y = v1.y;
+    ghost_velocities_and_kinds[n].z = v1.z;
+}
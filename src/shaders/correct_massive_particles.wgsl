@@ -0,0 +1,94 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> massive_previous_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> massive_previous_velocities_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read> massive_previous_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read> massive_previous_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<storage, read> massive_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(6)
+var<storage, read> massive_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(7)
+var<storage, read_write> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(8)
+var<storage, read_write> massive_velocities_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(9)
+var<uniform> counts: Counts;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let dt = settings.time_step;
+    let mass = massive_velocities_and_masses[n].w;
+
+    let a0 = massive_previous_forces[n].xyz / mass;
+    let j0 = massive_previous_jerks[n].xyz / mass;
+    let a1 = massive_forces[n].xyz / mass;
+    let j1 = massive_jerks[n].xyz / mass;
+
+    let p0 = massive_previous_positions_and_masses[n].xyz;
+    let v0 = massive_previous_velocities_and_masses[n].xyz;
+
+    // Fourth-order Hermite corrector
+    let v1 = v0 + ((a0 + a1) / 2.0 + (j0 - j1) * dt / 12.0) * dt;
+    let p1 = p0 + ((v0 + v1) / 2.0 + (a0 - a1) * dt / 12.0) * dt;
+
+    massive_positions_and_masses[n].x = p1.x;
+    massive_positions_and_masses[n].y = p1.y;
+    massive_positions_and_masses[n].z = p1.z;
+
+    massive_velocities_and_masses[n].x = v1.x;
+    massive_velocities_and_masses[n].y = v1.y;
+    massive_velocities_and_masses[n].z = v1.z;
+}
@@ -0,0 +1,119 @@
+struct Mesh {
+    size: u32,
+    padded_size: u32, // Twice the size for isolated boundaries, the size for periodic ones
+    assignment: u32, // 0: NGP, 1: CIC, 2: TSC
+    mass_scale: f32, // Fixed-point units per unit mass
+    box_size: f32, // Side of the cube about the origin (0 to fit the particles)
+    periodic: u32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> mesh: Mesh;
+
+@group(0)
+@binding(1)
+var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> mesh_frame: array<u32>; // [cell size, G, smoothing length, stale, origin xyz, -]
+
+@group(0)
+@binding(3)
+var<storage, read_write> mesh_masses: array<atomic<u32>>;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
+
+fn mesh_cell_size() -> f32 {
+    return bitcast<f32>(mesh_frame[0]);
+}
+
+// Grid coordinates of a position, wrapped into the mesh for periodic boundaries
+fn grid_coordinates(position: vec3<f32>) -> vec3<f32> {
+    let origin = vec3<f32>(bitcast<f32>(mesh_frame[4]), bitcast<f32>(mesh_frame[5]), bitcast<f32>(mesh_frame[6]));
+    let u = (position - origin) / mesh_cell_size();
+    if mesh.periodic != 0u {
+        let size = f32(mesh.size);
+        return u - size * floor(u / size);
+    }
+    return u;
+}
+
+// An isolated mesh only holds the particles within its four cell margin (all of them unless its box is fixed)
+fn on_mesh(u: vec3<f32>) -> bool {
+    let upper = vec3<f32>(f32(mesh.size) - 3.0);
+    return mesh.periodic != 0u || (all(u >= vec3<f32>(3.0)) && all(u <= upper));
+}
+
+struct Stencil {
+    start: i32,
+    weights: vec3<f32>,
+};
+
+// Cells touched along one axis by a particle at grid coordinate u
+fn stencil(u: f32) -> Stencil {
+    switch mesh.assignment {
+        case 0u: {
+            return Stencil(i32(round(u)), vec3<f32>(1.0, 0.0, 0.0));
+        }
+        case 1u: {
+            let cell = floor(u);
+            let f = u - cell;
+            return Stencil(i32(cell), vec3<f32>(1.0 - f, f, 0.0));
+        }
+        default: {
+            let cell = round(u);
+            let d = u - cell;
+            return Stencil(
+                i32(cell) - 1,
+                vec3<f32>(0.5 * (0.5 - d) * (0.5 - d), 0.75 - d * d, 0.5 * (0.5 + d) * (0.5 + d)),
+            );
+        }
+    }
+}
+
+// Masses are accumulated in fixed point as there are no floating point atomics
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let p = massive_positions_and_masses[n];
+    let u = grid_coordinates(p.xyz);
+    if !on_mesh(u) {
+        return;
+    }
+
+    let sx = stencil(u.x);
+    let sy = stencil(u.y);
+    let sz = stencil(u.z);
+    let size = i32(mesh.size);
+    for (var k = 0; k < 3; k = k + 1) {
+        for (var j = 0; j < 3; j = j + 1) {
+            for (var i = 0; i < 3; i = i + 1) {
+                let weight = sx.weights[i] * sy.weights[j] * sz.weights[k];
+                if weight <= 0.0 {
+                    continue;
+                }
+                // Wrapping only matters for periodic boundaries, where the stencil can straddle the edge
+                let x = (sx.start + i + size) % size;
+                let y = (sy.start + j + size) % size;
+                let z = (sz.start + k + size) % size;
+                let index = u32(x + size * (y + size * z));
+                atomicAdd(&mesh_masses[index], u32(round(weight * p.w * mesh.mass_scale)));
+            }
+        }
+    }
+}
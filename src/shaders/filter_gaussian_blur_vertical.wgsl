@@ -11,6 +11,8 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
 };
 
 @group(0)
@@ -19,11 +21,11 @@ var<uniform> settings: Settings;
 
 @group(0)
 @binding(1)
-var read_texture: texture_storage_2d<rgba8unorm, read>;
+var read_texture: texture_2d<f32>;
 
 @group(0)
 @binding(2)
-var write_texture: texture_storage_2d<rgba8unorm, read_write>;
+var write_texture: texture_storage_2d<rgba8unorm, write>;
 
 @compute
 @workgroup_size(8, 8, 1)
@@ -31,6 +33,10 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let x = global_id.x;
     let y = global_id.y;
 
+    if x >= u32(settings.display_width) || y >= u32(settings.display_height) {
+        return;
+    }
+
     var sum: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 0.0);
     var weight_sum: f32 = 0.0;
     let radius: i32 = i32(settings.blur_radius);
@@ -39,8 +45,8 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         let blur_y: u32 = u32(i32(y) + dy);
 
         if blur_y < u32(settings.display_height) {
-            let weight = exp(-f32(dy * dy) / (2.0 * settings.blur_radius * settings.blur_radius));
-            let texel = textureLoad(read_texture, vec2<i32>(i32(x), i32(blur_y)));
+            let weight = select(exp(-f32(dy * dy) / (2.0 * settings.blur_radius * settings.blur_radius)), 1.0, dy == 0); // A zero radius copies the texture
+            let texel = textureLoad(read_texture, vec2<i32>(i32(x), i32(blur_y)), 0);
             sum += texel * weight;
             weight_sum += weight;
         }
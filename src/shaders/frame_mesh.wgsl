@@ -0,0 +1,114 @@
+// Cube the other mesh passes work in, with the cell size and origin they read from the frame. With a box size it is
+// that box about the origin; otherwise it is rewritten over the particle bounds, with its cell size rounded up to a
+// sixteenth of an octave. Either way the Green's function (which depends only on the cell size, G and the smoothing
+// length) only has to be rebuilt and transformed when the cell size or either setting changes.
+
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Mesh {
+    size: u32,
+    padded_size: u32, // Twice the size for isolated boundaries, the size for periodic ones
+    assignment: u32, // 0: NGP, 1: CIC, 2: TSC
+    mass_scale: f32, // Fixed-point units per unit mass
+    box_size: f32, // Side of the cube about the origin (0 to fit the particles)
+    periodic: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<uniform> mesh: Mesh;
+
+@group(0)
+@binding(2)
+var<storage, read> mesh_bounds: array<u32>;
+
+// [cell size, G, smoothing length, Green's function stale, origin xyz, -] of the current Green's function (zero until
+// the first force evaluation)
+@group(0)
+@binding(3)
+var<storage, read_write> mesh_frame: array<u32>;
+
+const LEVELS_PER_OCTAVE: f32 = 16.0;
+
+fn from_ordered_key(key: u32) -> f32 {
+    if (key & 0x80000000u) != 0u {
+        return bitcast<f32>(key & 0x7FFFFFFFu);
+    }
+    return bitcast<f32>(~key);
+}
+
+fn level_cell_size(level: i32) -> f32 {
+    return exp2(f32(level) / LEVELS_PER_OCTAVE);
+}
+
+@compute
+@workgroup_size(1, 1, 1)
+fn main() {
+    // Isolated meshes keep a four cell margin, so the stencils and finite differences never leave the region where
+    // the zero-padded potential is valid
+    var cell_size: f32;
+    var origin: vec3<f32>;
+    if mesh.periodic != 0u {
+        cell_size = mesh.box_size / f32(mesh.size);
+        origin = vec3<f32>(-0.5 * mesh.box_size);
+    } else if mesh.box_size > 0.0 {
+        cell_size = mesh.box_size / f32(mesh.size - 8u);
+        origin = vec3<f32>(-0.5 * mesh.box_size - 4.0 * cell_size);
+    } else {
+        let upper = vec3<f32>(
+            from_ordered_key(mesh_bounds[0]),
+            from_ordered_key(mesh_bounds[1]),
+            from_ordered_key(mesh_bounds[2]),
+        );
+        let lower = vec3<f32>(
+            from_ordered_key(~mesh_bounds[3]),
+            from_ordered_key(~mesh_bounds[4]),
+            from_ordered_key(~mesh_bounds[5]),
+        );
+        let extent = upper - lower;
+        var side = max(extent.x, max(extent.y, extent.z));
+        if side <= 0.0 {
+            side = 1.0;
+        }
+        let cells = f32(mesh.size - 8u);
+
+        // Smallest level whose cells cover the particles (checked, as exp2 and log2 are approximate)
+        var level = i32(ceil(LEVELS_PER_OCTAVE * log2(side / cells)));
+        if level_cell_size(level) * cells < side {
+            level = level + 1;
+        }
+        cell_size = level_cell_size(level);
+        origin = lower - 4.0 * cell_size;
+    }
+
+    let cell_bits = bitcast<u32>(cell_size);
+    let grav_const = bitcast<u32>(settings.gravitational_constant);
+    let smoothing_length = bitcast<u32>(settings.smoothing_length);
+    let stale = mesh_frame[0] != cell_bits || mesh_frame[1] != grav_const || mesh_frame[2] != smoothing_length;
+    mesh_frame[0] = cell_bits;
+    mesh_frame[1] = grav_const;
+    mesh_frame[2] = smoothing_length;
+    mesh_frame[3] = u32(stale);
+    mesh_frame[4] = bitcast<u32>(origin.x);
+    mesh_frame[5] = bitcast<u32>(origin.y);
+    mesh_frame[6] = bitcast<u32>(origin.z);
+}
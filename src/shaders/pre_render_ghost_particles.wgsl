@@ -11,6 +11,8 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
 };
 
 struct Camera {
@@ -18,6 +20,11 @@ struct Camera {
     zoom: f32,
 };
 
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
 @group(0)
 @binding(0)
 var<uniform> settings: Settings;
@@ -30,41 +37,55 @@ var<uniform> camera: Camera;
 @binding(2)
 var<storage, read> ghost_positions_and_kinds: array<vec4<f32>>;
 
+// Ghosts landing on each pixel, counted per colour channel: [r, g, b] per pixel in row-major order
 @group(0)
 @binding(3)
-var texture: texture_storage_2d<rgba8unorm, read_write>;
+var<storage, read_write> ghost_pixels: array<atomic<u32>>;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
 
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
     let position = ghost_positions_and_kinds[n].xyz;
     let kind = ghost_positions_and_kinds[n].w;
 
     let clip_space_pos = camera.mvp * vec4<f32>(position.x / camera.zoom, position.y / camera.zoom, position.z / camera.zoom, 1.0);
 
     let pixel = position_to_pixel(clip_space_pos.xyz);
-    var prev_colour = textureLoad(texture, pixel);
-
-    let a = 1.0 / settings.ghost_stack_visible_limit;
+    let width = i32(settings.display_width);
+    let height = i32(settings.display_height);
+    if pixel.x < 0 || pixel.x >= width || pixel.y < 0 || pixel.y >= height {
+        return;
+    }
 
-    var colour = vec3<f32>(1.0, 1.0, 1.0);
+    var colour = vec3<u32>(1u, 1u, 1u);
     if kind <= 1.0 {
-        colour = vec3<f32>(1.0, 0.0, 0.0);
+        colour = vec3<u32>(1u, 0u, 0u);
     } else if kind <= 2.0 {
-        colour = vec3<f32>(0.0, 1.0, 0.0);
+        colour = vec3<u32>(0u, 1u, 0u);
     } else if kind <= 3.0 {
-        colour = vec3<f32>(0.0, 0.0, 1.0);
+        colour = vec3<u32>(0u, 0u, 1u);
     } else if kind <= 4.0 {
-        colour = vec3<f32>(0.0, 1.0, 1.0);
+        colour = vec3<u32>(0u, 1u, 1u);
     } else if kind <= 5.0 {
-        colour = vec3<f32>(1.0, 0.0, 1.0);
+        colour = vec3<u32>(1u, 0u, 1u);
     } else if kind <= 6.0 {
-        colour = vec3<f32>(1.0, 1.0, 0.0);
+        colour = vec3<u32>(1u, 1u, 0u);
     }
 
-    textureStore(texture, pixel, prev_colour + vec4<f32>(colour, 1.0));
+    let index = 3u * u32(pixel.y * width + pixel.x);
+    atomicAdd(&ghost_pixels[index], colour.r);
+    atomicAdd(&ghost_pixels[index + 1u], colour.g);
+    atomicAdd(&ghost_pixels[index + 2u], colour.b);
 }
 
 fn position_to_pixel(clip_space_pos: vec3<f32>) -> vec2<i32> {
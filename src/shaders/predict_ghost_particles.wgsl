@@ -0,0 +1,74 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> ghost_forces_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> ghost_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> ghost_positions_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> ghost_velocities_and_kinds: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<uniform> counts: Counts;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_ghost_particles {
+        return;
+    }
+
+    let dt = settings.time_step;
+    let mass = settings.ghost_mass;
+
+    let a = ghost_forces_and_kinds[n].xyz / mass;
+    let j = ghost_jerks[n].xyz / mass;
+    let v = ghost_velocities_and_kinds[n].xyz;
+
+    // Taylor series predictor
+    let dp = (v + (a / 2.0 + j * dt / 6.0) * dt) * dt;
+    let dv = (a + j * dt / 2.0) * dt;
+
+    ghost_positions_and_kinds[n].x += dp.x;
+    ghost_positions_and_kinds[n].y += dp.y;
+    ghost_positions_and_kinds[n].z += dp.z;
+
+    ghost_velocities_and_kinds[n].x += dv.x;
+    ghost_velocities_and_kinds[n].y += dv.y;
+    ghost_velocities_and_kinds[n].z += dv.z;
+}
@@ -0,0 +1,74 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+@group(0)
+@binding(1)
+var<storage, read> massive_forces: array<vec4<f32>>;
+
+@group(0)
+@binding(2)
+var<storage, read> massive_jerks: array<vec4<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> massive_positions_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> massive_velocities_and_masses: array<vec4<f32>>;
+
+@group(0)
+@binding(5)
+var<uniform> counts: Counts;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let n = global_id.x;
+
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
+    let dt = settings.time_step;
+    let mass = massive_velocities_and_masses[n].w;
+
+    let a = massive_forces[n].xyz / mass;
+    let j = massive_jerks[n].xyz / mass;
+    let v = massive_velocities_and_masses[n].xyz;
+
+    // Taylor series predictor
+    let dp = (v + (a / 2.0 + j * dt / 6.0) * dt) * dt;
+    let dv = (a + j * dt / 2.0) * dt;
+
+    massive_positions_and_masses[n].x += dp.x;
+    massive_positions_and_masses[n].y += dp.y;
+    massive_positions_and_masses[n].z += dp.z;
+
+    massive_velocities_and_masses[n].x += dv.x;
+    massive_velocities_and_masses[n].y += dv.y;
+    massive_velocities_and_masses[n].z += dv.z;
+}
@@ -0,0 +1,108 @@
+struct Settings {
+    display_width: f32,
+    display_height: f32,
+    pixel_size: f32,
+
+    gravitational_constant: f32,
+    time_step: f32,
+    smoothing_length: f32,
+
+    ghost_mass: f32,
+    ghost_stack_visible_limit: f32,
+
+    blur_radius: f32,
+
+    opening_angle: f32,
+};
+
+const PI: f32 = 3.14159265358979323846;
+
+@group(0)
+@binding(0)
+var<uniform> settings: Settings;
+
+struct Mesh {
+    size: u32,
+    padded_size: u32, // Twice the size for isolated boundaries, the size for periodic ones
+    assignment: u32, // 0: NGP, 1: CIC, 2: TSC
+    mass_scale: f32, // Fixed-point units per unit mass
+    box_size: f32, // Side of the cube about the origin (0 to fit the particles)
+    periodic: u32,
+};
+
+@group(0)
+@binding(1)
+var<uniform> mesh: Mesh;
+
+@group(0)
+@binding(2)
+var<storage, read> mesh_frame: array<u32>; // [cell size, G, smoothing length, Green's function stale, origin xyz, -]
+
+@group(0)
+@binding(3)
+var<storage, read> mesh_masses: array<u32>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> mesh_potential: array<vec2<f32>>;
+
+@group(0)
+@binding(5)
+var<storage, read_write> mesh_green: array<vec2<f32>>;
+
+// Zero-padded masses, and the Green's function when the frame has made it stale, one workgroup per x line of the
+// padded mesh. Isolated meshes get the softened point-mass potential, transformed by the green FFT passes; periodic
+// meshes get the transform of the finite-difference Poisson solution directly, with the mean density removed and no
+// softening.
+@compute
+@workgroup_size(64, 1, 1)
+fn main(
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let padded_size = mesh.padded_size;
+    let y = workgroup_id.x;
+    let z = workgroup_id.y;
+    let cell_size = bitcast<f32>(mesh_frame[0]);
+    let green_stale = mesh_frame[3] != 0u;
+
+    var dy = f32(y);
+    if y > padded_size / 2u {
+        dy = dy - f32(padded_size);
+    }
+    var dz = f32(z);
+    if z > padded_size / 2u {
+        dz = dz - f32(padded_size);
+    }
+
+    for (var x = local_index; x < padded_size; x = x + 64u) {
+        let index = x + padded_size * (y + padded_size * z);
+
+        var mass = 0.0;
+        if x < mesh.size && y < mesh.size && z < mesh.size {
+            mass = f32(mesh_masses[x + mesh.size * (y + mesh.size * z)]) / mesh.mass_scale;
+        }
+        mesh_potential[index] = vec2<f32>(mass, 0.0);
+
+        if !green_stale {
+            continue;
+        }
+        if mesh.periodic != 0u {
+            let s = sin(PI * vec3<f32>(f32(x), f32(y), f32(z)) / f32(padded_size));
+            let s2 = dot(s, s);
+            var green = 0.0;
+            if s2 > 0.0 {
+                green = -PI * settings.gravitational_constant / (cell_size * s2);
+            }
+            mesh_green[index] = vec2<f32>(green, 0.0);
+            continue;
+        }
+        var dx = f32(x);
+        if x > padded_size / 2u {
+            dx = dx - f32(padded_size);
+        }
+        let r2 = (dx * dx + dy * dy + dz * dz) * cell_size * cell_size;
+        let green = -settings.gravitational_constant / sqrt(r2 + settings.smoothing_length * settings.smoothing_length);
+        mesh_green[index] = vec2<f32>(green, 0.0);
+    }
+}
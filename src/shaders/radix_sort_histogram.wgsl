@@ -0,0 +1,53 @@
+struct RadixPass {
+    shift: u32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> radix_pass: RadixPass;
+
+@group(0)
+@binding(1)
+var<storage, read> keys: array<u32>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> histograms: array<u32>;
+
+@group(0)
+@binding(3)
+var<uniform> counts: Counts;
+
+var<workgroup> digit_counts: array<atomic<u32>, 16>;
+
+// Count the 4-bit digits in each block of 64 keys, stored digit-major: histograms[digit * num_blocks + block]
+@compute
+@workgroup_size(64, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = global_id.x;
+    let num_keys = counts.num_massive_particles;
+    let num_blocks = (num_keys + 63u) / 64u;
+
+    if local_index < 16u {
+        atomicStore(&digit_counts[local_index], 0u);
+    }
+    workgroupBarrier();
+
+    if n < num_keys {
+        atomicAdd(&digit_counts[(keys[n] >> radix_pass.shift) & 15u], 1u);
+    }
+    workgroupBarrier();
+
+    if local_index < 16u {
+        histograms[local_index * num_blocks + workgroup_id.x] = atomicLoad(&digit_counts[local_index]);
+    }
+}
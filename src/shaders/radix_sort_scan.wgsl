@@ -0,0 +1,48 @@
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<storage, read_write> histograms: array<u32>;
+
+@group(0)
+@binding(1)
+var<uniform> counts: Counts;
+
+var<workgroup> chunk_totals: array<u32, 64>;
+
+// Exclusive prefix sum of the whole histogram buffer using a single workgroup
+@compute
+@workgroup_size(64, 1, 1)
+fn main(@builtin(local_invocation_index) local_index: u32) {
+    let num_counts = 16u * ((counts.num_massive_particles + 63u) / 64u);
+    let chunk_size = (num_counts + 63u) / 64u;
+    let start = min(local_index * chunk_size, num_counts);
+    let end = min(start + chunk_size, num_counts);
+
+    var total = 0u;
+    for (var i = start; i < end; i = i + 1u) {
+        total = total + histograms[i];
+    }
+    chunk_totals[local_index] = total;
+    workgroupBarrier();
+
+    if local_index == 0u {
+        var offset = 0u;
+        for (var i = 0u; i < 64u; i = i + 1u) {
+            let chunk_total = chunk_totals[i];
+            chunk_totals[i] = offset;
+            offset = offset + chunk_total;
+        }
+    }
+    workgroupBarrier();
+
+    var offset = chunk_totals[local_index];
+    for (var i = start; i < end; i = i + 1u) {
+        let count = histograms[i];
+        histograms[i] = offset;
+        offset = offset + count;
+    }
+}
@@ -0,0 +1,73 @@
+struct RadixPass {
+    shift: u32,
+};
+
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
+@group(0)
+@binding(0)
+var<uniform> radix_pass: RadixPass;
+
+@group(0)
+@binding(1)
+var<storage, read> keys: array<u32>;
+
+@group(0)
+@binding(2)
+var<storage, read> values: array<u32>;
+
+@group(0)
+@binding(3)
+var<storage, read> histograms: array<u32>;
+
+@group(0)
+@binding(4)
+var<storage, read_write> sorted_keys: array<u32>;
+
+@group(0)
+@binding(5)
+var<storage, read_write> sorted_values: array<u32>;
+
+@group(0)
+@binding(6)
+var<uniform> counts: Counts;
+
+var<workgroup> digits: array<u32, 64>;
+
+// Stable scatter: each key goes after all smaller digits and after equal digits earlier in the sequence
+@compute
+@workgroup_size(64, 1, 1)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let n = global_id.x;
+    let num_keys = counts.num_massive_particles;
+    let num_blocks = (num_keys + 63u) / 64u;
+
+    var digit = 16u; // Out of range threads never match a real digit
+    if n < num_keys {
+        digit = (keys[n] >> radix_pass.shift) & 15u;
+    }
+    digits[local_index] = digit;
+    workgroupBarrier();
+
+    if n >= num_keys {
+        return;
+    }
+
+    var rank = 0u;
+    for (var i = 0u; i < local_index; i = i + 1u) {
+        if digits[i] == digit {
+            rank = rank + 1u;
+        }
+    }
+
+    let destination = histograms[digit * num_blocks + workgroup_id.x] + rank;
+    sorted_keys[destination] = keys[n];
+    sorted_values[destination] = values[n];
+}
@@ -11,11 +11,8 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
-};
 
-struct Camera {
-    mvp: mat4x4<f32>,
-    zoom: f32,
+    opening_angle: f32,
 };
 
 @group(0)
@@ -24,38 +21,27 @@ var<uniform> settings: Settings;
 
 @group(0)
 @binding(1)
-var<uniform> camera: Camera;
+var<storage, read> ghost_pixels: array<u32>;
 
 @group(0)
 @binding(2)
-var<storage, read> ghost_positions_and_kinds: array<vec4<f32>>;
-
-@group(0)
-@binding(3)
-var texture: texture_storage_2d<rgba8unorm, read_write>;
+var texture: texture_storage_2d<rgba8unorm, write>;
 
+// Colour each pixel by its ghost counts, reaching full brightness at `ghost_stack_visible_limit` ghosts
 @compute
-@workgroup_size(64, 1, 1)
+@workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    let n = global_id.x;
-
-    let position = ghost_positions_and_kinds[n].xyz;
-    let mass = ghost_positions_and_kinds[n].w;
+    let x = global_id.x;
+    let y = global_id.y;
+    let width = u32(settings.display_width);
 
-    let clip_space_pos = camera.mvp * vec4<f32>(position.x / camera.zoom, position.y / camera.zoom, position.z / camera.zoom, 1.0);
+    if x >= width || y >= u32(settings.display_height) {
+        return;
+    }
 
-    let pixel = position_to_pixel(clip_space_pos.xyz);
-    let colour = textureLoad(texture, pixel);
-
-    let scale = 1.0;
-    let alpha_scale = 1.0;
-    let new_colour = vec4<f32>(colour.x, colour.y, colour.z, colour.w);
-
-    textureStore(texture, pixel, new_colour);
-}
+    let index = 3u * (y * width + x);
+    let counts = vec3<f32>(f32(ghost_pixels[index]), f32(ghost_pixels[index + 1u]), f32(ghost_pixels[index + 2u]));
+    let colour = min(counts / settings.ghost_stack_visible_limit, vec3<f32>(1.0, 1.0, 1.0));
 
-fn position_to_pixel(clip_space_pos: vec3<f32>) -> vec2<i32> {
-    let col = (clip_space_pos.x + 1.0) * 0.5 * settings.display_width;
-    let row = (clip_space_pos.y + 1.0) * 0.5 * settings.display_height;
-    return vec2<i32>(i32(col), i32(row));
+    textureStore(texture, vec2<i32>(i32(x), i32(y)), vec4<f32>(colour, 1.0));
 }
@@ -11,6 +11,8 @@ struct Settings {
     ghost_stack_visible_limit: f32,
 
     blur_radius: f32,
+
+    opening_angle: f32,
 };
 
 struct Camera {
@@ -18,6 +20,11 @@ struct Camera {
     zoom: f32,
 };
 
+struct Counts {
+    num_massive_particles: u32,
+    num_ghost_particles: u32,
+};
+
 @group(0)
 @binding(0)
 var<uniform> settings: Settings;
@@ -32,13 +39,21 @@ var<storage, read> massive_positions_and_masses: array<vec4<f32>>;
 
 @group(0)
 @binding(3)
-var texture: texture_storage_2d<rgba8unorm, read_write>;
+var texture: texture_storage_2d<rgba8unorm, write>;
+
+@group(0)
+@binding(4)
+var<uniform> counts: Counts;
 
 @compute
 @workgroup_size(64, 1, 1)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let n = global_id.x;
 
+    if n >= counts.num_massive_particles {
+        return;
+    }
+
     let position = massive_positions_and_masses[n].xyz;
     let mass = massive_positions_and_masses[n].w;
 
@@ -0,0 +1,95 @@
+struct Mesh {
+    size: u32,
+    padded_size: u32, // Twice the size for isolated boundaries, the size for periodic ones
+    assignment: u32, // 0: NGP, 1: CIC, 2: TSC
+    mass_scale: f32, // Fixed-point units per unit mass
+    box_size: f32, // Side of the cube about the origin (0 to fit the particles)
+    periodic: u32,
+};
+
+struct FftPass {
+    axis: u32,
+    inverse: u32,
+    green: u32, // Transforms the Green's function, so there is nothing to do unless it is stale and isolated
+};
+
+@group(0)
+@binding(0)
+var<uniform> mesh: Mesh;
+
+@group(0)
+@binding(1)
+var<uniform> fft_pass: FftPass;
+
+@group(0)
+@binding(2)
+var<storage, read_write> data: array<vec2<f32>>;
+
+@group(0)
+@binding(3)
+var<storage, read> mesh_frame: array<u32>; // [cell size, G, smoothing length, Green's function stale, origin xyz, -]
+
+const PI: f32 = 3.14159265358979323846;
+
+var<workgroup> line: array<vec2<f32>, 256>;
+
+// Radix-2 FFT of one line of the padded mesh per workgroup (unnormalised)
+@compute
+@workgroup_size(64, 1, 1)
+fn main(
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    if fft_pass.green != 0u && (mesh_frame[3] == 0u || mesh.periodic != 0u) {
+        return;
+    }
+
+    let size = mesh.padded_size;
+    let bits = countTrailingZeros(size);
+
+    var base = 0u;
+    var stride = 1u;
+    switch fft_pass.axis {
+        case 0u: {
+            base = size * (workgroup_id.x + size * workgroup_id.y);
+            stride = 1u;
+        }
+        case 1u: {
+            base = workgroup_id.x + size * size * workgroup_id.y;
+            stride = size;
+        }
+        default: {
+            base = workgroup_id.x + size * workgroup_id.y;
+            stride = size * size;
+        }
+    }
+
+    for (var i = local_index; i < size; i = i + 64u) {
+        line[reverseBits(i) >> (32u - bits)] = data[base + i * stride];
+    }
+    workgroupBarrier();
+
+    var sign = -1.0;
+    if fft_pass.inverse != 0u {
+        sign = 1.0;
+    }
+    for (var half = 1u; half < size; half = half * 2u) {
+        for (var k = local_index; k < size / 2u; k = k + 64u) {
+            let offset = k % half;
+            let i = (k / half) * 2u * half + offset;
+            let j = i + half;
+
+            let angle = sign * PI * f32(offset) / f32(half);
+            let w = vec2<f32>(cos(angle), sin(angle));
+            let t = vec2<f32>(w.x * line[j].x - w.y * line[j].y, w.x * line[j].y + w.y * line[j].x);
+            let u = line[i];
+            line[i] = u + t;
+            line[j] = u - t;
+        }
+        workgroupBarrier();
+    }
+
+    for (var i = local_index; i < size; i = i + 64u) {
+        data[base + i * stride] = line[i];
+    }
+}
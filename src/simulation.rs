@@ -1,6 +1,11 @@
+use std::path::Path;
+
 use winit::{event::WindowEvent, window::Window};
 
-use crate::{Camera, Hardware, Memory, NBody, Pipelines, Settings};
+use crate::{
+    Camera, Checkpoint, Hardware, HardwareError, Integrator, Memory, NBody, Pipelines, Settings,
+    Snapshot, Solver,
+};
 
 pub struct Simulation {
     pub hardware: Hardware,
@@ -8,6 +13,17 @@ pub struct Simulation {
     pub pipelines: Pipelines,
     pub settings: Settings,
     pub camera: Camera,
+
+    uploaded_settings: Settings, // As last written to the settings uniform
+
+    steps: u64,
+    time: f64, // Simulated time, summed over the time step of every update
+
+    forces_initialised: bool,
+    jerks_initialised: bool,
+
+    damping: f32, // Rate at which velocities decay while relaxing [1/s] (zero otherwise)
+    damping_frame_velocity: [f32; 3], // Velocity of the centre of mass, which damping leaves alone
 }
 
 impl Simulation {
@@ -16,10 +32,71 @@ impl Simulation {
         settings: Settings,
         camera: Camera,
         initial_conditions: NBody,
+    ) -> Result<Self, HardwareError> {
+        let hardware =
+            Hardware::new(window, settings.backend, settings.force_fallback_adapter).await?;
+        Ok(Self::init(
+            hardware,
+            settings,
+            camera,
+            initial_conditions,
+            true,
+        ))
+    }
+
+    // Compute-only simulation: no window and no rendering
+    pub async fn new_headless(
+        settings: Settings,
+        camera: Camera,
+        initial_conditions: NBody,
+    ) -> Result<Self, HardwareError> {
+        let hardware = Hardware::new_headless(
+            (settings.display_width * settings.pixel_size) as u32,
+            (settings.display_height * settings.pixel_size) as u32,
+            settings.backend,
+            settings.force_fallback_adapter,
+        )
+        .await?;
+        Ok(Self::init(
+            hardware,
+            settings,
+            camera,
+            initial_conditions,
+            false,
+        ))
+    }
+
+    // Windowless simulation that renders into offscreen frames
+    pub async fn new_offscreen(
+        settings: Settings,
+        camera: Camera,
+        initial_conditions: NBody,
+    ) -> Result<Self, HardwareError> {
+        let hardware = Hardware::new_headless(
+            (settings.display_width * settings.pixel_size) as u32,
+            (settings.display_height * settings.pixel_size) as u32,
+            settings.backend,
+            settings.force_fallback_adapter,
+        )
+        .await?;
+        Ok(Self::init(
+            hardware,
+            settings,
+            camera,
+            initial_conditions,
+            true,
+        ))
+    }
+
+    fn init(
+        hardware: Hardware,
+        settings: Settings,
+        camera: Camera,
+        initial_conditions: NBody,
+        with_rendering: bool,
     ) -> Self {
-        let hardware = Hardware::new(window).await;
         let memory = Memory::new(&settings, &camera, initial_conditions, &hardware.device);
-        let pipelines = Pipelines::new(&hardware, &memory);
+        let pipelines = Pipelines::new(&hardware, &memory, with_rendering);
 
         Self {
             hardware,
@@ -27,6 +104,13 @@ impl Simulation {
             pipelines,
             settings,
             camera,
+            uploaded_settings: settings,
+            steps: 0,
+            time: 0.0,
+            forces_initialised: false,
+            jerks_initialised: false,
+            damping: 0.0,
+            damping_frame_velocity: [0.0; 3],
         }
     }
 
@@ -34,115 +118,839 @@ impl Simulation {
         if new_size.width > 0 && new_size.height > 0 {
             self.hardware.config.width = new_size.width;
             self.hardware.config.height = new_size.height;
-            self.hardware
-                .surface
-                .configure(&self.hardware.device, &self.hardware.config);
+            if let Some(surface) = &self.hardware.surface {
+                surface.configure(&self.hardware.device, &self.hardware.config);
+            }
         }
     }
 
     pub fn input(&mut self, _event: &WindowEvent) -> bool {
-        // Update settings uniform buffer
+        self.upload_settings();
+
+        false
+    }
+
+    // Write the settings uniform if the shared settings have changed since the last upload. The cached forces (and
+    // jerks) were computed under the old ones, so they are recomputed at the start of the next step.
+    fn upload_settings(&mut self) {
+        if self.settings.as_slice() == self.uploaded_settings.as_slice() {
+            return;
+        }
+
         self.hardware.queue.write_buffer(
             &self.memory.settings_uniform,
             0,
             bytemuck::cast_slice(self.settings.as_slice()),
         );
-
-        false
+        self.uploaded_settings = self.settings;
+        self.forces_initialised = false;
+        self.jerks_initialised = false;
     }
 
     pub fn update(&mut self) {
-        let mut encoder =
-            self.hardware
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Compute Encoder"),
-                });
+        self.upload_settings();
+
+        match self.settings.integrator {
+            Integrator::Euler => self.step_euler(),
+            Integrator::Leapfrog => self.step_leapfrog(1.0),
+            Integrator::VelocityVerlet => self.step_velocity_verlet(),
+            Integrator::Hermite => self.step_hermite(),
+            Integrator::Yoshida => {
+                for weight in Integrator::YOSHIDA_WEIGHTS {
+                    self.step_leapfrog(weight);
+                }
+            }
+        }
+
+        self.steps += 1;
+        self.time += self.settings.time_step as f64;
+    }
+
+    // Damped leapfrog steps that let initial conditions far from equilibrium settle before the run, with velocities
+    // relative to the centre of mass also decaying at the rate `damping` [1/s]. They count towards neither the steps
+    // nor the simulated time.
+    pub fn relax(&mut self, num_steps: u32, damping: f32) {
+        debug_assert!(damping >= 0.0);
+
+        self.upload_settings();
+
+        // Neither the forces nor the damping change the centre of mass velocity, so it is read once
+        if num_steps > 0 && damping > 0.0 {
+            self.damping_frame_velocity = self.read_state().centre_of_mass_velocity();
+        }
+        self.damping = damping;
+        for _ in 0..num_steps {
+            self.step_leapfrog(1.0);
+        }
+        self.damping = 0.0;
+        self.damping_frame_velocity = [0.0; 3];
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step_euler(&mut self) {
+        self.write_step_uniform(1.0, 1.0);
+
+        let mut encoder = self.create_compute_encoder();
+
+        self.calculate_forces(&mut encoder);
+        self.calculate_velocities(&mut encoder);
+        self.calculate_positions(&mut encoder);
+
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        // Forces now lag the positions by one drift
+        self.forces_initialised = false;
+        self.jerks_initialised = false;
+    }
+
+    // A single kick-drift-kick sub-step covering `weight` of the time step
+    fn step_leapfrog(&mut self, weight: f32) {
+        self.write_step_uniform(0.5 * weight, weight);
+
+        let mut encoder = self.create_compute_encoder();
+
+        // The opening half kick reuses the forces from the end of the previous step
+        if !self.forces_initialised {
+            self.calculate_forces(&mut encoder);
+            self.forces_initialised = true;
+        }
+
+        self.calculate_velocities(&mut encoder); // Half kick
+        self.calculate_positions(&mut encoder); // Drift
+        self.calculate_forces(&mut encoder);
+        self.calculate_velocities(&mut encoder); // Half kick
+
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        self.jerks_initialised = false;
+    }
+
+    fn step_velocity_verlet(&mut self) {
+        let mut encoder = self.create_compute_encoder();
+
+        if !self.forces_initialised {
+            self.calculate_forces(&mut encoder);
+            self.forces_initialised = true;
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &self.memory.massive_forces_and_masses_buffer,
+            0,
+            &self.memory.massive_previous_forces_buffer,
+            0,
+            self.memory.massive_forces_and_masses_buffer.size(),
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.memory.ghost_forces_and_kinds_buffer,
+            0,
+            &self.memory.ghost_previous_forces_buffer,
+            0,
+            self.memory.ghost_forces_and_kinds_buffer.size(),
+        );
+
+        self.dispatch(
+            &mut encoder,
+            "Calculate Massive Verlet Positions",
+            &self.pipelines.calculate_massive_verlet_positions_pipeline,
+            &self.pipelines.calculate_massive_verlet_positions_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            &mut encoder,
+            "Calculate Ghost Verlet Positions",
+            &self.pipelines.calculate_ghost_verlet_positions_pipeline,
+            &self.pipelines.calculate_ghost_verlet_positions_bind_group,
+            self.memory.num_ghost_particles,
+        );
+        self.calculate_forces(&mut encoder);
+        self.dispatch(
+            &mut encoder,
+            "Calculate Massive Verlet Velocities",
+            &self.pipelines.calculate_massive_verlet_velocities_pipeline,
+            &self
+                .pipelines
+                .calculate_massive_verlet_velocities_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            &mut encoder,
+            "Calculate Ghost Verlet Velocities",
+            &self.pipelines.calculate_ghost_verlet_velocities_pipeline,
+            &self.pipelines.calculate_ghost_verlet_velocities_bind_group,
+            self.memory.num_ghost_particles,
+        );
+
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        self.jerks_initialised = false;
+    }
+
+    fn step_hermite(&mut self) {
+        let mut encoder = self.create_compute_encoder();
+
+        if !self.jerks_initialised {
+            self.calculate_forces_and_jerks(&mut encoder);
+            self.forces_initialised = true;
+            self.jerks_initialised = true;
+        }
+
+        // Keep the state at the start of the step for the corrector
+        for (source, destination) in [
+            (
+                &self.memory.massive_positions_and_masses_buffer,
+                &self.memory.massive_previous_positions_and_masses_buffer,
+            ),
+            (
+                &self.memory.massive_velocities_and_masses_buffer,
+                &self.memory.massive_previous_velocities_and_masses_buffer,
+            ),
+            (
+                &self.memory.massive_forces_and_masses_buffer,
+                &self.memory.massive_previous_forces_buffer,
+            ),
+            (
+                &self.memory.massive_jerks_buffer,
+                &self.memory.massive_previous_jerks_buffer,
+            ),
+            (
+                &self.memory.ghost_positions_and_kinds_buffer,
+                &self.memory.ghost_previous_positions_and_kinds_buffer,
+            ),
+            (
+                &self.memory.ghost_velocities_and_kinds_buffer,
+                &self.memory.ghost_previous_velocities_and_kinds_buffer,
+            ),
+            (
+                &self.memory.ghost_forces_and_kinds_buffer,
+                &self.memory.ghost_previous_forces_buffer,
+            ),
+            (
+                &self.memory.ghost_jerks_buffer,
+                &self.memory.ghost_previous_jerks_buffer,
+            ),
+        ] {
+            encoder.copy_buffer_to_buffer(source, 0, destination, 0, source.size());
+        }
+
+        self.dispatch(
+            &mut encoder,
+            "Predict Massive Particles",
+            &self.pipelines.predict_massive_particles_pipeline,
+            &self.pipelines.predict_massive_particles_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            &mut encoder,
+            "Predict Ghost Particles",
+            &self.pipelines.predict_ghost_particles_pipeline,
+            &self.pipelines.predict_ghost_particles_bind_group,
+            self.memory.num_ghost_particles,
+        );
+        self.calculate_forces_and_jerks(&mut encoder);
+        self.dispatch(
+            &mut encoder,
+            "Correct Massive Particles",
+            &self.pipelines.correct_massive_particles_pipeline,
+            &self.pipelines.correct_massive_particles_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            &mut encoder,
+            "Correct Ghost Particles",
+            &self.pipelines.correct_ghost_particles_pipeline,
+            &self.pipelines.correct_ghost_particles_bind_group,
+            self.memory.num_ghost_particles,
+        );
+
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+    }
+
+    // Pairwise interactions per second of a solver, timed over whole force evaluations
+    // (approximate solvers are credited with the interactions of the direct sum they replace)
+    pub fn benchmark_forces(&mut self, solver: Solver, num_evaluations: u32) -> f64 {
+        let selected_solver = std::mem::replace(&mut self.settings.solver, solver);
+
+        self.time_forces(1); // Warm up
+        let seconds = self.time_forces(num_evaluations);
+
+        self.settings.solver = selected_solver;
+        self.forces_initialised = false;
+
+        let num_massive_particles = self.memory.num_massive_particles as f64;
+        let num_ghost_particles = self.memory.num_ghost_particles as f64;
+        let interactions_per_evaluation = num_massive_particles * (num_massive_particles - 1.0)
+            + num_ghost_particles * num_massive_particles;
+
+        num_evaluations as f64 * interactions_per_evaluation / seconds
+    }
+
+    fn time_forces(&self, num_evaluations: u32) -> f64 {
+        let start = std::time::Instant::now();
+
+        let mut encoder = self.create_compute_encoder();
+        for _ in 0..num_evaluations {
+            self.calculate_forces(&mut encoder);
+        }
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+        self.hardware.device.poll(wgpu::Maintain::Wait);
 
+        start.elapsed().as_secs_f64()
+    }
+
+    fn write_step_uniform(&self, kick_fraction: f32, drift_fraction: f32) {
+        // Fraction of the velocity lost over the kick's share of the time step
+        let damping = 1.0 - (-self.damping * kick_fraction * self.settings.time_step).exp();
+        self.hardware.queue.write_buffer(
+            &self.memory.step_uniform,
+            0,
+            bytemuck::cast_slice(&[
+                [kick_fraction, drift_fraction, damping, 0.0],
+                [
+                    self.damping_frame_velocity[0],
+                    self.damping_frame_velocity[1],
+                    self.damping_frame_velocity[2],
+                    0.0,
+                ],
+            ]),
+        );
+    }
+
+    fn create_compute_encoder(&self) -> wgpu::CommandEncoder {
+        self.hardware
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Compute Encoder"),
+            })
+    }
+
+    fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::ComputePipeline,
+        bind_group: &wgpu::BindGroup,
+        num_threads: u32,
+    ) {
+        self.dispatch_workgroups(
+            encoder,
+            label,
+            pipeline,
+            bind_group,
+            [num_threads.div_ceil(64), 1, 1],
+        );
+    }
+
+    fn dispatch_workgroups(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::ComputePipeline,
+        bind_group: &wgpu::BindGroup,
+        [x, y, z]: [u32; 3],
+    ) {
+        let mut compute_pass =
+            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(label) });
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.set_pipeline(pipeline);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+
+    fn calculate_forces(&self, encoder: &mut wgpu::CommandEncoder) {
+        // Without massive particles every force stays at its initial zero
+        if self.memory.num_massive_particles == 0 {
+            return;
+        }
+
+        assert!(
+            self.memory.has_solver_buffers(self.settings.solver),
+            "the {:?} solver must be selected when the simulation is created",
+            self.settings.solver
+        );
+        match self.settings.solver {
+            Solver::Direct => self.calculate_direct_forces(encoder),
+            Solver::TiledDirect => self.calculate_tiled_forces(encoder),
+            Solver::Tree => self.calculate_tree_forces(encoder),
+            Solver::ParticleMesh => self.calculate_mesh_forces(encoder),
+        }
+    }
+
+    fn calculate_direct_forces(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(
+            encoder,
+            "Calculate Massive Forces",
+            &self.pipelines.calculate_massive_forces_pipeline,
+            &self.pipelines.calculate_massive_forces_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Forces",
+            &self.pipelines.calculate_ghost_forces_pipeline,
+            &self.pipelines.calculate_ghost_forces_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    fn calculate_tiled_forces(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(
+            encoder,
+            "Calculate Massive Tiled Forces",
+            &self.pipelines.calculate_massive_tiled_forces_pipeline,
+            &self.pipelines.calculate_massive_tiled_forces_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Tiled Forces",
+            &self.pipelines.calculate_ghost_tiled_forces_pipeline,
+            &self.pipelines.calculate_ghost_tiled_forces_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    fn calculate_tree_forces(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.build_tree(encoder);
+
+        let tree = &self.pipelines.tree;
+        self.dispatch(
+            encoder,
+            "Calculate Massive Tree Forces",
+            &tree.calculate_massive_forces_pipeline,
+            &tree.calculate_massive_forces_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Tree Forces",
+            &tree.calculate_ghost_forces_pipeline,
+            &tree.calculate_ghost_forces_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    // Sort the massive particles along a Morton curve and build the tree over them
+    fn build_tree(&self, encoder: &mut wgpu::CommandEncoder) {
+        let tree = &self.pipelines.tree;
+        let num_massive_particles = self.memory.num_massive_particles;
+
+        encoder.clear_buffer(&self.memory.tree_bounds_buffer, 0, None);
+        self.dispatch(
+            encoder,
+            "Compute Tree Bounds",
+            &tree.compute_bounds_pipeline,
+            &tree.compute_bounds_bind_group,
+            num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Compute Morton Codes",
+            &tree.compute_morton_codes_pipeline,
+            &tree.compute_morton_codes_bind_group,
+            num_massive_particles,
+        );
+
+        for (histogram_bind_group, scatter_bind_group) in tree
+            .radix_sort_histogram_bind_groups
+            .iter()
+            .zip(&tree.radix_sort_scatter_bind_groups)
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Calculate Massive Forces"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.calculate_massive_forces_bind_group,
-                &[],
+            self.dispatch(
+                encoder,
+                "Radix Sort Histogram",
+                &tree.radix_sort_histogram_pipeline,
+                histogram_bind_group,
+                num_massive_particles,
+            );
+            self.dispatch(
+                encoder,
+                "Radix Sort Scan",
+                &tree.radix_sort_scan_pipeline,
+                &tree.radix_sort_scan_bind_group,
+                1, // Single workgroup
+            );
+            self.dispatch(
+                encoder,
+                "Radix Sort Scatter",
+                &tree.radix_sort_scatter_pipeline,
+                scatter_bind_group,
+                num_massive_particles,
             );
-            compute_pass.set_pipeline(&self.pipelines.calculate_massive_forces_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_massive_particles / 64) as u32, 1, 1);
         }
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Calculate Ghost Forces"),
-            });
-            compute_pass.set_bind_group(0, &self.pipelines.calculate_ghost_forces_bind_group, &[]);
-            compute_pass.set_pipeline(&self.pipelines.calculate_ghost_forces_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_ghost_particles / 64) as u32, 1, 1);
+
+        self.dispatch(
+            encoder,
+            "Build Tree Nodes",
+            &tree.build_nodes_pipeline,
+            &tree.build_nodes_bind_group,
+            num_massive_particles.saturating_sub(1),
+        );
+        // One thread per leaf, climbing the tree until it is the first of two children to reach a node
+        encoder.clear_buffer(&self.memory.tree_visits_buffer, 0, None);
+        encoder.clear_buffer(&self.memory.tree_moments_buffer, 0, None);
+        self.dispatch(
+            encoder,
+            "Compute Tree Moments",
+            &tree.compute_moments_pipeline,
+            &tree.compute_moments_bind_group,
+            num_massive_particles,
+        );
+    }
+
+    fn calculate_mesh_forces(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mesh = &self.pipelines.mesh;
+        assert_eq!(
+            self.settings.mesh_boundary, self.memory.mesh_boundary,
+            "the mesh boundary must be chosen when the simulation is created"
+        );
+        let padded_size = self.memory.mesh_padded_size;
+        let lines = [padded_size, padded_size, 1]; // One workgroup per line of the padded mesh
+
+        // The assignment scheme and box size may have changed since the mesh was created
+        self.hardware.queue.write_buffer(
+            &self.memory.mesh_uniform,
+            8,
+            bytemuck::cast_slice(&[self.settings.mass_assignment as u32]),
+        );
+        self.hardware.queue.write_buffer(
+            &self.memory.mesh_uniform,
+            16,
+            bytemuck::cast_slice(&[self.settings.mesh_box_size]),
+        );
+
+        // A fixed box doesn't need the particle bounds
+        if self.settings.mesh_box_size == 0.0 {
+            encoder.clear_buffer(&self.memory.mesh_bounds_buffer, 0, None);
+            self.dispatch(
+                encoder,
+                "Compute Mesh Bounds",
+                &mesh.compute_bounds_pipeline,
+                &mesh.compute_bounds_bind_group,
+                self.memory
+                    .num_massive_particles
+                    .max(self.memory.num_ghost_particles),
+            );
         }
+        self.dispatch_workgroups(
+            encoder,
+            "Frame Mesh",
+            &mesh.frame_pipeline,
+            &mesh.frame_bind_group,
+            [1, 1, 1],
+        );
 
+        encoder.clear_buffer(&self.memory.mesh_masses_buffer, 0, None);
+        self.dispatch(
+            encoder,
+            "Deposit Mesh Masses",
+            &mesh.deposit_masses_pipeline,
+            &mesh.deposit_masses_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch_workgroups(
+            encoder,
+            "Prepare Mesh",
+            &mesh.prepare_pipeline,
+            &mesh.prepare_bind_group,
+            lines,
+        );
+
+        // The Green's function passes return at once unless the frame has made it stale
+        for bind_group in mesh
+            .forward_transform_potential_bind_groups
+            .iter()
+            .chain(&mesh.forward_transform_green_bind_groups)
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Calculate Massive Velocities"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.calculate_massive_velocities_bind_group,
-                &[],
+            self.dispatch_workgroups(
+                encoder,
+                "Forward Transform Mesh",
+                &mesh.transform_pipeline,
+                bind_group,
+                lines,
             );
-            compute_pass.set_pipeline(&self.pipelines.calculate_massive_velocities_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_massive_particles / 64) as u32, 1, 1);
         }
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Calculate Ghost Velocities"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.calculate_ghost_velocities_bind_group,
-                &[],
+        self.dispatch_workgroups(
+            encoder,
+            "Convolve Mesh",
+            &mesh.convolve_pipeline,
+            &mesh.convolve_bind_group,
+            lines,
+        );
+        for bind_group in &mesh.inverse_transform_potential_bind_groups {
+            self.dispatch_workgroups(
+                encoder,
+                "Inverse Transform Mesh",
+                &mesh.transform_pipeline,
+                bind_group,
+                lines,
             );
-            compute_pass.set_pipeline(&self.pipelines.calculate_ghost_velocities_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_ghost_particles / 64) as u32, 1, 1);
         }
 
+        self.dispatch(
+            encoder,
+            "Calculate Massive Mesh Forces",
+            &mesh.calculate_massive_forces_pipeline,
+            &mesh.calculate_massive_forces_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Mesh Forces",
+            &mesh.calculate_ghost_forces_pipeline,
+            &mesh.calculate_ghost_forces_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    // Jerks are only available from the direct sum (settings reject Hermite with an approximate solver)
+    fn calculate_forces_and_jerks(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(
+            encoder,
+            "Calculate Massive Forces and Jerks",
+            &self.pipelines.calculate_massive_forces_and_jerks_pipeline,
+            &self.pipelines.calculate_massive_forces_and_jerks_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Forces and Jerks",
+            &self.pipelines.calculate_ghost_forces_and_jerks_pipeline,
+            &self.pipelines.calculate_ghost_forces_and_jerks_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    fn calculate_velocities(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(
+            encoder,
+            "Calculate Massive Velocities",
+            &self.pipelines.calculate_massive_velocities_pipeline,
+            &self.pipelines.calculate_massive_velocities_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Velocities",
+            &self.pipelines.calculate_ghost_velocities_pipeline,
+            &self.pipelines.calculate_ghost_velocities_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    fn calculate_positions(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.dispatch(
+            encoder,
+            "Calculate Massive Positions",
+            &self.pipelines.calculate_massive_positions_pipeline,
+            &self.pipelines.calculate_massive_positions_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch(
+            encoder,
+            "Calculate Ghost Positions",
+            &self.pipelines.calculate_ghost_positions_pipeline,
+            &self.pipelines.calculate_ghost_positions_bind_group,
+            self.memory.num_ghost_particles,
+        );
+    }
+
+    // Download the particle buffers (blocks until the GPU has finished)
+    pub fn read_state(&self) -> NBody {
+        let [massive_positions_and_masses, massive_velocities_and_masses, ghost_positions_and_kinds, ghost_velocities_and_kinds] =
+            self.read_buffers([
+                &self.memory.massive_positions_and_masses_buffer,
+                &self.memory.massive_velocities_and_masses_buffer,
+                &self.memory.ghost_positions_and_kinds_buffer,
+                &self.memory.ghost_velocities_and_kinds_buffer,
+            ]);
+
+        let mut state = NBody::new();
+        for ([px, py, pz, mass], [vx, vy, vz, _]) in massive_positions_and_masses
+            .into_iter()
+            .zip(massive_velocities_and_masses)
+            .take(self.memory.num_massive_particles as usize)
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Calculate Massive Positions"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.calculate_massive_positions_bind_group,
-                &[],
-            );
-            compute_pass.set_pipeline(&self.pipelines.calculate_massive_positions_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_massive_particles / 64) as u32, 1, 1);
+            state.add_massive_particle([px, py, pz], [vx, vy, vz], mass);
         }
+        for ([px, py, pz, kind], [vx, vy, vz, _]) in ghost_positions_and_kinds
+            .into_iter()
+            .zip(ghost_velocities_and_kinds)
+            .take(self.memory.num_ghost_particles as usize)
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Calculate Ghost Positions"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.calculate_ghost_positions_bind_group,
-                &[],
-            );
-            compute_pass.set_pipeline(&self.pipelines.calculate_ghost_positions_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_ghost_particles / 64) as u32, 1, 1);
+            state.add_ghost_particle([px, py, pz], [vx, vy, vz], kind);
         }
 
-        self.hardware
-            .queue
-            .submit(std::iter::once(encoder.finish()));
+        state
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+    // Current state tagged with the step count and simulated time
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            step: self.steps,
+            time: self.time,
+            state: self.read_state(),
+        }
+    }
+
+    // Everything needed to continue this run bit-for-bit on the same adapter
+    pub fn checkpoint(&self) -> Checkpoint {
+        let memory = &self.memory;
+        let [massive_positions, massive_velocities, massive_forces, massive_jerks, ghost_positions, ghost_velocities, ghost_forces, ghost_jerks] =
+            self.read_buffers([
+                &memory.massive_positions_and_masses_buffer,
+                &memory.massive_velocities_and_masses_buffer,
+                &memory.massive_forces_and_masses_buffer,
+                &memory.massive_jerks_buffer,
+                &memory.ghost_positions_and_kinds_buffer,
+                &memory.ghost_velocities_and_kinds_buffer,
+                &memory.ghost_forces_and_kinds_buffer,
+                &memory.ghost_jerks_buffer,
+            ]);
+        let num_massive = memory.num_massive_particles as usize;
+        let num_ghost = memory.num_ghost_particles as usize;
+
+        Checkpoint {
+            settings: self.settings,
+            camera: self.camera,
+            steps: self.steps,
+            time: self.time,
+            forces_initialised: self.forces_initialised,
+            jerks_initialised: self.jerks_initialised,
+            massive_buffers: [
+                massive_positions,
+                massive_velocities,
+                massive_forces,
+                massive_jerks,
+            ]
+            .map(|mut buffer| {
+                buffer.truncate(num_massive);
+                buffer
+            }),
+            ghost_buffers: [ghost_positions, ghost_velocities, ghost_forces, ghost_jerks].map(
+                |mut buffer| {
+                    buffer.truncate(num_ghost);
+                    buffer
+                },
+            ),
+        }
+    }
+
+    // Continue from a checkpoint; the simulation must have been created from `checkpoint.state()`
+    // with `checkpoint.settings` (fixed options such as the solver buffers cannot change here)
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        assert_eq!(
+            checkpoint.num_massive_particles(),
+            self.memory.num_massive_particles as usize
+        );
+        assert_eq!(
+            checkpoint.num_ghost_particles(),
+            self.memory.num_ghost_particles as usize
+        );
+
+        let memory = &self.memory;
+        for (buffer, data) in [
+            &memory.massive_positions_and_masses_buffer,
+            &memory.massive_velocities_and_masses_buffer,
+            &memory.massive_forces_and_masses_buffer,
+            &memory.massive_jerks_buffer,
+            &memory.ghost_positions_and_kinds_buffer,
+            &memory.ghost_velocities_and_kinds_buffer,
+            &memory.ghost_forces_and_kinds_buffer,
+            &memory.ghost_jerks_buffer,
+        ]
+        .into_iter()
+        .zip(
+            checkpoint
+                .massive_buffers
+                .iter()
+                .chain(&checkpoint.ghost_buffers),
+        ) {
+            self.hardware
+                .queue
+                .write_buffer(buffer, 0, bytemuck::cast_slice(data));
+        }
+        let mesh_mass_scale =
+            Memory::mesh_mass_scale(checkpoint.massive_buffers[0].iter().map(|p| p[3]));
         self.hardware.queue.write_buffer(
-            &self.memory.camera_uniform,
+            &memory.mesh_uniform,
+            12,
+            bytemuck::cast_slice(&[mesh_mass_scale]),
+        );
+
+        self.settings = checkpoint.settings;
+        self.camera = checkpoint.camera;
+        self.hardware.queue.write_buffer(
+            &self.memory.settings_uniform,
             0,
-            bytemuck::cast_slice(&self.camera.as_slice()),
+            bytemuck::cast_slice(self.settings.as_slice()),
         );
+        self.uploaded_settings = self.settings;
+
+        self.steps = checkpoint.steps;
+        self.time = checkpoint.time;
+        self.forces_initialised = checkpoint.forces_initialised;
+        self.jerks_initialised = checkpoint.jerks_initialised;
+    }
+
+    // Download several buffers through staging buffers in a single submission
+    fn read_buffers<const N: usize>(&self, buffers: [&wgpu::Buffer; N]) -> [Vec<[f32; 4]>; N] {
+        let mut encoder =
+            self.hardware
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Readback Encoder"),
+                });
+        let staging_buffers = buffers.map(|buffer| {
+            let staging_buffer = self.hardware.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Staging Buffer"),
+                size: buffer.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+            staging_buffer
+        });
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        for staging_buffer in &staging_buffers {
+            staging_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        }
+        self.hardware.device.poll(wgpu::Maintain::Wait);
+
+        staging_buffers.map(|staging_buffer| {
+            let data = bytemuck::cast_slice(&staging_buffer.slice(..).get_mapped_range()).to_vec();
+            staging_buffer.unmap();
+            data
+        })
+    }
 
-        let output = self.hardware.surface.get_current_texture()?;
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let output = self
+            .hardware
+            .surface
+            .as_ref()
+            .expect("Rendering requires a window surface")
+            .get_current_texture()?;
 
         let screen_view = output
             .texture
@@ -154,101 +962,188 @@ impl Simulation {
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("Render Encoder"),
                 });
+        self.render_frame(&mut encoder, &screen_view);
 
-        {
-            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Clear Texture"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.memory.display_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-        }
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+        output.present();
 
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Pre-Render Ghost Particles"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.pre_render_ghost_particles_bind_group,
-                &[],
-            );
-            compute_pass.set_pipeline(&self.pipelines.pre_render_ghost_particles_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_ghost_particles / 64) as u32, 1, 1);
-        }
-        // {
-        //     let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-        //         label: Some("Render Ghost Particles"),
-        //     });
-        //     compute_pass.set_bind_group(0, &self.pipelines.render_ghost_particles_bind_group, &[]);
-        //     compute_pass.set_pipeline(&self.pipelines.render_ghost_particles_pipeline);
-        //     compute_pass.dispatch_workgroups((self.memory.num_ghost_particles / 64) as u32, 1, 1);
-        // }
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Render Massive Particles"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.render_massive_particles_bind_group,
-                &[],
-            );
-            compute_pass.set_pipeline(&self.pipelines.render_massive_particles_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_massive_particles / 64) as u32, 1, 1);
-        }
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Vertical Gaussian Blur"),
-            });
-            compute_pass.set_bind_group(0, &self.pipelines.blur_vertically_bind_group, &[]);
-            compute_pass.set_pipeline(&self.pipelines.blur_vertically_pipeline);
-            compute_pass.dispatch_workgroups((1300 / 8) as u32, (1300 / 8) as u32, 1);
-        }
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Horizontal Gaussian Blur"),
+        Ok(())
+    }
+
+    // Render a frame offscreen and download it as tightly packed RGBA8 rows
+    pub fn capture_frame(&self) -> Vec<u8> {
+        let width = self.hardware.config.width;
+        let height = self.hardware.config.height;
+        let format = self.hardware.config.format;
+
+        let texture = self
+            .hardware
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Capture Texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
             });
-            compute_pass.set_bind_group(0, &self.pipelines.blur_horizontally_bind_group, &[]);
-            compute_pass.set_pipeline(&self.pipelines.blur_horizontally_pipeline);
-            compute_pass.dispatch_workgroups((1300 / 8) as u32, (1300 / 8) as u32, 1);
-        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Buffer rows must be padded to the copy alignment
+        let bytes_per_row = 4 * width;
+        let padded_bytes_per_row = bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging_buffer = self.hardware.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Staging Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            self.hardware
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Capture Encoder"),
+                });
+        self.render_frame(&mut encoder, &view);
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.hardware
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        self.hardware.device.poll(wgpu::Maintain::Wait);
+
+        let mut pixels = Vec::with_capacity((bytes_per_row * height) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Render Ghost Particles"),
-            });
-            compute_pass.set_bind_group(0, &self.pipelines.render_ghost_particles_bind_group, &[]);
-            compute_pass.set_pipeline(&self.pipelines.render_ghost_particles_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_ghost_particles / 64) as u32, 1, 1);
+            pixels.extend_from_slice(&row[..bytes_per_row as usize]);
         }
-        {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Render Massive Particles"),
-            });
-            compute_pass.set_bind_group(
-                0,
-                &self.pipelines.render_massive_particles_bind_group,
-                &[],
-            );
-            compute_pass.set_pipeline(&self.pipelines.render_massive_particles_pipeline);
-            compute_pass.dispatch_workgroups((self.memory.num_massive_particles / 64) as u32, 1, 1);
+        staging_buffer.unmap();
+
+        // Window surfaces may prefer BGRA
+        if matches!(
+            format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
         }
 
+        pixels
+    }
+
+    // Render a frame offscreen and write it to a PNG file
+    pub fn save_frame(&self, path: &Path) -> std::io::Result<()> {
+        let pixels = self.capture_frame();
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(
+            file,
+            self.hardware.config.width,
+            self.hardware.config.height,
+        );
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(std::io::Error::other)
+    }
+
+    // Draw the particles into the display texture, then the display texture onto `target_view`
+    fn render_frame(&self, encoder: &mut wgpu::CommandEncoder, target_view: &wgpu::TextureView) {
+        let render_pipelines = self
+            .pipelines
+            .render
+            .as_ref()
+            .expect("Compute-only simulations cannot render");
+        let pixels = [
+            (self.settings.display_width as u32).div_ceil(8),
+            (self.settings.display_height as u32).div_ceil(8),
+            1,
+        ];
+
+        self.hardware.queue.write_buffer(
+            &self.memory.camera_uniform,
+            0,
+            bytemuck::cast_slice(&self.camera.as_slice()),
+        );
+
+        // Ghosts are counted per pixel, then the counts are resolved into the whole display texture
+        encoder.clear_buffer(&self.memory.ghost_pixels_buffer, 0, None);
+        self.dispatch(
+            encoder,
+            "Pre-Render Ghost Particles",
+            &render_pipelines.pre_render_ghost_particles_pipeline,
+            &render_pipelines.pre_render_ghost_particles_bind_group,
+            self.memory.num_ghost_particles,
+        );
+        self.dispatch_workgroups(
+            encoder,
+            "Render Ghost Particles",
+            &render_pipelines.render_ghost_particles_pipeline,
+            &render_pipelines.render_ghost_particles_bind_group,
+            pixels,
+        );
+        self.dispatch(
+            encoder,
+            "Render Massive Particles",
+            &render_pipelines.render_massive_particles_pipeline,
+            &render_pipelines.render_massive_particles_bind_group,
+            self.memory.num_massive_particles,
+        );
+        self.dispatch_workgroups(
+            encoder,
+            "Vertical Gaussian Blur",
+            &render_pipelines.blur_vertically_pipeline,
+            &render_pipelines.blur_vertically_bind_group,
+            pixels,
+        );
+        self.dispatch_workgroups(
+            encoder,
+            "Horizontal Gaussian Blur",
+            &render_pipelines.blur_horizontally_pipeline,
+            &render_pipelines.blur_horizontally_bind_group,
+            pixels,
+        );
+        self.dispatch(
+            encoder,
+            "Render Massive Particles",
+            &render_pipelines.render_massive_particles_pipeline,
+            &render_pipelines.render_massive_particles_bind_group,
+            self.memory.num_massive_particles,
+        );
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &screen_view,
+                    view: target_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -263,8 +1158,8 @@ impl Simulation {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_pipeline(&self.pipelines.display_pipeline);
-            render_pass.set_bind_group(0, &self.pipelines.display_bind_group, &[]);
+            render_pass.set_pipeline(&render_pipelines.display_pipeline);
+            render_pass.set_bind_group(0, &render_pipelines.display_bind_group, &[]);
             render_pass.set_index_buffer(
                 self.memory.index_buffer.slice(..),
                 wgpu::IndexFormat::Uint16,
@@ -272,12 +1167,5 @@ impl Simulation {
             render_pass.set_vertex_buffer(0, self.memory.vertex_buffer.slice(..));
             render_pass.draw_indexed(0..self.memory.num_indices, 0, 0..1);
         }
-
-        self.hardware
-            .queue
-            .submit(std::iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
     }
 }
@@ -0,0 +1,211 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+use crate::{Checkpoint, Endianness, GadgetFormat, GadgetMapping, NBody, TipsyGas};
+
+// Native snapshot layout (all little-endian):
+//   header: b"HVNS", version: u32, step: u64, time: f64, num_massive: u64, num_ghost: u64
+//   massive positions [f32; 3 * num_massive], velocities [f32; 3 * num_massive], masses [f32; num_massive]
+//   ghost positions [f32; 3 * num_ghost], velocities [f32; 3 * num_ghost], kinds [f32; num_ghost]
+const MAGIC: &[u8; 4] = b"HVNS";
+const VERSION: u32 = 1;
+
+// File formats snapshots convert between, recognised by their extension
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    // Native snapshots (`.hvs`)
+    Heavens,
+    // Checkpoints (`.hvc`, read only: a snapshot lacks the forces and settings to resume from)
+    Checkpoint,
+    // GADGET-2 format 1 files (`.gadget`, read in either format) with the default type mapping
+    Gadget,
+    // Standard (big-endian) Tipsy files (`.tipsy`, read in either endianness) with gas as ghosts
+    Tipsy,
+    // Particle tables (`.csv`, `.npy`) and arrays (`.npz`), without the step or time (which read as zero)
+    Csv,
+    Npy,
+    Npz,
+}
+
+impl SnapshotFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "hvs" => Some(Self::Heavens),
+            "hvc" => Some(Self::Checkpoint),
+            "gadget" => Some(Self::Gadget),
+            "tipsy" => Some(Self::Tipsy),
+            "csv" => Some(Self::Csv),
+            "npy" => Some(Self::Npy),
+            "npz" => Some(Self::Npz),
+            _ => None,
+        }
+    }
+
+    pub fn load(self, path: &Path) -> Result<Snapshot> {
+        match self {
+            Self::Heavens => Snapshot::load(path),
+            Self::Checkpoint => {
+                let checkpoint = Checkpoint::load(path)?;
+                Ok(Snapshot {
+                    step: checkpoint.steps,
+                    time: checkpoint.time,
+                    state: checkpoint.state(),
+                })
+            }
+            Self::Gadget => Snapshot::load_gadget(path, &GadgetMapping::default()),
+            Self::Tipsy => Snapshot::load_tipsy(path, TipsyGas::Ghosts),
+            Self::Csv | Self::Npy | Self::Npz => {
+                let state = match self {
+                    Self::Csv => NBody::load_csv(path)?,
+                    Self::Npy => NBody::load_npy(path)?,
+                    _ => NBody::load_npz(path)?,
+                };
+                Ok(Snapshot {
+                    step: 0,
+                    time: 0.0,
+                    state,
+                })
+            }
+        }
+    }
+
+    pub fn save(self, snapshot: &Snapshot, path: &Path) -> Result<()> {
+        match self {
+            Self::Heavens => snapshot.save(path),
+            Self::Checkpoint => Err(Error::new(
+                ErrorKind::Unsupported,
+                "Checkpoints can only be written by a running simulation",
+            )),
+            Self::Gadget => {
+                snapshot.save_gadget(path, &GadgetMapping::default(), GadgetFormat::Format1)
+            }
+            Self::Tipsy => snapshot.save_tipsy(path, Endianness::Big),
+            Self::Csv => snapshot.state.save_csv(path),
+            Self::Npy => snapshot.state.save_npy(path),
+            Self::Npz => snapshot.state.save_npz(path),
+        }
+    }
+}
+
+// Particle state at a given step of a run
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub step: u64,
+    pub time: f64,
+    pub state: NBody,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let state = &self.state;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.step.to_le_bytes())?;
+        writer.write_all(&self.time.to_le_bytes())?;
+        writer.write_all(&(state.num_massive_particles() as u64).to_le_bytes())?;
+        writer.write_all(&(state.num_ghost_particles() as u64).to_le_bytes())?;
+
+        write_f32s(writer, state.massive_positions().iter().flatten())?;
+        write_f32s(writer, state.massive_velocities().iter().flatten())?;
+        write_f32s(writer, state.massive_masses())?;
+        write_f32s(writer, state.ghost_positions().iter().flatten())?;
+        write_f32s(writer, state.ghost_velocities().iter().flatten())?;
+        write_f32s(writer, state.ghost_kinds())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a heavens snapshot"));
+        }
+        let version = u32::from_le_bytes(read_bytes(reader)?);
+        if version != VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported snapshot version {}", version),
+            ));
+        }
+
+        let step = u64::from_le_bytes(read_bytes(reader)?);
+        let time = f64::from_le_bytes(read_bytes(reader)?);
+        let num_massive = u64::from_le_bytes(read_bytes(reader)?) as usize;
+        let num_ghost = u64::from_le_bytes(read_bytes(reader)?) as usize;
+
+        let massive_positions = read_vec3s(reader, num_massive)?;
+        let massive_velocities = read_vec3s(reader, num_massive)?;
+        let massive_masses = read_f32s(reader, num_massive)?;
+        let ghost_positions = read_vec3s(reader, num_ghost)?;
+        let ghost_velocities = read_vec3s(reader, num_ghost)?;
+        let ghost_kinds = read_f32s(reader, num_ghost)?;
+
+        if massive_masses
+            .iter()
+            .any(|mass| mass.is_nan() || *mass <= 0.0)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Snapshot contains a non-positive mass",
+            ));
+        }
+
+        let state = NBody {
+            massive_positions,
+            massive_velocities,
+            massive_masses,
+            ghost_positions,
+            ghost_velocities,
+            ghost_kinds,
+        };
+
+        Ok(Self { step, time, state })
+    }
+}
+
+pub(crate) fn write_f32s<'a>(
+    writer: &mut impl Write,
+    values: impl IntoIterator<Item = &'a f32>,
+) -> Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_bytes<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn read_f32s(reader: &mut impl Read, count: usize) -> Result<Vec<f32>> {
+    (0..count)
+        .map(|_| read_bytes(reader).map(f32::from_le_bytes))
+        .collect()
+}
+
+pub(crate) fn read_vec3s(reader: &mut impl Read, count: usize) -> Result<Vec<[f32; 3]>> {
+    (0..count)
+        .map(|_| {
+            Ok([
+                f32::from_le_bytes(read_bytes(reader)?),
+                f32::from_le_bytes(read_bytes(reader)?),
+                f32::from_le_bytes(read_bytes(reader)?),
+            ])
+        })
+        .collect()
+}
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Solver {
+    // Sum over every massive body (exact, O(N^2))
+    #[default]
+    Direct,
+    // The same sum, staging tiles of bodies in workgroup memory
+    TiledDirect,
+    // Barnes-Hut walk of a Morton-ordered tree rebuilt every force evaluation (O(N log N))
+    Tree,
+    // FFT Poisson solve on a mesh (resolution limited to the cell size), see `MeshBoundary`
+    ParticleMesh,
+}
+
+// Boundary conditions of the particle-mesh solver
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeshBoundary {
+    // Zero-padded FFT of an isolated system, on a cube around every particle or of `mesh_box_size` about the origin
+    // (particles outside it neither contribute to nor feel the mesh force)
+    #[default]
+    Isolated,
+    // A cube of `mesh_box_size` about the origin repeated through space, as for cosmological boxes (the force is
+    // periodic but positions are not wrapped back into the box, the mean density is removed and there is no softening)
+    Periodic,
+}
+
+// Mass assignment and force interpolation scheme of the particle-mesh solver
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MassAssignment {
+    // Nearest grid point
+    Ngp,
+    // Cloud-in-cell
+    #[default]
+    Cic,
+    // Triangular-shaped cloud
+    Tsc,
+}
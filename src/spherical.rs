@@ -0,0 +1,266 @@
+use rand::Rng;
+
+// Points per decade of the logarithmic radial grid
+const GRID_DENSITY: f64 = 100.0;
+
+// Quadrature points of the Eddington integral
+const EDDINGTON_POINTS: usize = 200;
+
+// Speeds scanned for the peak of the speed distribution at each radius
+const SPEED_SCAN_POINTS: usize = 64;
+
+// Isotropic spherical model in units of G = M = scale radius = 1, tabulated on a logarithmic radial grid:
+// density, enclosed mass, relative potential (Psi = -Phi, up to a constant) and the Eddington distribution
+// function f(E) of the relative energy E = Psi - v^2 / 2 at E = Psi(r) of each grid point. The potential may
+// include other spherical components, so that the model is in equilibrium within a composite system.
+pub(crate) struct SphericalModel {
+    radii: Vec<f64>,
+    density: Vec<f64>,
+    masses: Vec<f64>,
+    potentials: Vec<f64>,
+    distribution: Vec<f64>,
+}
+
+impl SphericalModel {
+    // rho ~ 1 / (r (1 + r)^3), out to where all but 0.02% of the mass is enclosed
+    pub(crate) fn hernquist() -> Self {
+        Self::new(&log_grid(1.0e-4, 1.0e4), |r| 1.0 / (r * (1.0 + r).powi(3)))
+    }
+
+    // rho ~ 1 / (r (1 + r)^2) out to the virial radius c, then tapered exponentially over a tenth of it with the
+    // logarithmic slope kept continuous (Kazantzidis, Magorrian & Moore 2004)
+    pub(crate) fn nfw(concentration: f64) -> Self {
+        let c = concentration;
+        let decay = 0.1 * c;
+        let slope = -(1.0 + 3.0 * c) / (1.0 + c) + c / decay;
+        let edge = 1.0 / (c * (1.0 + c).powi(2));
+
+        Self::new(&log_grid(1.0e-4 * c.min(1.0), c + 40.0 * decay), |r| {
+            if r <= c {
+                1.0 / (r * (1.0 + r).powi(2))
+            } else {
+                edge * (r / c).powf(slope) * (-(r - c) / decay).exp()
+            }
+        })
+    }
+
+    // Lowered isothermal sphere of dimensionless central potential W0, with radii in King radii: the
+    // potential W (in units of the velocity dispersion squared) solves Poisson's equation
+    //   W'' + 2 W' / r = -9 rho(W) / rho(W0)
+    // outwards from the centre until it vanishes at the tidal radius
+    pub(crate) fn king(central_potential: f64) -> Self {
+        let w0 = central_potential;
+        let step = std::f64::consts::LN_10 / GRID_DENSITY;
+        let rho0 = king_density(w0);
+
+        // In x = ln r the equation is W_xx + W_x = -9 r^2 rho(W) / rho(W0), started from the
+        // constant-density core solution W = W0 - 3 r^2 / 2
+        let derivatives = |x: f64, [w, dw]: [f64; 2]| {
+            let r2 = (2.0 * x).exp();
+            [dw, -dw - 9.0 * r2 * king_density(w.max(0.0)) / rho0]
+        };
+        let mut x = (1.0e-4f64).ln();
+        let r2 = (2.0 * x).exp();
+        let mut state = [w0 - 1.5 * r2, -3.0 * r2];
+
+        let mut radii = vec![x.exp()];
+        let mut density = vec![king_density(state[0]) / rho0];
+        loop {
+            let next = rk4_step(&derivatives, x, state, step);
+            if next[0] <= 0.0 {
+                // The tidal radius, where the potential and density fall to zero
+                let fraction = state[0] / (state[0] - next[0]);
+                radii.push((x + fraction * step).exp());
+                density.push(0.0);
+                break;
+            }
+            x += step;
+            state = next;
+            radii.push(x.exp());
+            density.push(king_density(state[0]) / rho0);
+        }
+
+        Self::from_density(radii, density)
+    }
+
+    fn new(radii: &[f64], density: impl Fn(f64) -> f64) -> Self {
+        let density = radii.iter().map(|r| density(*r)).collect();
+        Self::from_density(radii.to_vec(), density)
+    }
+
+    fn from_density(radii: Vec<f64>, density: Vec<f64>) -> Self {
+        let n = radii.len();
+        let four_pi = 4.0 * std::f64::consts::PI;
+
+        // Mass inside the grid assumes a power-law density there
+        let inner_slope = -(density[1] / density[0]).ln() / (radii[1] / radii[0]).ln();
+        let mut masses = vec![four_pi * radii[0].powi(3) * density[0] / (3.0 - inner_slope)];
+        for i in 1..n {
+            let shell = |j: usize| four_pi * radii[j].powi(3) * density[j];
+            let dx = (radii[i] / radii[i - 1]).ln();
+            masses.push(masses[i - 1] + 0.5 * (shell(i - 1) + shell(i)) * dx);
+        }
+
+        // Normalise to unit total mass
+        let total_mass = masses[n - 1];
+        let masses: Vec<f64> = masses.iter().map(|m| m / total_mass).collect();
+        let density: Vec<f64> = density.iter().map(|rho| rho / total_mass).collect();
+
+        let mut model = Self {
+            radii,
+            density,
+            masses,
+            potentials: Vec::new(),
+            distribution: Vec::new(),
+        };
+        model.solve(&|_| 0.0);
+        model
+    }
+
+    // Put the profile in equilibrium in the potential of its own mass plus `external_mass(r)` within each radius
+    pub(crate) fn set_external_mass(&mut self, external_mass: impl Fn(f64) -> f64) {
+        self.solve(&external_mass);
+    }
+
+    // Mass within radius r
+    pub(crate) fn enclosed_mass(&self, r: f64) -> f64 {
+        interpolate(&self.radii, &self.masses, r)
+    }
+
+    fn solve(&mut self, external_mass: &dyn Fn(f64) -> f64) {
+        let (radii, density) = (&self.radii, &self.density);
+        let n = radii.len();
+        let total_mass = |i: usize| self.masses[i] + external_mass(radii[i]);
+
+        // Psi(r) = integral from r outwards of M(r') / r'^2 dr', with the mass beyond the grid as a point mass
+        let mut potentials = vec![total_mass(n - 1) / radii[n - 1]; n];
+        for i in (0..n - 1).rev() {
+            let dx = (radii[i + 1] / radii[i]).ln();
+            potentials[i] = potentials[i + 1]
+                + 0.5 * (total_mass(i) / radii[i] + total_mass(i + 1) / radii[i + 1]) * dx;
+        }
+
+        // d rho / d Psi = (d rho / d r) / (d Psi / d r), with d Psi / d r = -M(r) / r^2 of all the mass
+        let gradients: Vec<f64> = (0..n)
+            .map(|i| {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                let drho_dr = (density[b] - density[a]) / (radii[b] - radii[a]);
+                drho_dr / (-total_mass(i) / radii[i].powi(2))
+            })
+            .collect();
+
+        // Eddington's formula f(E) = 1 / (sqrt(8) pi^2) d/dE integral of (d rho / d Psi) / sqrt(E - Psi),
+        // with Psi = E - u^2 removing the singularity at the upper limit
+        let psi_min = potentials[n - 1];
+        let integrals: Vec<f64> = potentials
+            .iter()
+            .map(|&energy| {
+                let u_max = (energy - psi_min).max(0.0).sqrt();
+                let du = u_max / EDDINGTON_POINTS as f64;
+                (0..EDDINGTON_POINTS)
+                    .map(|k| {
+                        let u = (k as f64 + 0.5) * du;
+                        2.0 * interpolate(&potentials, &gradients, energy - u * u) * du
+                    })
+                    .sum::<f64>()
+            })
+            .collect();
+        self.distribution = (0..n)
+            .map(|i| {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                let derivative = (integrals[b] - integrals[a]) / (potentials[b] - potentials[a]);
+                (derivative / (8.0f64.sqrt() * std::f64::consts::PI.powi(2))).max(0.0)
+            })
+            .collect();
+        self.potentials = potentials;
+    }
+
+    // Radius enclosing a uniformly drawn fraction of the mass
+    pub(crate) fn sample_radius(&self, rng: &mut impl Rng) -> f64 {
+        let fraction = rng.gen_range(self.masses[0]..1.0);
+        let i = self.masses.partition_point(|m| *m < fraction).max(1);
+        let t = (fraction - self.masses[i - 1]) / (self.masses[i] - self.masses[i - 1]);
+        (self.radii[i - 1].ln() + t * (self.radii[i] / self.radii[i - 1]).ln()).exp()
+    }
+
+    // Speed at radius r from p(v) ~ v^2 f(Psi(r) - v^2 / 2), by rejection under the scanned peak
+    // (up to the speed that would carry a particle beyond the grid)
+    pub(crate) fn sample_speed(&self, rng: &mut impl Rng, r: f64) -> f64 {
+        let psi = interpolate(&self.radii, &self.potentials, r);
+        let escape_speed = (2.0 * (psi - self.potentials[self.potentials.len() - 1]))
+            .max(0.0)
+            .sqrt();
+        let probability = |v: f64| v * v * self.distribution_at(psi - 0.5 * v * v);
+
+        let peak = (1..SPEED_SCAN_POINTS)
+            .map(|k| probability(escape_speed * k as f64 / SPEED_SCAN_POINTS as f64))
+            .fold(0.0, f64::max);
+        if peak <= 0.0 {
+            return 0.0;
+        }
+
+        loop {
+            let v = rng.gen_range(0.0..escape_speed);
+            if rng.gen_range(0.0..1.2 * peak) < probability(v) {
+                break v;
+            }
+        }
+    }
+
+    fn distribution_at(&self, energy: f64) -> f64 {
+        interpolate(&self.potentials, &self.distribution, energy)
+    }
+}
+
+// Logarithmically spaced radii from `min` to `max`
+fn log_grid(min: f64, max: f64) -> Vec<f64> {
+    let n = ((max / min).log10() * GRID_DENSITY).ceil() as usize;
+    (0..=n)
+        .map(|i| min * (max / min).powf(i as f64 / n as f64))
+        .collect()
+}
+
+// Linear interpolation of ys at x along a monotonic xs (either direction), held constant beyond the ends
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+    let n = xs.len();
+    let increasing = xs[n - 1] > xs[0];
+    let i = xs.partition_point(|xi| (*xi < x) == increasing);
+    if i == 0 {
+        return ys[0];
+    }
+    if i == n {
+        return ys[n - 1];
+    }
+
+    let t = (x - xs[i - 1]) / (xs[i] - xs[i - 1]);
+    ys[i - 1] + t * (ys[i] - ys[i - 1])
+}
+
+// King density (up to a constant) at dimensionless potential W:
+//   e^W erf(sqrt(W)) - sqrt(4 W / pi) (1 + 2 W / 3) = 2 / sqrt(pi) sum over n >= 2 of 2^n W^(n + 1/2) / (2n + 1)!!
+// summed as a series to avoid the cancellation near the tidal radius
+fn king_density(w: f64) -> f64 {
+    let mut term = 4.0 * w.powf(2.5) / 15.0;
+    let mut sum = 0.0;
+    let mut n = 2.0;
+    while term > 1.0e-17 * sum {
+        sum += term;
+        term *= 2.0 * w / (2.0 * n + 3.0);
+        n += 1.0;
+    }
+    2.0 / std::f64::consts::PI.sqrt() * sum
+}
+
+fn rk4_step(
+    derivatives: &impl Fn(f64, [f64; 2]) -> [f64; 2],
+    x: f64,
+    y: [f64; 2],
+    h: f64,
+) -> [f64; 2] {
+    let add = |y: [f64; 2], k: [f64; 2], s: f64| [y[0] + s * k[0], y[1] + s * k[1]];
+    let k1 = derivatives(x, y);
+    let k2 = derivatives(x + 0.5 * h, add(y, k1, 0.5 * h));
+    let k3 = derivatives(x + 0.5 * h, add(y, k2, 0.5 * h));
+    let k4 = derivatives(x + h, add(y, k3, h));
+    [0, 1].map(|i| y[i] + h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+use crate::{NBody, Snapshot};
+
+// Tipsy files, in either endianness (the "standard" XDR variant is big-endian):
+//   header: time: f64, nbodies, ndim, nsph, ndark, nstar (i32 each), padding: i32
+//   gas: mass, pos [3], vel [3], rho, temp, hsmooth, metals, phi (f32 each) for each of nsph
+//   dark: mass, pos [3], vel [3], eps, phi (f32 each) for each of ndark
+//   star: mass, pos [3], vel [3], metals, tform, eps, phi (f32 each) for each of nstar
+const HEADER_SIZE: usize = 32;
+const GAS_FIELDS: usize = 12;
+const DARK_FIELDS: usize = 9;
+const STAR_FIELDS: usize = 11;
+
+// Ghost kinds travel in the metallicity of gas particles
+const GAS_METALS: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+// What Tipsy gas particles become (dark matter and stars are always massive)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TipsyGas {
+    Massive,
+    Ghosts,
+    Ignored,
+}
+
+struct Header {
+    time: f64,
+    num_gas: usize,
+    num_dark: usize,
+    num_star: usize,
+}
+
+impl Snapshot {
+    pub fn load_tipsy(path: &Path, gas: TipsyGas) -> Result<Self> {
+        Self::read_tipsy(&mut File::open(path)?, gas)
+    }
+
+    pub fn save_tipsy(&self, path: &Path, endianness: Endianness) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_tipsy(&mut writer, endianness)?;
+        writer.flush()
+    }
+
+    // The endianness is whichever makes the header describe a 3D file of exactly this size
+    // (the step count is not part of the format, so it reads as zero)
+    pub fn read_tipsy(reader: &mut impl Read, gas: TipsyGas) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let (endianness, header) = [Endianness::Little, Endianness::Big]
+            .into_iter()
+            .find_map(|endianness| Some((endianness, read_header(&bytes, endianness)?)))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Not a Tipsy file"))?;
+
+        let mut values = bytes[HEADER_SIZE..]
+            .chunks_exact(4)
+            .map(|bytes| decode_f32(bytes, endianness));
+        let mut particle = |fields| values.by_ref().take(fields).collect::<Vec<_>>();
+
+        let mut state = NBody::new();
+        for _ in 0..header.num_gas {
+            let values = particle(GAS_FIELDS);
+            match gas {
+                TipsyGas::Massive => add_massive(&mut state, &values)?,
+                TipsyGas::Ghosts => state.add_ghost_particle(
+                    [values[1], values[2], values[3]],
+                    [values[4], values[5], values[6]],
+                    values[GAS_METALS],
+                ),
+                TipsyGas::Ignored => {}
+            }
+        }
+        for _ in 0..header.num_dark {
+            add_massive(&mut state, &particle(DARK_FIELDS))?;
+        }
+        for _ in 0..header.num_star {
+            add_massive(&mut state, &particle(STAR_FIELDS))?;
+        }
+
+        Ok(Self {
+            step: 0,
+            time: header.time,
+            state,
+        })
+    }
+
+    // Massive particles are written as dark matter and ghosts as massless gas
+    pub fn write_tipsy(&self, writer: &mut impl Write, endianness: Endianness) -> Result<()> {
+        let state = &self.state;
+        let count = |n: usize| {
+            i32::try_from(n)
+                .map_err(|_| Error::new(ErrorKind::InvalidInput, "Too many particles for Tipsy"))
+        };
+        let num_gas = count(state.num_ghost_particles())?;
+        let num_dark = count(state.num_massive_particles())?;
+        let num_bodies = count(state.num_ghost_particles() + state.num_massive_particles())?;
+
+        let time = match endianness {
+            Endianness::Little => self.time.to_le_bytes(),
+            Endianness::Big => self.time.to_be_bytes(),
+        };
+        writer.write_all(&time)?;
+        for value in [num_bodies, 3, num_gas, num_dark, 0, 0] {
+            writer.write_all(&encode_u32(value as u32, endianness))?;
+        }
+
+        for ((p, v), kind) in state
+            .ghost_positions()
+            .iter()
+            .zip(state.ghost_velocities())
+            .zip(state.ghost_kinds())
+        {
+            let mut values = [0.0; GAS_FIELDS];
+            values[1..7].copy_from_slice(&[p[0], p[1], p[2], v[0], v[1], v[2]]);
+            values[GAS_METALS] = *kind;
+            write_values(writer, &values, endianness)?;
+        }
+        for ((p, v), mass) in state
+            .massive_positions()
+            .iter()
+            .zip(state.massive_velocities())
+            .zip(state.massive_masses())
+        {
+            let values = [*mass, p[0], p[1], p[2], v[0], v[1], v[2], 0.0, 0.0];
+            write_values(writer, &values, endianness)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Mass, position and velocity lead every particle struct
+fn add_massive(state: &mut NBody, values: &[f32]) -> Result<()> {
+    let mass = values[0];
+    if mass.is_nan() || mass <= 0.0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Tipsy file contains a non-positive mass",
+        ));
+    }
+    state.add_massive_particle(
+        [values[1], values[2], values[3]],
+        [values[4], values[5], values[6]],
+        mass,
+    );
+    Ok(())
+}
+
+fn read_header(bytes: &[u8], endianness: Endianness) -> Option<Header> {
+    if bytes.len() < HEADER_SIZE {
+        return None;
+    }
+
+    let time_bytes = bytes[..8].try_into().unwrap();
+    let time = match endianness {
+        Endianness::Little => f64::from_le_bytes(time_bytes),
+        Endianness::Big => f64::from_be_bytes(time_bytes),
+    };
+    let [num_bodies, num_dims, num_gas, num_dark, num_star] =
+        std::array::from_fn(|i| decode_u32(&bytes[8 + 4 * i..], endianness) as i32 as i64);
+
+    let size = HEADER_SIZE as i64
+        + 4 * (GAS_FIELDS as i64 * num_gas
+            + DARK_FIELDS as i64 * num_dark
+            + STAR_FIELDS as i64 * num_star);
+    let valid = num_dims == 3
+        && [num_gas, num_dark, num_star].iter().all(|n| *n >= 0)
+        && num_bodies == num_gas + num_dark + num_star
+        && size == bytes.len() as i64;
+
+    valid.then_some(Header {
+        time,
+        num_gas: num_gas as usize,
+        num_dark: num_dark as usize,
+        num_star: num_star as usize,
+    })
+}
+
+fn write_values(writer: &mut impl Write, values: &[f32], endianness: Endianness) -> Result<()> {
+    for value in values {
+        writer.write_all(&encode_u32(value.to_bits(), endianness))?;
+    }
+    Ok(())
+}
+
+fn encode_u32(value: u32, endianness: Endianness) -> [u8; 4] {
+    match endianness {
+        Endianness::Little => value.to_le_bytes(),
+        Endianness::Big => value.to_be_bytes(),
+    }
+}
+
+fn decode_u32(bytes: &[u8], endianness: Endianness) -> u32 {
+    let bytes = bytes[..4].try_into().unwrap();
+    match endianness {
+        Endianness::Little => u32::from_le_bytes(bytes),
+        Endianness::Big => u32::from_be_bytes(bytes),
+    }
+}
+
+fn decode_f32(bytes: &[u8], endianness: Endianness) -> f32 {
+    f32::from_bits(decode_u32(bytes, endianness))
+}
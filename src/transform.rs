@@ -0,0 +1,153 @@
+use nalgebra::{Rotation3, Unit, Vector3};
+
+use crate::NBody;
+
+// Whole-system operations, for building initial conditions from generated or loaded pieces. Ghosts move with the
+// massive particles but carry no mass, so they are left out of the centre of mass.
+impl NBody {
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        for position in self.positions_mut() {
+            (0..3).for_each(|k| position[k] += offset[k]);
+        }
+    }
+
+    pub fn boost(&mut self, velocity: [f32; 3]) {
+        for particle_velocity in self.velocities_mut() {
+            (0..3).for_each(|k| particle_velocity[k] += velocity[k]);
+        }
+    }
+
+    // Positions and velocities turned by `angle` (in radians, anticlockwise seen from the tip of `axis`) about the
+    // axis through the origin
+    pub fn rotate(&mut self, axis: [f32; 3], angle: f32) {
+        debug_assert!(axis.iter().any(|x| *x != 0.0));
+
+        let rotation = Rotation3::from_axis_angle(&Unit::new_normalize(Vector3::from(axis)), angle);
+        for vector in self
+            .massive_positions
+            .iter_mut()
+            .chain(&mut self.massive_velocities)
+            .chain(&mut self.ghost_positions)
+            .chain(&mut self.ghost_velocities)
+        {
+            *vector = (rotation * Vector3::from(*vector)).into();
+        }
+    }
+
+    // Masses, lengths (about the origin) and velocities scaled independently. A system in equilibrium stays so if
+    // velocities scale as sqrt(mass / length).
+    pub fn scale_masses(&mut self, factor: f32) {
+        debug_assert!(factor > 0.0);
+
+        self.massive_masses
+            .iter_mut()
+            .for_each(|mass| *mass *= factor);
+    }
+
+    pub fn scale_lengths(&mut self, factor: f32) {
+        for position in self.positions_mut() {
+            position.iter_mut().for_each(|x| *x *= factor);
+        }
+    }
+
+    pub fn scale_velocities(&mut self, factor: f32) {
+        for velocity in self.velocities_mut() {
+            velocity.iter_mut().for_each(|v| *v *= factor);
+        }
+    }
+
+    // The particles of `other` appended after these
+    pub fn merge(&mut self, other: &NBody) {
+        self.massive_positions
+            .extend_from_slice(&other.massive_positions);
+        self.massive_velocities
+            .extend_from_slice(&other.massive_velocities);
+        self.massive_masses.extend_from_slice(&other.massive_masses);
+
+        self.ghost_positions
+            .extend_from_slice(&other.ghost_positions);
+        self.ghost_velocities
+            .extend_from_slice(&other.ghost_velocities);
+        self.ghost_kinds.extend_from_slice(&other.ghost_kinds);
+    }
+
+    // The massive particles for which `massive(position, velocity, mass)` holds and the ghosts for which
+    // `ghost(position, velocity, kind)` does, in their original order
+    pub fn subset(
+        &self,
+        mut massive: impl FnMut([f32; 3], [f32; 3], f32) -> bool,
+        mut ghost: impl FnMut([f32; 3], [f32; 3], f32) -> bool,
+    ) -> Self {
+        let mut subset = Self::new();
+        for n in 0..self.num_massive_particles() {
+            let (position, velocity, mass) = (
+                self.massive_positions[n],
+                self.massive_velocities[n],
+                self.massive_masses[n],
+            );
+            if massive(position, velocity, mass) {
+                subset.add_massive_particle(position, velocity, mass);
+            }
+        }
+        for n in 0..self.num_ghost_particles() {
+            let (position, velocity, kind) = (
+                self.ghost_positions[n],
+                self.ghost_velocities[n],
+                self.ghost_kinds[n],
+            );
+            if ghost(position, velocity, kind) {
+                subset.add_ghost_particle(position, velocity, kind);
+            }
+        }
+        subset
+    }
+
+    pub fn total_mass(&self) -> f32 {
+        self.massive_masses
+            .iter()
+            .map(|mass| *mass as f64)
+            .sum::<f64>() as f32
+    }
+
+    pub fn centre_of_mass(&self) -> [f32; 3] {
+        self.mass_weighted_mean(&self.massive_positions)
+    }
+
+    pub fn centre_of_mass_velocity(&self) -> [f32; 3] {
+        self.mass_weighted_mean(&self.massive_velocities)
+    }
+
+    // Moved so that the centre of mass sits at the origin at rest
+    pub fn to_centre_of_mass_frame(&mut self) {
+        let (centre, velocity) = (self.centre_of_mass(), self.centre_of_mass_velocity());
+        self.translate(centre.map(|x| -x));
+        self.boost(velocity.map(|v| -v));
+    }
+
+    fn mass_weighted_mean(&self, vectors: &[[f32; 3]]) -> [f32; 3] {
+        debug_assert!(self.num_massive_particles() > 0);
+
+        let mut sum = [0.0f64; 3];
+        for (vector, mass) in vectors.iter().zip(&self.massive_masses) {
+            (0..3).for_each(|k| sum[k] += *mass as f64 * vector[k] as f64);
+        }
+        let total_mass = self
+            .massive_masses
+            .iter()
+            .map(|mass| *mass as f64)
+            .sum::<f64>();
+        sum.map(|x| (x / total_mass) as f32)
+    }
+
+    fn positions_mut(&mut self) -> impl Iterator<Item = &mut [f32; 3]> {
+        self.massive_positions
+            .iter_mut()
+            .chain(self.ghost_positions.iter_mut())
+    }
+
+    fn velocities_mut(&mut self) -> impl Iterator<Item = &mut [f32; 3]> {
+        self.massive_velocities
+            .iter_mut()
+            .chain(self.ghost_velocities.iter_mut())
+    }
+}
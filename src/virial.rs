@@ -0,0 +1,77 @@
+use crate::NBody;
+
+// Energies of the massive particles, with the potential softened like the shaders' force (pass the run's
+// `gravitational_constant` and `smoothing_length`). Ghosts have no mass and take no part.
+impl NBody {
+    // In the centre-of-mass frame
+    pub fn kinetic_energy(&self) -> f64 {
+        if self.num_massive_particles() == 0 {
+            return 0.0;
+        }
+
+        let frame_velocity = self.centre_of_mass_velocity();
+        self.massive_velocities
+            .iter()
+            .zip(&self.massive_masses)
+            .map(|(velocity, mass)| {
+                let speed2 = (0..3)
+                    .map(|k| (velocity[k] as f64 - frame_velocity[k] as f64).powi(2))
+                    .sum::<f64>();
+                0.5 * *mass as f64 * speed2
+            })
+            .sum()
+    }
+
+    // Sum over pairs of -G m_i m_j / sqrt(r^2 + smoothing_length^2), the potential of a Plummer-softened force
+    pub fn potential_energy(&self, grav_const: f32, smoothing_length: f32) -> f64 {
+        let softening2 = (smoothing_length as f64).powi(2);
+        let positions = &self.massive_positions;
+        let masses = &self.massive_masses;
+
+        let mut potential = 0.0;
+        for i in 0..positions.len() {
+            let mut sum = 0.0;
+            for j in 0..i {
+                let r2 = (0..3)
+                    .map(|k| (positions[i][k] as f64 - positions[j][k] as f64).powi(2))
+                    .sum::<f64>();
+                sum += masses[j] as f64 / (r2 + softening2).sqrt();
+            }
+            potential -= masses[i] as f64 * sum;
+        }
+        grav_const as f64 * potential
+    }
+
+    // K / |W|, which is 1/2 in equilibrium
+    pub fn virial_ratio(&self, grav_const: f32, smoothing_length: f32) -> f64 {
+        self.kinetic_energy() / -self.potential_energy(grav_const, smoothing_length)
+    }
+
+    // Velocities relative to the centre of mass (the ghosts' too, so they keep tracing the same flow) scaled to give
+    // the virial ratio K / |W| = `virial_ratio`. A cold system (K = 0) has no motions to scale, so it is left as it
+    // is, with a warning; give it random velocities first, or relax it instead.
+    pub fn virialise(&mut self, grav_const: f32, smoothing_length: f32, virial_ratio: f32) {
+        debug_assert!(virial_ratio >= 0.0);
+
+        let kinetic = self.kinetic_energy();
+        if kinetic <= 0.0 {
+            log::warn!(
+                "A system with no kinetic energy cannot be virialised, leaving its velocities"
+            );
+            return;
+        }
+
+        let target = virial_ratio as f64 * -self.potential_energy(grav_const, smoothing_length);
+        let factor = (target / kinetic).sqrt() as f32;
+        let frame_velocity = self.centre_of_mass_velocity();
+        for velocity in self
+            .massive_velocities
+            .iter_mut()
+            .chain(&mut self.ghost_velocities)
+        {
+            (0..3).for_each(|k| {
+                velocity[k] = frame_velocity[k] + factor * (velocity[k] - frame_velocity[k])
+            });
+        }
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::camera;
+use heavens::{capture, NBody, Settings, Simulation};
+
+const WIDTH: f32 = 48.0;
+const HEIGHT: f32 = 32.0;
+const PIXEL_SIZE: f32 = 2.0;
+
+fn settings() -> Settings {
+    Settings {
+        display_width: WIDTH,
+        display_height: HEIGHT,
+        pixel_size: PIXEL_SIZE,
+        time_step: 1.0e-3,
+        ghost_stack_visible_limit: 2.0,
+        ..common::settings()
+    }
+}
+
+// A row of massive bodies and a stack of red ghosts in front of the camera
+fn bodies() -> NBody {
+    let mut nbody = NBody::new();
+    for n in 0..5 {
+        nbody.add_massive_particle([0.0, 0.1 * n as f32, 0.0], [0.0; 3], 1.0);
+    }
+    for _ in 0..3 {
+        nbody.add_ghost_particle([0.0, -0.5, 0.0], [0.0; 3], 1.0);
+    }
+
+    nbody
+}
+
+fn read_png(path: &std::path::Path) -> (png::OutputInfo, Vec<u8>) {
+    let decoder = png::Decoder::new(std::fs::File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    pixels.truncate(info.buffer_size());
+
+    (info, pixels)
+}
+
+#[test]
+fn captured_frame_shows_particles() {
+    let simulation =
+        pollster::block_on(Simulation::new_offscreen(settings(), camera(), bodies())).unwrap();
+    let pixels = simulation.capture_frame();
+
+    assert_eq!(
+        pixels.len(),
+        (4.0 * WIDTH * PIXEL_SIZE * HEIGHT * PIXEL_SIZE) as usize
+    );
+
+    let rgba = pixels.chunks(4).collect::<Vec<_>>();
+    let white = rgba.iter().filter(|p| p[..3] == [255, 255, 255]).count();
+    let red = rgba.iter().filter(|p| p[..3] == [255, 0, 0]).count();
+    let black = rgba.iter().filter(|p| p[..3] == [0, 0, 0]).count();
+    assert!(white > 0, "no massive particles drawn");
+    assert!(red > 0, "no ghosts drawn");
+    assert_eq!(white + red + black, rgba.len(), "unexpected colours");
+}
+
+#[test]
+fn capture_writes_numbered_frames() {
+    let output_dir = std::env::temp_dir().join(format!("heavens-capture-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&output_dir);
+
+    pollster::block_on(capture(settings(), camera(), bodies(), 7, 3, &output_dir)).unwrap();
+
+    let mut frames = std::fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    frames.sort();
+    assert_eq!(
+        frames,
+        ["frame_000000.png", "frame_000001.png", "frame_000002.png"]
+    );
+
+    let (info, pixels) = read_png(&output_dir.join("frame_000002.png"));
+    assert_eq!(info.width, (WIDTH * PIXEL_SIZE) as u32);
+    assert_eq!(info.height, (HEIGHT * PIXEL_SIZE) as u32);
+    assert_eq!(info.color_type, png::ColorType::Rgba);
+    assert!(pixels.chunks(4).any(|p| p[..3] != [0, 0, 0]));
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
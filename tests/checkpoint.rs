@@ -0,0 +1,117 @@
+mod common;
+
+use common::{camera, random_bodies};
+use heavens::{
+    checkpoint_path, latest_checkpoint, Checkpoint, Integrator, Settings, Simulation, Solver,
+};
+
+fn settings(integrator: Integrator, solver: Solver) -> Settings {
+    Settings {
+        smoothing_length: 0.1,
+        integrator,
+        solver,
+        ..common::settings()
+    }
+}
+
+#[test]
+fn restarted_run_continues_bit_for_bit() {
+    for (integrator, solver) in [
+        (Integrator::Euler, Solver::Direct),
+        (Integrator::Leapfrog, Solver::Direct),
+        (Integrator::VelocityVerlet, Solver::Direct),
+        (Integrator::Hermite, Solver::Direct),
+        (Integrator::Yoshida, Solver::Direct),
+        (Integrator::Leapfrog, Solver::Tree),
+        (Integrator::Leapfrog, Solver::ParticleMesh),
+    ] {
+        let (bytes, expected, expected_time) = {
+            let mut original = pollster::block_on(Simulation::new_headless(
+                settings(integrator, solver),
+                camera(),
+                random_bodies(11, 90, 40, 0.5),
+            ))
+            .unwrap();
+            for _ in 0..5 {
+                original.update();
+            }
+            let mut bytes = Vec::new();
+            original.checkpoint().write(&mut bytes).unwrap();
+            for _ in 0..5 {
+                original.update();
+            }
+            (bytes, original.read_state(), original.time())
+        };
+
+        let checkpoint = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(checkpoint.steps, 5);
+        assert_eq!(checkpoint.settings.integrator, integrator);
+        assert_eq!(checkpoint.settings.solver, solver);
+
+        let mut restarted = pollster::block_on(Simulation::new_headless(
+            checkpoint.settings,
+            checkpoint.camera,
+            checkpoint.state(),
+        ))
+        .unwrap();
+        restarted.restore(&checkpoint);
+        for _ in 0..5 {
+            restarted.update();
+        }
+        assert_eq!(restarted.steps(), 10);
+        assert_eq!(restarted.time(), expected_time);
+
+        let state = restarted.read_state();
+        assert_eq!(
+            state.massive_positions(),
+            expected.massive_positions(),
+            "{:?} {:?}",
+            integrator,
+            solver
+        );
+        assert_eq!(
+            state.massive_velocities(),
+            expected.massive_velocities(),
+            "{:?} {:?}",
+            integrator,
+            solver
+        );
+        assert_eq!(state.ghost_positions(), expected.ghost_positions());
+        assert_eq!(state.ghost_velocities(), expected.ghost_velocities());
+        assert_eq!(state.ghost_kinds(), expected.ghost_kinds());
+    }
+}
+
+#[test]
+fn truncated_checkpoint_is_rejected() {
+    let simulation = pollster::block_on(Simulation::new_headless(
+        settings(Integrator::Leapfrog, Solver::Direct),
+        camera(),
+        random_bodies(11, 90, 40, 0.5),
+    ))
+    .unwrap();
+
+    let mut bytes = Vec::new();
+    simulation.checkpoint().write(&mut bytes).unwrap();
+
+    assert!(Checkpoint::read(&mut &bytes[..bytes.len() - 4]).is_err());
+    assert!(Checkpoint::read(&mut &b"HVNS"[..]).is_err());
+}
+
+#[test]
+fn latest_checkpoint_is_found_by_step() {
+    let dir = std::env::temp_dir().join(format!("heavens-checkpoints-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    assert_eq!(latest_checkpoint(&dir).unwrap(), None);
+
+    for step in [900, 10_000, 1000] {
+        std::fs::write(checkpoint_path(&dir, step), []).unwrap();
+    }
+    std::fs::write(dir.join("checkpoint_0000099999.hvc.partial"), []).unwrap();
+    assert_eq!(
+        latest_checkpoint(&dir).unwrap(),
+        Some(checkpoint_path(&dir, 10_000))
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
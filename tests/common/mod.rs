@@ -0,0 +1,107 @@
+// Each test binary uses only some of these
+#![allow(dead_code)]
+
+use heavens::{Camera, NBody, Settings};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Any camera will do for tests that don't render
+pub fn camera() -> Camera {
+    Camera::new([1.0, 0.0, 1.0], [0.0, 0.0, 0.0], 90.0_f32.to_radians(), 1.0)
+}
+
+// A small display and mesh keep the GPU buffers cheap; tests override what they exercise
+pub fn settings() -> Settings {
+    Settings {
+        display_width: 64.0,
+        display_height: 64.0,
+        time_step: 1.0e-2,
+        smoothing_length: 1.0e-2,
+        blur_radius: 0.0,
+        mesh_size: 16,
+        ..Settings::default()
+    }
+}
+
+// Bodies scattered through a cube of side 10, with velocity components up to `max_speed`
+pub fn random_bodies(
+    seed: u64,
+    num_massive_bodies: usize,
+    num_ghosts: usize,
+    max_speed: f32,
+) -> NBody {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut nbody = NBody::new();
+
+    for _ in 0..num_massive_bodies {
+        let position = [0; 3].map(|_| rng.gen_range(-5.0..5.0));
+        let velocity = [0; 3].map(|_| rng.gen_range(-max_speed..=max_speed));
+        nbody.add_massive_particle(position, velocity, rng.gen_range(0.5..1.5));
+    }
+    for _ in 0..num_ghosts {
+        let position = [0; 3].map(|_| rng.gen_range(-5.0..5.0));
+        let velocity = [0; 3].map(|_| rng.gen_range(-max_speed..=max_speed));
+        nbody.add_ghost_particle(position, velocity, 1.0);
+    }
+
+    nbody
+}
+
+// Host f64 sum of the softened acceleration at `position` from the massive bodies picked by `include`
+pub fn direct_acceleration(
+    settings: &Settings,
+    nbody: &NBody,
+    position: [f32; 3],
+    include: impl Fn(usize) -> bool,
+) -> [f64; 3] {
+    let softening2 = (settings.smoothing_length as f64).powi(2);
+
+    let mut acceleration = [0.0f64; 3];
+    for (n, (p, m)) in nbody
+        .massive_positions()
+        .iter()
+        .zip(nbody.massive_masses())
+        .enumerate()
+    {
+        if !include(n) {
+            continue;
+        }
+        let dx = [0, 1, 2].map(|i| p[i] as f64 - position[i] as f64);
+        let r2 = dx.iter().map(|x| x * x).sum::<f64>() + softening2;
+        let f = settings.gravitational_constant as f64 * *m as f64 / (r2 * r2.sqrt());
+        for i in 0..3 {
+            acceleration[i] += f * dx[i];
+        }
+    }
+
+    acceleration
+}
+
+// Length of the difference relative to the length of `expected`
+pub fn relative_error<T: Copy + Into<f64>>(expected: [T; 3], actual: [T; 3]) -> f64 {
+    let [expected, actual] = [expected, actual].map(|v| v.map(Into::<f64>::into));
+    let difference = (0..3)
+        .map(|i| (expected[i] - actual[i]).powi(2))
+        .sum::<f64>()
+        .sqrt();
+    let magnitude = expected.iter().map(|x| x * x).sum::<f64>().sqrt();
+    difference / magnitude
+}
+
+// Particle `i` of the file format fixtures
+pub fn position(i: usize) -> [f32; 3] {
+    let i = i as f32;
+    [i, i + 0.5, -i]
+}
+
+pub fn velocity(i: usize) -> [f32; 3] {
+    [i as f32 / 4.0, -0.5, 1.0]
+}
+
+pub fn assert_same_state(a: &NBody, b: &NBody) {
+    assert_eq!(a.massive_positions(), b.massive_positions());
+    assert_eq!(a.massive_velocities(), b.massive_velocities());
+    assert_eq!(a.massive_masses(), b.massive_masses());
+    assert_eq!(a.ghost_positions(), b.ghost_positions());
+    assert_eq!(a.ghost_velocities(), b.ghost_velocities());
+    assert_eq!(a.ghost_kinds(), b.ghost_kinds());
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::{camera, random_bodies};
+use heavens::{CpuSimulation, Integrator, Settings, Simulation};
+
+const NUM_STEPS: usize = 20;
+
+fn settings(integrator: Integrator) -> Settings {
+    Settings {
+        smoothing_length: 0.1,
+        integrator,
+        ..common::settings()
+    }
+}
+
+fn max_difference(a: &[[f32; 3]], b: &[[f32; 3]]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (0..3).map(|k| (a[k] - b[k]).abs()).fold(0.0, f32::max))
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn gpu_direct_sum_matches_cpu_reference() {
+    for integrator in [
+        Integrator::Euler,
+        Integrator::Leapfrog,
+        Integrator::VelocityVerlet,
+        Integrator::Hermite,
+        Integrator::Yoshida,
+    ] {
+        let mut gpu = pollster::block_on(Simulation::new_headless(
+            settings(integrator),
+            camera(),
+            random_bodies(9, 100, 70, 0.5),
+        ))
+        .unwrap();
+        let mut cpu = CpuSimulation::new(settings(integrator), random_bodies(9, 100, 70, 0.5));
+        for _ in 0..NUM_STEPS {
+            gpu.update();
+            cpu.update();
+        }
+        let gpu = gpu.read_state();
+        let cpu = cpu.state();
+
+        for (name, difference) in [
+            (
+                "massive positions",
+                max_difference(gpu.massive_positions(), cpu.massive_positions()),
+            ),
+            (
+                "massive velocities",
+                max_difference(gpu.massive_velocities(), cpu.massive_velocities()),
+            ),
+            (
+                "ghost positions",
+                max_difference(gpu.ghost_positions(), cpu.ghost_positions()),
+            ),
+            (
+                "ghost velocities",
+                max_difference(gpu.ghost_velocities(), cpu.ghost_velocities()),
+            ),
+        ] {
+            assert!(
+                difference < 1.0e-4,
+                "{:?}: {} differ by {}",
+                integrator,
+                name,
+                difference
+            );
+        }
+    }
+}
+
+#[test]
+fn new_settings_recompute_cached_forces() {
+    // Leapfrog reuses the last step's forces, which a change of G must not leave behind
+    let doubled_gravity = Settings {
+        gravitational_constant: 2.0 * settings(Integrator::Leapfrog).gravitational_constant,
+        ..settings(Integrator::Leapfrog)
+    };
+    let mut changed = CpuSimulation::new(
+        settings(Integrator::Leapfrog),
+        random_bodies(9, 100, 70, 0.5),
+    );
+    changed.update();
+    changed.set_settings(doubled_gravity);
+    changed.update();
+
+    let mut reference = CpuSimulation::new(
+        settings(Integrator::Leapfrog),
+        random_bodies(9, 100, 70, 0.5),
+    );
+    reference.update();
+    let mut reference = CpuSimulation::new(doubled_gravity, reference.read_state());
+    reference.update();
+
+    assert_eq!(
+        changed.state().massive_velocities(),
+        reference.state().massive_velocities()
+    );
+}
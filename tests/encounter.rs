@@ -0,0 +1,180 @@
+use heavens::{DiscOrientation, Encounter, GalaxyBuilder, NBody, OrbitalElements};
+use nalgebra::Vector3;
+use rand::{rngs::StdRng, SeedableRng};
+
+const GRAV_CONST: f32 = 2.0;
+
+fn encounter(pericentre: f32, eccentricity: f32, separation: f32) -> Encounter {
+    Encounter {
+        pericentre,
+        eccentricity,
+        separation,
+        first: DiscOrientation::PROGRADE,
+        second: DiscOrientation::RETROGRADE,
+    }
+}
+
+#[test]
+fn galaxies_start_on_the_requested_orbit() {
+    let (first_mass, second_mass) = (3.0, 1.0);
+    let mu = GRAV_CONST as f64 * (first_mass + second_mass) as f64;
+
+    for (pericentre, eccentricity, separation) in
+        [(4.0, 0.5, 10.0), (4.0, 1.0, 30.0), (2.0, 1.5, 40.0)]
+    {
+        let [(r1, v1), (r2, v2)] = encounter(pericentre, eccentricity, separation).states(
+            GRAV_CONST,
+            first_mass,
+            second_mass,
+        );
+
+        // About the centre of mass, at rest
+        for k in 0..3 {
+            assert!((first_mass * r1[k] + second_mass * r2[k]).abs() < 1.0e-4);
+            assert!((first_mass * v1[k] + second_mass * v2[k]).abs() < 1.0e-5);
+        }
+
+        let r = Vector3::from_fn(|k, _| r2[k] as f64 - r1[k] as f64);
+        let v = Vector3::from_fn(|k, _| v2[k] as f64 - v1[k] as f64);
+        assert!((r.norm() / separation as f64 - 1.0).abs() < 1.0e-5);
+        // On the way in, turning about +z
+        assert!(r.dot(&v) < 0.0);
+        let h = r.cross(&v);
+        assert!(h.x.abs() < 1.0e-6 && h.y.abs() < 1.0e-6 && h.z > 0.0);
+
+        // The specific energy and angular momentum of a conic with this pericentre and eccentricity
+        let energy = 0.5 * v.norm_squared() - mu / separation as f64;
+        let expected = 0.5 * mu * (eccentricity as f64 - 1.0) / pericentre as f64;
+        assert!((energy - expected).abs() < 1.0e-5, "energy {}", energy);
+        let expected = mu * pericentre as f64 * (1.0 + eccentricity as f64);
+        assert!((h.z * h.z / expected - 1.0).abs() < 1.0e-5);
+
+        // With pericentre along +x
+        if eccentricity != 1.0 {
+            let elements = OrbitalElements::from_state(
+                mu as f32,
+                r.map(|x| x as f32).into(),
+                v.map(|x| x as f32).into(),
+            );
+            assert!(
+                (elements.semi_major_axis * (1.0 - eccentricity) / pericentre - 1.0).abs() < 1.0e-4
+            );
+            assert!((elements.eccentricity - eccentricity).abs() < 1.0e-4);
+            let periapsis = elements.argument_of_periapsis + elements.longitude_of_ascending_node;
+            assert!(periapsis.sin().abs() < 1.0e-4 && periapsis.cos() > 0.0);
+        }
+    }
+}
+
+#[test]
+fn galaxies_spin_with_their_orientations() {
+    let first = GalaxyBuilder::new([0.0; 3], [0.0; 3]).disc(1.0, 0.1, 2.0, 1.5, 2000);
+    let second = GalaxyBuilder::new([0.0; 3], [0.0; 3])
+        .spin_axis([1.0, 0.0, 0.0])
+        .disc(1.5, 0.1, 1.0, 1.5, 2000);
+    let mut encounter = encounter(5.0, 1.0, 40.0);
+    encounter.second = DiscOrientation {
+        inclination: 60.0f32.to_radians(),
+        argument_of_pericentre: 30.0f32.to_radians(),
+    };
+
+    let mut nbody = NBody::new();
+    encounter.add_to(
+        &mut StdRng::seed_from_u64(22),
+        GRAV_CONST,
+        &first,
+        &second,
+        &mut nbody,
+    );
+    assert_eq!(nbody.num_massive_particles(), 4000);
+
+    let states = encounter.states(GRAV_CONST, first.total_mass(), second.total_mass());
+    let expected_spins = [[0.0, 0.0, 1.0], encounter.second.spin_axis()];
+    for (n, ((centre, velocity), spin_axis)) in states.into_iter().zip(expected_spins).enumerate() {
+        let particles = 2000 * n..2000 * (n + 1);
+        let relative = |vectors: &[[f32; 3]], offset: [f32; 3]| -> Vec<Vector3<f64>> {
+            vectors[particles.clone()]
+                .iter()
+                .map(|v| Vector3::from_fn(|k, _| v[k] as f64 - offset[k] as f64))
+                .collect()
+        };
+        let positions = relative(nbody.massive_positions(), centre);
+        let velocities = relative(nbody.massive_velocities(), velocity);
+
+        // Each disc centred on its place on the orbit, with its angular momentum along its spin axis
+        let mean = positions.iter().sum::<Vector3<f64>>() / 2000.0;
+        assert!(mean.norm() < 0.2, "centre offset {:?}", mean);
+        let momentum = positions
+            .iter()
+            .zip(&velocities)
+            .map(|(position, velocity)| position.cross(velocity))
+            .sum::<Vector3<f64>>();
+        let spin_axis = Vector3::from(spin_axis.map(|x| x as f64));
+        let alignment = momentum.dot(&spin_axis) / momentum.norm();
+        assert!(alignment > 0.99, "disc spin alignment {}", alignment);
+    }
+
+    // Tilted by 60 degrees from the orbital plane, about a node 30 degrees before pericentre
+    let [x, y, z] = encounter.second.spin_axis();
+    assert!((z - 0.5).abs() < 1.0e-6);
+    let node = [30.0f32.to_radians().cos(), -30.0f32.to_radians().sin()];
+    assert!((x * node[0] + y * node[1]).abs() < 1.0e-6);
+    assert_eq!(DiscOrientation::RETROGRADE.spin_axis()[2], -1.0);
+}
+
+#[test]
+fn prebuilt_systems_are_placed_by_their_centres_of_mass() {
+    // Built away from the origin, spinning about +z
+    let systems = [(2000, [3.0, -1.0, 2.0]), (1000, [-4.0, 0.5, 0.0])].map(|(seed, offset)| {
+        let mut system = NBody::new();
+        GalaxyBuilder::new(offset, offset.map(|x| 0.1 * x))
+            .disc(1.0, 0.1, 1.0, 1.5, 1000)
+            .add_to(&mut StdRng::seed_from_u64(seed), GRAV_CONST, &mut system);
+        system
+    });
+    let mut encounter = encounter(5.0, 0.5, 12.0);
+    encounter.second = DiscOrientation {
+        inclination: 60.0f32.to_radians(),
+        argument_of_pericentre: 30.0f32.to_radians(),
+    };
+
+    let mut nbody = NBody::new();
+    encounter.add_systems_to(GRAV_CONST, &systems[0], &systems[1], &mut nbody);
+    assert_eq!(nbody.num_massive_particles(), 2000);
+
+    let states = encounter.states(GRAV_CONST, systems[0].total_mass(), systems[1].total_mass());
+    let expected_spins = [[0.0, 0.0, 1.0], encounter.second.spin_axis()];
+    for (n, ((centre, velocity), spin_axis)) in states.into_iter().zip(expected_spins).enumerate() {
+        let particles = 1000 * n..1000 * (n + 1);
+        let mut placed = NBody::new();
+        for m in particles {
+            placed.add_massive_particle(
+                nbody.massive_positions()[m],
+                nbody.massive_velocities()[m],
+                nbody.massive_masses()[m],
+            );
+        }
+
+        let (found_centre, found_velocity) =
+            (placed.centre_of_mass(), placed.centre_of_mass_velocity());
+        for k in 0..3 {
+            assert!((found_centre[k] - centre[k]).abs() < 1.0e-4);
+            assert!((found_velocity[k] - velocity[k]).abs() < 1.0e-5);
+        }
+
+        placed.to_centre_of_mass_frame();
+        let momentum = placed
+            .massive_positions()
+            .iter()
+            .zip(placed.massive_velocities())
+            .zip(placed.massive_masses())
+            .map(|((position, velocity), mass)| {
+                let (r, v) = (Vector3::from(*position), Vector3::from(*velocity));
+                r.cross(&v).map(|x| x as f64) * *mass as f64
+            })
+            .sum::<Vector3<f64>>();
+        let spin_axis = Vector3::from(spin_axis.map(|x| x as f64));
+        let alignment = momentum.dot(&spin_axis) / momentum.norm();
+        assert!(alignment > 0.99, "system spin alignment {}", alignment);
+    }
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{assert_same_state, camera, position, settings, velocity};
+use std::path::Path;
+
+use heavens::{GadgetFormat, GadgetMapping, GadgetRole, NBody, Simulation, Snapshot};
+
+// Both fixtures hold the same six particles at time 1.25: two gas particles (masses 1 and 2 in the MASS
+// block), three halo particles (massarr 0.5) and one boundary particle (mass 0), followed by an ignored U
+// block. Particle `i` sits at [i, i + 0.5, -i] moving at [i / 4, -0.5, 1].
+const FORMAT1: &str = "tests/fixtures/format1.gadget";
+const FORMAT2_BIG_ENDIAN_DOUBLE: &str = "tests/fixtures/format2_big_endian_double.gadget";
+
+#[test]
+fn fixtures_map_types_to_massive_and_ghost_particles() {
+    for path in [FORMAT1, FORMAT2_BIG_ENDIAN_DOUBLE] {
+        let snapshot = Snapshot::load_gadget(Path::new(path), &GadgetMapping::default()).unwrap();
+        let state = &snapshot.state;
+
+        assert_eq!(snapshot.step, 0);
+        assert_eq!(snapshot.time, 1.25);
+        assert_eq!(state.massive_masses(), [1.0, 2.0, 0.5, 0.5, 0.5]);
+        assert_eq!(
+            state.massive_positions(),
+            (0..5).map(position).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            state.massive_velocities(),
+            (0..5).map(velocity).collect::<Vec<_>>()
+        );
+        assert_eq!(state.ghost_positions(), [position(5)]);
+        assert_eq!(state.ghost_velocities(), [velocity(5)]);
+        assert_eq!(state.ghost_kinds(), [0.0]);
+    }
+
+    // Halo particles as ghosts of kind 3, without the gas or the boundary
+    let mapping = GadgetMapping([
+        GadgetRole::Ignored,
+        GadgetRole::Ghost(3.0),
+        GadgetRole::Massive,
+        GadgetRole::Massive,
+        GadgetRole::Massive,
+        GadgetRole::Ignored,
+    ]);
+    let state = Snapshot::load_gadget(Path::new(FORMAT1), &mapping)
+        .unwrap()
+        .state;
+    assert_eq!(state.num_massive_particles(), 0);
+    assert_eq!(
+        state.ghost_positions(),
+        (2..5).map(position).collect::<Vec<_>>()
+    );
+    assert_eq!(state.ghost_kinds(), [3.0; 3]);
+
+    // The massless boundary particle cannot be massive
+    let mapping = GadgetMapping([GadgetRole::Massive; 6]);
+    assert!(Snapshot::load_gadget(Path::new(FORMAT1), &mapping).is_err());
+}
+
+#[test]
+fn fixture_round_trips_through_both_formats() {
+    let mapping = GadgetMapping::default();
+    let fixture = Snapshot::load_gadget(Path::new(FORMAT1), &mapping).unwrap();
+
+    for format in [GadgetFormat::Format1, GadgetFormat::Format2] {
+        let mut bytes = Vec::new();
+        fixture.write_gadget(&mut bytes, &mapping, format).unwrap();
+        let read = Snapshot::read_gadget(&mut bytes.as_slice(), &mapping).unwrap();
+
+        assert_eq!(read.time, fixture.time);
+        assert_same_state(&read.state, &fixture.state);
+
+        // A truncated file is an error rather than a shorter snapshot
+        bytes.truncate(bytes.len() - 3);
+        assert!(Snapshot::read_gadget(&mut bytes.as_slice(), &mapping).is_err());
+    }
+}
+
+#[test]
+fn simulation_snapshot_round_trips() {
+    let mut bodies = NBody::new();
+    bodies.add_massive_particle([1.0, 0.0, 0.0], [0.0, 0.5, 0.0], 1.0);
+    bodies.add_massive_particle([-1.0, 0.0, 0.0], [0.0, -0.5, 0.0], 1.0);
+    bodies.add_ghost_particle([0.0, 3.0, 0.0], [0.0, 0.0, 0.2], 0.0);
+    bodies.add_ghost_particle([0.0, -3.0, 0.0], [0.0, 0.0, -0.2], 0.0);
+
+    let mut simulation =
+        pollster::block_on(Simulation::new_headless(settings(), camera(), bodies)).unwrap();
+    for _ in 0..5 {
+        simulation.update();
+    }
+    let snapshot = simulation.snapshot();
+    drop(simulation);
+
+    let path = std::env::temp_dir().join(format!("heavens-{}.gadget", std::process::id()));
+    snapshot
+        .save_gadget(&path, &GadgetMapping::default(), GadgetFormat::Format2)
+        .unwrap();
+    let read = Snapshot::load_gadget(&path, &GadgetMapping::default()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read.time, snapshot.time);
+    assert_same_state(&read.state, &snapshot.state);
+}
@@ -0,0 +1,525 @@
+use heavens::{CpuSimulation, GalaxyBuilder, Integrator, NBody, Orientation, Settings, Spin};
+use rand::{rngs::StdRng, SeedableRng};
+
+const GRAV_CONST: f32 = 2.0;
+
+// Kinetic energy in the frame moving with `frame_velocity` and (unsoftened) potential energy of the massive
+// particles
+fn energies(nbody: &NBody, grav_const: f32, frame_velocity: [f32; 3]) -> (f64, f64) {
+    let positions = nbody.massive_positions();
+    let masses = nbody.massive_masses();
+
+    let kinetic = nbody
+        .massive_velocities()
+        .iter()
+        .zip(masses)
+        .map(|(v, m)| {
+            let speed2 = (0..3)
+                .map(|k| (v[k] as f64 - frame_velocity[k] as f64).powi(2))
+                .sum::<f64>();
+            0.5 * *m as f64 * speed2
+        })
+        .sum();
+
+    let mut potential = 0.0;
+    for i in 0..positions.len() {
+        for j in 0..i {
+            let r = (0..3)
+                .map(|k| (positions[i][k] as f64 - positions[j][k] as f64).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            potential -= grav_const as f64 * masses[i] as f64 * masses[j] as f64 / r;
+        }
+    }
+
+    (kinetic, potential)
+}
+
+fn mean(vectors: &[[f32; 3]]) -> [f64; 3] {
+    [0, 1, 2].map(|k| vectors.iter().map(|v| v[k] as f64).sum::<f64>() / vectors.len() as f64)
+}
+
+fn distances(nbody: &NBody, centre: [f32; 3]) -> Vec<f32> {
+    let mut distances: Vec<f32> = nbody
+        .massive_positions()
+        .iter()
+        .map(|p| {
+            (0..3)
+                .map(|k| (p[k] - centre[k]).powi(2))
+                .sum::<f32>()
+                .sqrt()
+        })
+        .collect();
+    distances.sort_by(f32::total_cmp);
+    distances
+}
+
+#[test]
+fn plummer_sphere_is_in_virial_equilibrium() {
+    let (centre, drift) = ([10.0, -5.0, 2.0], [0.5, 0.0, -1.0]);
+    let (scale_radius, total_mass, num_particles) = (3.0, 4.0, 4000);
+
+    let mut nbody = NBody::new();
+    nbody.add_plummer_sphere(
+        &mut StdRng::seed_from_u64(17),
+        GRAV_CONST,
+        centre,
+        drift,
+        scale_radius,
+        total_mass,
+        num_particles,
+    );
+
+    assert_eq!(nbody.num_massive_particles(), num_particles);
+    let mass = nbody.massive_masses().iter().sum::<f32>();
+    assert!((mass - total_mass).abs() < 1.0e-3, "total mass {}", mass);
+
+    // The sample's own centre of mass moves with the drift
+    let (mean_position, mean_velocity) = (
+        mean(nbody.massive_positions()),
+        mean(nbody.massive_velocities()),
+    );
+    for k in 0..3 {
+        assert!((mean_position[k] - centre[k] as f64).abs() < 1.0e-3);
+        assert!((mean_velocity[k] - drift[k] as f64).abs() < 1.0e-4);
+    }
+
+    // Half the mass lies within a / sqrt(2^(2/3) - 1) = 1.305 a
+    let half_mass_radius = distances(&nbody, centre)[num_particles / 2] / scale_radius;
+    assert!(
+        (half_mass_radius - 1.305).abs() < 0.05,
+        "half-mass radius {} a",
+        half_mass_radius
+    );
+
+    // The sample stops short of the last 0.1% of the mass, beyond 38.7 a
+    let outermost_radius = distances(&nbody, centre)[num_particles - 1] / scale_radius;
+    assert!(
+        outermost_radius < 39.0,
+        "outermost radius {} a",
+        outermost_radius
+    );
+
+    // Virial ratio K / |W| = 1/2, in the frame of the sphere
+    let (kinetic, potential) = energies(&nbody, GRAV_CONST, drift);
+    let virial_ratio = kinetic / -potential;
+    assert!(
+        (virial_ratio - 0.5).abs() < 0.03,
+        "virial ratio {}",
+        virial_ratio
+    );
+
+    // The potential energy of a Plummer sphere is -3 pi G M^2 / (32 a)
+    let expected = -3.0 * std::f64::consts::PI * GRAV_CONST as f64 * (total_mass as f64).powi(2)
+        / (32.0 * scale_radius as f64);
+    assert!(
+        (potential / expected - 1.0).abs() < 0.05,
+        "potential energy {} (expected {})",
+        potential,
+        expected
+    );
+}
+
+// Radii enclosing the given fractions of the mass, about the origin
+fn lagrangian_radii(nbody: &NBody, fractions: &[f32]) -> Vec<f32> {
+    let distances = distances(nbody, [0.0; 3]);
+    fractions
+        .iter()
+        .map(|f| distances[(f * distances.len() as f32) as usize])
+        .collect()
+}
+
+#[test]
+fn spherical_profiles_keep_their_lagrangian_radii() {
+    const NUM_PARTICLES: usize = 1000;
+    const NUM_DYNAMICAL_TIMES: f32 = 2.0;
+    const STEPS_PER_DYNAMICAL_TIME: usize = 50;
+    const FRACTIONS: [f32; 3] = [0.25, 0.5, 0.75];
+
+    type Generator = fn(&mut NBody, &mut StdRng);
+    let generators: [(&str, Generator); 3] = [
+        ("Hernquist", |nbody, rng| {
+            nbody.add_hernquist_sphere(rng, 1.0, [0.0; 3], [0.0; 3], 1.0, 1.0, NUM_PARTICLES)
+        }),
+        ("NFW", |nbody, rng| {
+            nbody.add_nfw_halo(rng, 1.0, [0.0; 3], [0.0; 3], 1.0, 10.0, 1.0, NUM_PARTICLES)
+        }),
+        ("King", |nbody, rng| {
+            nbody.add_king_cluster(rng, 1.0, [0.0; 3], [0.0; 3], 1.0, 5.0, 1.0, NUM_PARTICLES)
+        }),
+    ];
+
+    for (name, generate) in generators {
+        // The centre of mass stays at the origin
+        let mut nbody = NBody::new();
+        generate(&mut nbody, &mut StdRng::seed_from_u64(18));
+        let initial = lagrangian_radii(&nbody, &FRACTIONS);
+
+        // Dynamical time at the half-mass radius, with G = M = 1
+        let dynamical_time = initial[1].powf(1.5);
+        let mut simulation = CpuSimulation::new(
+            Settings {
+                gravitational_constant: 1.0,
+                time_step: dynamical_time / STEPS_PER_DYNAMICAL_TIME as f32,
+                smoothing_length: 0.05,
+                integrator: Integrator::Leapfrog,
+                ..Settings::default()
+            },
+            nbody,
+        );
+        for _ in 0..(NUM_DYNAMICAL_TIMES * STEPS_PER_DYNAMICAL_TIME as f32) as usize {
+            simulation.update();
+        }
+
+        let evolved = lagrangian_radii(simulation.state(), &FRACTIONS);
+        for ((fraction, before), after) in FRACTIONS.iter().zip(&initial).zip(&evolved) {
+            assert!(
+                (after / before - 1.0).abs() < 0.15,
+                "{}: {}% Lagrangian radius went from {} to {}",
+                name,
+                fraction * 100.0,
+                before,
+                after
+            );
+        }
+    }
+}
+
+#[test]
+fn exponential_disc_has_the_requested_toomre_q() {
+    let (centre, drift) = ([-3.0, 1.0, 4.0], [0.0, 2.0, 0.5]);
+    let (scale_length, scale_height, disc_mass, toomre_q) = (2.0, 0.2, 1.0, 1.5);
+    let (halo_mass, halo_radius) = (5.0, 6.0);
+    let num_particles = 40000;
+
+    // Hernquist halo mass within r
+    let halo = |r: f32| halo_mass * r * r / ((r + halo_radius) * (r + halo_radius));
+
+    let mut nbody = NBody::new();
+    nbody.add_exponential_disc(
+        &mut StdRng::seed_from_u64(19),
+        GRAV_CONST,
+        centre,
+        drift,
+        scale_length,
+        scale_height,
+        disc_mass,
+        toomre_q,
+        halo,
+        num_particles,
+    );
+
+    assert_eq!(nbody.num_massive_particles(), num_particles);
+    let mean_position = mean(nbody.massive_positions());
+    for k in 0..3 {
+        assert!((mean_position[k] - centre[k] as f64).abs() < 1.0e-3);
+    }
+
+    // Cylindrical coordinates about the centre, in the frame of the disc
+    let particles: Vec<([f32; 3], [f32; 3])> = nbody
+        .massive_positions()
+        .iter()
+        .zip(nbody.massive_velocities())
+        .map(|(p, v)| {
+            let [x, y, z] = [0, 1, 2].map(|k| p[k] - centre[k]);
+            let [vx, vy, vz] = [0, 1, 2].map(|k| v[k] - drift[k]);
+            let r = (x * x + y * y).sqrt();
+            let v_r = (x * vx + y * vy) / r;
+            let v_phi = (y * vx - x * vy) / r;
+            ([r, z, 0.0], [v_r, v_phi, vz])
+        })
+        .collect();
+
+    // The mean radius of an exponential disc is twice its scale length, and half of a sech^2 layer lies within
+    // atanh(1/2) = 0.549 scale heights of the midplane
+    let mean_radius =
+        particles.iter().map(|(p, _)| p[0] as f64).sum::<f64>() / num_particles as f64;
+    assert!(
+        (mean_radius / (2.0 * scale_length as f64) - 1.0).abs() < 0.02,
+        "mean radius {}",
+        mean_radius
+    );
+    let mut heights: Vec<f32> = particles.iter().map(|(p, _)| p[1].abs()).collect();
+    heights.sort_by(f32::total_cmp);
+    let median_height = heights[num_particles / 2] / scale_height;
+    assert!(
+        (median_height - 0.549).abs() < 0.02,
+        "median height {} z0",
+        median_height
+    );
+
+    // Dispersions in an annulus around two scale lengths
+    let r = 2.0 * scale_length;
+    let annulus: Vec<[f32; 3]> = particles
+        .iter()
+        .filter(|(p, _)| (p[0] / r - 1.0).abs() < 0.05)
+        .map(|(_, v)| *v)
+        .collect();
+    let means = mean(&annulus);
+    let dispersion = |k: usize| {
+        (annulus
+            .iter()
+            .map(|v| (v[k] as f64 - means[k]).powi(2))
+            .sum::<f64>()
+            / annulus.len() as f64)
+            .sqrt() as f32
+    };
+
+    let surface_density =
+        disc_mass / (2.0 * std::f32::consts::PI * scale_length.powi(2)) * (-r / scale_length).exp();
+    let disc_enclosed = disc_mass * (1.0 - (1.0 + r / scale_length) * (-r / scale_length).exp());
+    let disc_gradient = 2.0 * std::f32::consts::PI * r * surface_density;
+    let halo_gradient = 2.0 * halo_mass * halo_radius * r / (r + halo_radius).powi(3);
+    let circular_speed = (GRAV_CONST * (disc_enclosed + halo(r)) / r).sqrt();
+    let epicyclic_frequency =
+        (GRAV_CONST * (disc_enclosed + halo(r) + r * (disc_gradient + halo_gradient)) / r.powi(3))
+            .sqrt();
+
+    let measured_q = dispersion(0) * epicyclic_frequency / (3.36 * GRAV_CONST * surface_density);
+    assert!(
+        (measured_q / toomre_q - 1.0).abs() < 0.1,
+        "Toomre Q {}",
+        measured_q
+    );
+    let vertical = dispersion(2).powi(2)
+        / (std::f32::consts::PI * GRAV_CONST * surface_density * scale_height);
+    assert!(
+        (vertical - 1.0).abs() < 0.1,
+        "sigma_z^2 / (pi G Sigma z0) = {}",
+        vertical
+    );
+
+    // Rotating with the other discs, a little slower than the circular speed
+    let rotation = means[1] as f32 / circular_speed;
+    assert!(
+        rotation > 0.8 && rotation < 1.0,
+        "v_phi / v_c = {}",
+        rotation
+    );
+}
+
+#[test]
+#[should_panic(expected = "disc ghosts need a disc")]
+fn disc_ghosts_without_a_disc_panic() {
+    GalaxyBuilder::new([0.0; 3], [0.0; 3])
+        .bulge(0.5, 1.0, 100)
+        .disc_ghosts(100, 1.0)
+        .add_to(&mut StdRng::seed_from_u64(1), GRAV_CONST, &mut NBody::new());
+}
+
+#[test]
+fn galaxy_components_are_in_equilibrium_together() {
+    let (centre, velocity) = ([100.0, -50.0, 20.0], [1.0, 2.0, -0.5]);
+    let spin_axis = [1.0, 0.0, 1.0];
+    let (bulge_radius, bulge_mass, num_bulge) = (0.5, 0.5, 4000);
+    let (scale_length, disc_mass, num_disc) = (2.0, 1.0, 4000);
+    let (halo_radius, halo_mass, num_halo) = (5.0, 6.0, 8000);
+    let num_ghosts = 1000;
+
+    let builder = GalaxyBuilder::new(centre, velocity)
+        .spin_axis(spin_axis)
+        .bulge(bulge_radius, bulge_mass, num_bulge)
+        .disc(scale_length, 0.2, disc_mass, 1.5, num_disc)
+        .hernquist_halo(halo_radius, halo_mass, num_halo)
+        .disc_ghosts(num_ghosts, 1.0);
+    let mut nbody = NBody::new();
+    builder.add_to(&mut StdRng::seed_from_u64(20), GRAV_CONST, &mut nbody);
+
+    let num_particles = num_bulge + num_disc + num_halo;
+    assert_eq!(nbody.num_massive_particles(), num_particles);
+    assert_eq!(nbody.num_ghost_particles(), num_ghosts);
+    assert!(nbody.ghost_kinds().iter().all(|kind| *kind == 1.0));
+    let mass = nbody.massive_masses().iter().sum::<f32>();
+    assert!(
+        (mass - builder.total_mass()).abs() < 1.0e-3,
+        "total mass {}",
+        mass
+    );
+
+    // Positions and velocities relative to the galaxy, which moves as a whole with its velocity
+    let relative = |vectors: &[[f32; 3]], offset: [f32; 3]| -> Vec<[f64; 3]> {
+        vectors
+            .iter()
+            .map(|v| [0, 1, 2].map(|k| v[k] as f64 - offset[k] as f64))
+            .collect()
+    };
+    let positions = relative(nbody.massive_positions(), centre);
+    let velocities = relative(nbody.massive_velocities(), velocity);
+    let masses = nbody.massive_masses();
+    for k in 0..3 {
+        let momentum = velocities
+            .iter()
+            .zip(masses)
+            .map(|(v, m)| v[k] * *m as f64)
+            .sum::<f64>();
+        assert!(momentum.abs() < 1.0e-3, "momentum {}", momentum);
+    }
+
+    // The disc and its ghosts spin about the spin axis, in its plane
+    let axis = [spin_axis[0] as f64, 0.0, spin_axis[2] as f64].map(|x| x / 2.0f64.sqrt());
+    let dot = |a: [f64; 3], b: [f64; 3]| (0..3).map(|k| a[k] * b[k]).sum::<f64>();
+    let disc = num_bulge..num_bulge + num_disc;
+    let mut momentum = [0.0; 3];
+    for n in disc.clone() {
+        let ([x, y, z], [vx, vy, vz]) = (positions[n], velocities[n]);
+        let l = [y * vz - z * vy, z * vx - x * vz, x * vy - y * vx];
+        (0..3).for_each(|k| momentum[k] += l[k]);
+    }
+    let alignment = dot(momentum, axis) / dot(momentum, momentum).sqrt();
+    assert!(alignment > 0.99, "disc spin alignment {}", alignment);
+
+    let ghosts = relative(nbody.ghost_positions(), centre);
+    let height = ghosts.iter().map(|p| dot(*p, axis).abs()).sum::<f64>() / num_ghosts as f64;
+    let radius = ghosts
+        .iter()
+        .map(|p| (dot(*p, *p) - dot(*p, axis).powi(2)).sqrt())
+        .sum::<f64>()
+        / num_ghosts as f64;
+    assert!(
+        height < 0.05 * radius,
+        "ghost height {} at radius {}",
+        height,
+        radius
+    );
+
+    // Each component obeys the virial theorem 2 K = sum of m G M(r) / r in the (spherically averaged) field of the
+    // whole galaxy, which a bulge sampled on its own would miss by more than half
+    let enclosed_mass = |r: f64| {
+        let (a, h) = (bulge_radius as f64, scale_length as f64);
+        bulge_mass as f64 * (r / (r + a)).powi(2)
+            + disc_mass as f64 * (1.0 - (1.0 + r / h) * (-r / h).exp())
+            + halo_mass as f64 * (r / (r + halo_radius as f64)).powi(2)
+    };
+    for (name, range) in [
+        ("bulge", 0..num_bulge),
+        ("disc", disc),
+        ("halo", num_bulge + num_disc..num_particles),
+    ] {
+        let kinetic = range
+            .clone()
+            .map(|n| masses[n] as f64 * dot(velocities[n], velocities[n]))
+            .sum::<f64>();
+        let virial = range
+            .map(|n| {
+                let r = dot(positions[n], positions[n]).sqrt();
+                masses[n] as f64 * GRAV_CONST as f64 * enclosed_mass(r) / r
+            })
+            .sum::<f64>();
+        let ratio = kinetic / virial;
+        assert!(
+            (ratio - 1.0).abs() < 0.05,
+            "{}: 2K / virial = {}",
+            name,
+            ratio
+        );
+    }
+}
+
+#[test]
+fn discs_take_their_orientation() {
+    let (centre, drift) = ([5.0, -2.0, 1.0], [0.0, 1.0, -1.0]);
+    let half_angle = std::f32::consts::FRAC_PI_4.sin_cos();
+    let orientations = [
+        Orientation::default(),
+        Orientation::from_spin_axis([0.0, 0.0, 1.0], Spin::Anticlockwise),
+        Orientation::from_spin_axis([1.0, -2.0, 0.5], Spin::Clockwise),
+        Orientation::from_sky(0.5, 2.0, Spin::Anticlockwise),
+        // A quarter turn about x
+        Orientation::from_quaternion([half_angle.1, half_angle.0, 0.0, 0.0], Spin::Clockwise),
+    ];
+
+    // The default is the xy plane turning clockwise, as the generators always built their discs
+    assert_eq!(orientations[0].angular_momentum_axis(), [0.0, 0.0, -1.0]);
+    assert_eq!(orientations[1].angular_momentum_axis(), [0.0, 0.0, 1.0]);
+    let normal = orientations[4].normal();
+    assert!(
+        normal[0].abs() < 1.0e-6 && (normal[1] + 1.0).abs() < 1.0e-6 && normal[2].abs() < 1.0e-6
+    );
+    // Edge-on with the line of nodes running north (+y) to south
+    let normal = Orientation::from_sky(std::f32::consts::FRAC_PI_2, 0.0, Spin::Clockwise).normal();
+    assert!(
+        (normal[0] - 1.0).abs() < 1.0e-6 && normal[1].abs() < 1.0e-6 && normal[2].abs() < 1.0e-6
+    );
+
+    let mut rng = StdRng::seed_from_u64(23);
+    for orientation in orientations {
+        let mut nbody = NBody::new();
+        nbody.add_massive_disc_oriented(
+            &mut rng,
+            GRAV_CONST,
+            centre,
+            drift,
+            orientation,
+            3.0,
+            1.0,
+            200,
+        );
+        nbody.add_massive_system_oriented(
+            &mut rng,
+            GRAV_CONST,
+            centre,
+            drift,
+            orientation,
+            3.0,
+            1.0,
+            0.1,
+            200,
+        );
+        nbody.add_massive_system2_oriented(
+            &mut rng,
+            GRAV_CONST,
+            centre,
+            drift,
+            orientation,
+            3.0,
+            1.0,
+            200,
+        );
+        nbody.add_exponential_disc_oriented(
+            &mut rng,
+            GRAV_CONST,
+            centre,
+            drift,
+            orientation,
+            1.0,
+            0.05,
+            1.0,
+            1.5,
+            |_| 0.0,
+            1000,
+        );
+        nbody.add_ghost_field_oriented(&mut rng, centre, drift, orientation, 3.0, 1.0, 200, 1.0);
+
+        let normal = orientation.normal().map(|x| x as f64);
+        let axis = orientation.angular_momentum_axis().map(|x| x as f64);
+        let dot = |a: [f64; 3], b: [f64; 3]| (0..3).map(|k| a[k] * b[k]).sum::<f64>();
+        let relative = |vector: &[f32; 3], offset: [f32; 3]| {
+            [0, 1, 2].map(|k| vector[k] as f64 - offset[k] as f64)
+        };
+
+        // Each generator's particles in the disc's plane, turning about its axis
+        let generators = [0..200, 200..401, 401..601, 601..1601];
+        let massive =
+            generators.map(|range| (range, nbody.massive_positions(), nbody.massive_velocities()));
+        let ghosts = [(0..200, nbody.ghost_positions(), nbody.ghost_velocities())];
+        for (range, positions, velocities) in massive.into_iter().chain(ghosts) {
+            let mut momentum = [0.0; 3];
+            for n in range.clone() {
+                let (r, v) = (
+                    relative(&positions[n], centre),
+                    relative(&velocities[n], drift),
+                );
+                // Within the sech^2 profile of the exponential disc
+                assert!(dot(r, normal).abs() < 0.5, "height {}", dot(r, normal));
+                let l = [
+                    r[1] * v[2] - r[2] * v[1],
+                    r[2] * v[0] - r[0] * v[2],
+                    r[0] * v[1] - r[1] * v[0],
+                ];
+                (0..3).for_each(|k| momentum[k] += l[k]);
+            }
+            let alignment = dot(momentum, axis) / dot(momentum, momentum).sqrt();
+            assert!(alignment > 0.99, "{:?}: alignment {}", range, alignment);
+        }
+    }
+}
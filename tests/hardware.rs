@@ -0,0 +1,36 @@
+mod common;
+
+use common::camera;
+use heavens::{Backend, HardwareError, NBody, Settings, Simulation};
+
+#[test]
+fn backend_names_parse() {
+    assert_eq!("any".parse(), Ok(Backend::Any));
+    assert_eq!("Vulkan".parse(), Ok(Backend::Vulkan));
+    assert_eq!("metal".parse(), Ok(Backend::Metal));
+    assert_eq!("DX12".parse(), Ok(Backend::Dx12));
+    assert_eq!("gl".parse(), Ok(Backend::Gl));
+    assert!("opengl".parse::<Backend>().is_err());
+}
+
+// Metal is only available on Apple platforms, so adapter discovery has to fail gracefully
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+#[test]
+fn missing_backend_is_an_error() {
+    let result = pollster::block_on(Simulation::new_headless(
+        Settings {
+            backend: Backend::Metal,
+            ..common::settings()
+        },
+        camera(),
+        NBody::new(),
+    ));
+
+    assert!(matches!(
+        result,
+        Err(HardwareError::NoAdapter {
+            backend: Backend::Metal,
+            ..
+        })
+    ));
+}
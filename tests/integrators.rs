@@ -0,0 +1,195 @@
+mod common;
+
+use common::camera;
+use heavens::{Integrator, NBody, Settings, Simulation, Solver};
+
+const GRAV_CONST: f32 = 1.0;
+const SMOOTHING_LENGTH: f32 = 1.0e-3;
+const BINARY_MASS: f32 = 0.5; // Per body
+const SEMI_MAJOR_AXIS: f32 = 1.0;
+const ECCENTRICITY: f32 = 0.5;
+const STEPS_PER_ORBIT: usize = 1000;
+const NUM_ORBITS: usize = 2;
+const SAMPLE_INTERVAL: usize = 50;
+
+fn settings(integrator: Integrator) -> Settings {
+    let total_mass = 2.0 * BINARY_MASS;
+    let period =
+        2.0 * std::f32::consts::PI * (SEMI_MAJOR_AXIS.powi(3) / (GRAV_CONST * total_mass)).sqrt();
+
+    Settings {
+        gravitational_constant: GRAV_CONST,
+        time_step: period / STEPS_PER_ORBIT as f32,
+        smoothing_length: SMOOTHING_LENGTH,
+        integrator,
+        ..common::settings()
+    }
+}
+
+// Equal-mass binary released from apocentre
+fn kepler_binary() -> NBody {
+    let total_mass = 2.0 * BINARY_MASS;
+    let apocentre = SEMI_MAJOR_AXIS * (1.0 + ECCENTRICITY);
+    let speed = (GRAV_CONST * total_mass * (1.0 - ECCENTRICITY) / apocentre).sqrt();
+
+    let mut nbody = NBody::new();
+    nbody.add_massive_particle(
+        [0.5 * apocentre, 0.0, 0.0],
+        [0.0, 0.5 * speed, 0.0],
+        BINARY_MASS,
+    );
+    nbody.add_massive_particle(
+        [-0.5 * apocentre, 0.0, 0.0],
+        [0.0, -0.5 * speed, 0.0],
+        BINARY_MASS,
+    );
+
+    nbody
+}
+
+// Softened energy and z angular momentum of the binary
+fn binary_invariants(nbody: &NBody) -> (f32, f32) {
+    let [p0, p1] = [nbody.massive_positions()[0], nbody.massive_positions()[1]];
+    let [v0, v1] = [nbody.massive_velocities()[0], nbody.massive_velocities()[1]];
+
+    let kinetic = 0.5
+        * BINARY_MASS
+        * (v0.iter().map(|v| v * v).sum::<f32>() + v1.iter().map(|v| v * v).sum::<f32>());
+    let r2 = (0..3).map(|i| (p0[i] - p1[i]).powi(2)).sum::<f32>();
+    let potential =
+        -GRAV_CONST * BINARY_MASS * BINARY_MASS / (r2 + SMOOTHING_LENGTH * SMOOTHING_LENGTH).sqrt();
+    let angular_momentum =
+        BINARY_MASS * ((p0[0] * v0[1] - p0[1] * v0[0]) + (p1[0] * v1[1] - p1[1] * v1[0]));
+
+    (kinetic + potential, angular_momentum)
+}
+
+// Largest relative error in energy and angular momentum over `NUM_ORBITS` orbits
+fn conservation_errors(integrator: Integrator) -> (f32, f32) {
+    let initial_conditions = kepler_binary();
+    let (initial_energy, initial_angular_momentum) = binary_invariants(&initial_conditions);
+
+    let mut simulation = pollster::block_on(Simulation::new_headless(
+        settings(integrator),
+        camera(),
+        initial_conditions,
+    ))
+    .unwrap();
+
+    let mut max_energy_error: f32 = 0.0;
+    let mut max_angular_momentum_error: f32 = 0.0;
+    for _ in 0..(STEPS_PER_ORBIT * NUM_ORBITS / SAMPLE_INTERVAL) {
+        for _ in 0..SAMPLE_INTERVAL {
+            simulation.update();
+        }
+
+        let (energy, angular_momentum) = binary_invariants(&simulation.read_state());
+        max_energy_error = max_energy_error.max(((energy - initial_energy) / initial_energy).abs());
+        max_angular_momentum_error = max_angular_momentum_error
+            .max(((angular_momentum - initial_angular_momentum) / initial_angular_momentum).abs());
+    }
+
+    (max_energy_error, max_angular_momentum_error)
+}
+
+#[test]
+fn leapfrog_conserves_kepler_orbit() {
+    let (energy_error, angular_momentum_error) = conservation_errors(Integrator::Leapfrog);
+    assert!(energy_error < 5.0e-4, "energy error {}", energy_error);
+    assert!(
+        angular_momentum_error < 1.0e-4,
+        "angular momentum error {}",
+        angular_momentum_error
+    );
+}
+
+#[test]
+fn velocity_verlet_conserves_kepler_orbit() {
+    let (energy_error, angular_momentum_error) = conservation_errors(Integrator::VelocityVerlet);
+    assert!(energy_error < 5.0e-4, "energy error {}", energy_error);
+    assert!(
+        angular_momentum_error < 1.0e-4,
+        "angular momentum error {}",
+        angular_momentum_error
+    );
+}
+
+#[test]
+fn hermite_conserves_kepler_orbit() {
+    let (energy_error, angular_momentum_error) = conservation_errors(Integrator::Hermite);
+    assert!(energy_error < 2.0e-5, "energy error {}", energy_error);
+    assert!(
+        angular_momentum_error < 1.0e-4,
+        "angular momentum error {}",
+        angular_momentum_error
+    );
+}
+
+#[test]
+fn yoshida_conserves_kepler_orbit() {
+    let (energy_error, angular_momentum_error) = conservation_errors(Integrator::Yoshida);
+    assert!(energy_error < 5.0e-5, "energy error {}", energy_error);
+    assert!(
+        angular_momentum_error < 1.0e-4,
+        "angular momentum error {}",
+        angular_momentum_error
+    );
+}
+
+#[test]
+fn euler_stays_near_kepler_orbit() {
+    let (energy_error, angular_momentum_error) = conservation_errors(Integrator::Euler);
+    assert!(energy_error < 5.0e-2, "energy error {}", energy_error);
+    assert!(
+        angular_momentum_error < 1.0e-4,
+        "angular momentum error {}",
+        angular_momentum_error
+    );
+}
+
+#[test]
+fn changing_settings_recomputes_cached_forces() {
+    let new_simulation = |settings, initial_conditions| {
+        pollster::block_on(Simulation::new_headless(
+            settings,
+            camera(),
+            initial_conditions,
+        ))
+        .unwrap()
+    };
+    let mut doubled = settings(Integrator::Leapfrog);
+    doubled.gravitational_constant *= 2.0;
+
+    // A step under G, then one under 2G
+    let (midway, changed) = {
+        let mut simulation = new_simulation(settings(Integrator::Leapfrog), kepler_binary());
+        simulation.update();
+        let midway = simulation.read_state();
+        simulation.settings.gravitational_constant = doubled.gravitational_constant;
+        simulation.update();
+        (midway, simulation.read_state())
+    };
+
+    // The opening half kick of the second step must not reuse the forces computed under G
+    let mut simulation = new_simulation(doubled, midway);
+    simulation.update();
+    let expected = simulation.read_state();
+    assert_eq!(changed.massive_positions(), expected.massive_positions());
+    assert_eq!(changed.massive_velocities(), expected.massive_velocities());
+}
+
+#[test]
+fn hermite_requires_an_exact_solver() {
+    for (solver, valid) in [
+        (Solver::Direct, true),
+        (Solver::TiledDirect, true),
+        (Solver::Tree, false),
+        (Solver::ParticleMesh, false),
+    ] {
+        let settings = Settings {
+            solver,
+            ..settings(Integrator::Hermite)
+        };
+        assert_eq!(settings.is_valid(), valid, "{:?}", solver);
+    }
+}
@@ -0,0 +1,161 @@
+use std::f32::consts::{PI, TAU};
+
+use heavens::{NBody, OrbitalElements, PlanetarySystemBuilder};
+
+const GRAV_CONST: f32 = 2.0;
+
+fn elements(
+    semi_major_axis: f32,
+    eccentricity: f32,
+    inclination: f32,
+    longitude_of_ascending_node: f32,
+    argument_of_periapsis: f32,
+    mean_anomaly: f32,
+) -> OrbitalElements {
+    OrbitalElements {
+        semi_major_axis,
+        eccentricity,
+        inclination,
+        longitude_of_ascending_node,
+        argument_of_periapsis,
+        mean_anomaly,
+    }
+}
+
+fn assert_same_orbit(found: OrbitalElements, expected: OrbitalElements) {
+    // Angles compared around the circle
+    let angle = |a: f32, b: f32| ((a - b + PI).rem_euclid(TAU) - PI).abs() < 1.0e-3;
+    let same = (found.semi_major_axis / expected.semi_major_axis - 1.0).abs() < 1.0e-4
+        && (found.eccentricity - expected.eccentricity).abs() < 1.0e-4
+        && angle(found.inclination, expected.inclination)
+        && angle(
+            found.longitude_of_ascending_node,
+            expected.longitude_of_ascending_node,
+        )
+        && angle(found.argument_of_periapsis, expected.argument_of_periapsis)
+        && angle(found.mean_anomaly, expected.mean_anomaly);
+    assert!(same, "found {:?} but expected {:?}", found, expected);
+}
+
+#[test]
+fn elements_survive_a_round_trip_through_state_vectors() {
+    let mu = 3.0;
+    for expected in [
+        elements(1.0, 0.0167, 0.1, 2.0, 1.5, 0.3),
+        elements(5.2, 0.3, 1.2, 4.0, 5.5, 3.0),
+        elements(0.4, 0.95, 0.5, 0.5, 3.5, 6.0),
+        // Retrograde, like Halley's comet
+        elements(17.8, 0.967, 2.83, 1.03, 1.95, 0.1),
+        // Hyperbolic, outbound and inbound
+        elements(-2.0, 1.5, 0.7, 1.0, 2.0, 1.2),
+        elements(-0.5, 3.0, 2.0, 5.0, 0.5, -4.0),
+    ] {
+        let (position, velocity) = expected.to_state(mu).unwrap();
+        assert_same_orbit(
+            OrbitalElements::from_state(mu, position, velocity),
+            expected,
+        );
+    }
+
+    // The Earth about the Sun in SI units
+    let (sun, earth) = (1.989e30, 5.972e24);
+    let mu = 6.674e-11 * (sun + earth);
+    let expected = elements(1.496e11, 0.0167, 0.0, 0.0, 1.796, 6.24);
+    let (position, velocity) = expected.to_state(mu).unwrap();
+    let distance = position.iter().map(|x| x * x).sum::<f32>().sqrt();
+    assert!((distance / 1.496e11 - 1.0).abs() < 0.02);
+    let speed = velocity.iter().map(|v| v * v).sum::<f32>().sqrt();
+    assert!((speed / 2.98e4 - 1.0).abs() < 0.02, "speed {}", speed);
+    assert_same_orbit(
+        OrbitalElements::from_state(mu, position, velocity),
+        expected,
+    );
+}
+
+#[test]
+fn degenerate_orbits_follow_the_conventions() {
+    // A circular orbit in the reference plane at a true longitude of 1 radian
+    let circular = elements(2.0, 0.0, 0.0, 0.0, 0.0, 1.0);
+    let (position, velocity) = circular.to_state(1.0).unwrap();
+    assert!((position[1].atan2(position[0]) - 1.0).abs() < 1.0e-6);
+    assert!(position[2].abs() < 1.0e-6 && velocity[2].abs() < 1.0e-6);
+
+    let found = OrbitalElements::from_state(1.0, position, velocity);
+    assert!(found.eccentricity < 1.0e-6);
+    assert_eq!(found.longitude_of_ascending_node, 0.0);
+    assert_eq!(found.argument_of_periapsis, 0.0);
+    assert!((found.mean_anomaly - 1.0).abs() < 1.0e-5);
+}
+
+#[test]
+fn planetary_system_is_barycentric() {
+    let (centre, drift) = ([10.0, 20.0, -30.0], [1.0, 0.0, -2.0]);
+    let central_mass = 1.0;
+    let bodies = [
+        (elements(1.0, 0.05, 0.02, 0.3, 1.0, 0.0), 3.0e-6),
+        (elements(5.2, 0.048, 0.023, 1.75, 4.78, 0.35), 9.5e-4),
+        (elements(9.5, 0.056, 0.043, 1.98, 5.92, 5.5), 2.9e-4),
+    ];
+
+    let mut builder = PlanetarySystemBuilder::new(central_mass);
+    for (elements, mass) in bodies {
+        builder = builder.body(elements, mass);
+    }
+    let mut nbody = NBody::new();
+    builder
+        .add_to(GRAV_CONST, centre, drift, &mut nbody)
+        .unwrap();
+
+    assert_eq!(nbody.num_massive_particles(), 1 + bodies.len());
+    assert_eq!(nbody.massive_masses()[0], central_mass);
+
+    let positions = nbody.massive_positions();
+    let velocities = nbody.massive_velocities();
+    let masses = nbody.massive_masses();
+    let total_mass = masses.iter().sum::<f32>();
+    for k in 0..3 {
+        let barycentre = (0..positions.len())
+            .map(|n| masses[n] as f64 * positions[n][k] as f64)
+            .sum::<f64>()
+            / total_mass as f64;
+        let velocity = (0..velocities.len())
+            .map(|n| masses[n] as f64 * velocities[n][k] as f64)
+            .sum::<f64>()
+            / total_mass as f64;
+        assert!((barycentre - centre[k] as f64).abs() < 1.0e-5);
+        assert!((velocity - drift[k] as f64).abs() < 1.0e-6);
+    }
+
+    // Each body's elements come back from its state relative to the central body
+    for (n, (expected, mass)) in bodies.into_iter().enumerate() {
+        let relative = |vectors: &[[f32; 3]]| [0, 1, 2].map(|k| vectors[n + 1][k] - vectors[0][k]);
+        let found = OrbitalElements::from_state(
+            GRAV_CONST * (central_mass + mass),
+            relative(positions),
+            relative(velocities),
+        );
+        assert_same_orbit(found, expected);
+    }
+}
+
+#[test]
+fn malformed_elements_are_rejected() {
+    for malformed in [
+        // Parabolic
+        elements(1.0, 1.0, 0.0, 0.0, 0.0, 0.0),
+        // Bound but with a negative semi-major axis, and unbound with a positive one
+        elements(-1.0, 0.5, 0.0, 0.0, 0.0, 0.0),
+        elements(1.0, 1.5, 0.0, 0.0, 0.0, 0.0),
+        elements(1.0, -0.1, 0.0, 0.0, 0.0, 0.0),
+        elements(1.0, 0.1, f32::NAN, 0.0, 0.0, 0.0),
+    ] {
+        assert!(malformed.to_state(1.0).is_err(), "{:?}", malformed);
+
+        let mut nbody = NBody::new();
+        let added = PlanetarySystemBuilder::new(1.0)
+            .body(malformed, 1.0e-3)
+            .add_to(GRAV_CONST, [0.0; 3], [0.0; 3], &mut nbody);
+        assert!(added.is_err(), "{:?}", malformed);
+        assert_eq!(nbody.num_massive_particles(), 0);
+    }
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use common::{camera, direct_acceleration, random_bodies, relative_error};
+use heavens::{Integrator, NBody, Settings, Simulation, Solver};
+
+const SOLVERS: [Solver; 4] = [
+    Solver::Direct,
+    Solver::TiledDirect,
+    Solver::Tree,
+    Solver::ParticleMesh,
+];
+const INTEGRATORS: [Integrator; 5] = [
+    Integrator::Euler,
+    Integrator::Leapfrog,
+    Integrator::VelocityVerlet,
+    Integrator::Hermite,
+    Integrator::Yoshida,
+];
+
+fn settings(integrator: Integrator, solver: Solver) -> Settings {
+    Settings {
+        opening_angle: 0.0, // Exact tree walk
+        integrator,
+        solver,
+        ..common::settings()
+    }
+}
+
+fn run(integrator: Integrator, solver: Solver, initial_conditions: NBody, steps: usize) -> NBody {
+    let mut simulation = pollster::block_on(Simulation::new_headless(
+        settings(integrator, solver),
+        camera(),
+        initial_conditions,
+    ))
+    .unwrap();
+    for _ in 0..steps {
+        simulation.update();
+    }
+
+    simulation.read_state()
+}
+
+#[test]
+fn odd_counts_match_host_sum() {
+    let initial_conditions = random_bodies(5, 101, 37, 0.0);
+
+    for solver in [Solver::Direct, Solver::TiledDirect, Solver::Tree] {
+        // Accelerations recovered from a single unit Euler kick from rest
+        let settings = Settings {
+            time_step: 1.0,
+            ..settings(Integrator::Euler, solver)
+        };
+        let mut simulation = pollster::block_on(Simulation::new_headless(
+            settings,
+            camera(),
+            random_bodies(5, 101, 37, 0.0),
+        ))
+        .unwrap();
+        simulation.update();
+        let state = simulation.read_state();
+
+        assert_eq!(state.num_massive_particles(), 101);
+        assert_eq!(state.num_ghost_particles(), 37);
+
+        let max_error = (0..101)
+            .map(|n| {
+                let expected = direct_acceleration(
+                    &settings,
+                    &initial_conditions,
+                    initial_conditions.massive_positions()[n],
+                    |m| m != n,
+                );
+                relative_error(expected, state.massive_velocities()[n].map(f64::from))
+            })
+            .fold(0.0, f64::max);
+        assert!(
+            max_error < 1.0e-4,
+            "{:?}: max relative error {}",
+            solver,
+            max_error
+        );
+    }
+}
+
+#[test]
+fn massive_bodies_run_without_ghosts() {
+    for solver in SOLVERS {
+        for integrator in INTEGRATORS {
+            // Hermite is rejected with the approximate solvers
+            if !settings(integrator, solver).is_valid() {
+                continue;
+            }
+            let state = run(integrator, solver, random_bodies(5, 77, 0, 0.0), 3);
+
+            assert_eq!(state.num_massive_particles(), 77);
+            assert_eq!(state.num_ghost_particles(), 0);
+            assert!(
+                state
+                    .massive_positions()
+                    .iter()
+                    .chain(state.massive_velocities())
+                    .flatten()
+                    .all(|x| x.is_finite()),
+                "{:?} {:?}: non-finite state",
+                solver,
+                integrator
+            );
+        }
+    }
+}
+
+#[test]
+fn ghosts_stay_at_rest_without_massive_bodies() {
+    let initial_conditions = random_bodies(5, 0, 45, 0.0);
+
+    for solver in SOLVERS {
+        for integrator in INTEGRATORS {
+            // Hermite is rejected with the approximate solvers
+            if !settings(integrator, solver).is_valid() {
+                continue;
+            }
+            let state = run(integrator, solver, random_bodies(5, 0, 45, 0.0), 3);
+
+            assert_eq!(state.num_massive_particles(), 0);
+            assert_eq!(
+                state.ghost_positions(),
+                initial_conditions.ghost_positions()
+            );
+            assert!(
+                state.ghost_velocities().iter().flatten().all(|v| *v == 0.0),
+                "{:?} {:?}: ghosts accelerated",
+                solver,
+                integrator
+            );
+        }
+    }
+}
+
+#[test]
+fn empty_simulation_runs() {
+    for solver in SOLVERS {
+        let state = run(Integrator::Leapfrog, solver, NBody::new(), 3);
+
+        assert_eq!(state.num_massive_particles(), 0);
+        assert_eq!(state.num_ghost_particles(), 0);
+    }
+}
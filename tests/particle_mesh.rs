@@ -0,0 +1,206 @@
+mod common;
+
+use common::{camera, direct_acceleration, relative_error};
+use heavens::{Integrator, MassAssignment, MeshBoundary, NBody, Settings, Simulation, Solver};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const GROUP_SIZE: usize = 32;
+const GROUP_SEPARATION: f32 = 8.0;
+const NUM_GHOSTS: usize = 128;
+
+fn settings(mass_assignment: MassAssignment) -> Settings {
+    Settings {
+        time_step: 1.0,
+        integrator: Integrator::Euler,
+        solver: Solver::ParticleMesh,
+        mesh_size: 32,
+        mass_assignment,
+        ..common::settings()
+    }
+}
+
+fn random_unit_vector(rng: &mut StdRng) -> [f32; 3] {
+    loop {
+        let p: [f32; 3] = [
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        ];
+        let r = p.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if r > 0.1 && r <= 1.0 {
+            return p.map(|x| x / r);
+        }
+    }
+}
+
+// Two compact groups of bodies at rest, surrounded by a shell of ghosts
+fn separated_groups() -> NBody {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut nbody = NBody::new();
+
+    for centre in [-0.5 * GROUP_SEPARATION, 0.5 * GROUP_SEPARATION] {
+        for _ in 0..GROUP_SIZE {
+            let direction = random_unit_vector(&mut rng);
+            let radius = rng.gen_range(0.0..0.3);
+            nbody.add_massive_particle(
+                [
+                    centre + radius * direction[0],
+                    radius * direction[1],
+                    radius * direction[2],
+                ],
+                [0.0; 3],
+                rng.gen_range(0.5..1.5),
+            );
+        }
+    }
+    for _ in 0..NUM_GHOSTS {
+        let direction = random_unit_vector(&mut rng);
+        let radius = rng.gen_range(7.0..9.0);
+        nbody.add_ghost_particle(direction.map(|x| radius * x), [0.0; 3], 1.0);
+    }
+
+    nbody
+}
+
+// Mesh accelerations of the massive bodies and ghosts from a unit Euler kick from rest
+fn mesh_accelerations(
+    settings: Settings,
+    initial_conditions: NBody,
+) -> (Vec<[f32; 3]>, Vec<[f32; 3]>) {
+    let mut simulation = pollster::block_on(Simulation::new_headless(
+        settings,
+        camera(),
+        initial_conditions,
+    ))
+    .unwrap();
+    simulation.update();
+    let state = simulation.read_state();
+    (
+        state.massive_velocities().to_vec(),
+        state.ghost_velocities().to_vec(),
+    )
+}
+
+// Net mass-weighted acceleration of each group
+fn group_forces(initial_conditions: &NBody, accelerations: &[[f32; 3]]) -> [[f64; 3]; 2] {
+    [0..GROUP_SIZE, GROUP_SIZE..2 * GROUP_SIZE].map(|group| {
+        let mut force = [0.0f64; 3];
+        for n in group {
+            let mass = initial_conditions.massive_masses()[n] as f64;
+            for i in 0..3 {
+                force[i] += mass * accelerations[n][i] as f64;
+            }
+        }
+        force
+    })
+}
+
+// Largest relative error of the mesh forces on the ghosts, and of the net force between the groups
+fn mesh_errors(settings: Settings, initial_conditions: NBody) -> (f64, f64) {
+    let (massive_accelerations, ghost_accelerations) =
+        mesh_accelerations(settings, initial_conditions.clone());
+
+    let ghost_error = initial_conditions
+        .ghost_positions()
+        .iter()
+        .zip(&ghost_accelerations)
+        .map(|(position, acceleration)| {
+            relative_error(
+                direct_acceleration(&settings, &initial_conditions, *position, |_| true),
+                acceleration.map(|a| a as f64),
+            )
+        })
+        .fold(0.0, f64::max);
+
+    // Forces within each group are unresolved by the mesh but cancel in the group's net force
+    let actual = group_forces(&initial_conditions, &massive_accelerations);
+    let group_error = [0..GROUP_SIZE, GROUP_SIZE..2 * GROUP_SIZE]
+        .into_iter()
+        .zip(actual)
+        .map(|(group, actual)| {
+            let mut expected = [0.0f64; 3];
+            for n in group.clone() {
+                let mass = initial_conditions.massive_masses()[n] as f64;
+                let direct = direct_acceleration(
+                    &settings,
+                    &initial_conditions,
+                    initial_conditions.massive_positions()[n],
+                    |m| !group.contains(&m),
+                );
+                for i in 0..3 {
+                    expected[i] += mass * direct[i];
+                }
+            }
+            relative_error(expected, actual)
+        })
+        .fold(0.0, f64::max);
+
+    (ghost_error, group_error)
+}
+
+#[test]
+fn ngp_mesh_matches_direct_sum_at_large_separations() {
+    let (ghost_error, group_error) = mesh_errors(settings(MassAssignment::Ngp), separated_groups());
+    // NGP puts each group's mass on a few cells and reads the force from one cell, so a ghost's force jumps as it
+    // crosses a cell boundary: at a few cells from a group that is tens of percent of the force
+    assert!(ghost_error < 3.0e-1, "ghost force error {}", ghost_error);
+    assert!(group_error < 1.0e-1, "group force error {}", group_error);
+}
+
+#[test]
+fn cic_mesh_matches_direct_sum_at_large_separations() {
+    let (ghost_error, group_error) = mesh_errors(settings(MassAssignment::Cic), separated_groups());
+    assert!(ghost_error < 5.0e-2, "ghost force error {}", ghost_error);
+    assert!(group_error < 1.0e-2, "group force error {}", group_error);
+}
+
+#[test]
+fn tsc_mesh_matches_direct_sum_at_large_separations() {
+    let (ghost_error, group_error) = mesh_errors(settings(MassAssignment::Tsc), separated_groups());
+    assert!(ghost_error < 1.0e-2, "ghost force error {}", ghost_error);
+    assert!(group_error < 1.0e-3, "group force error {}", group_error);
+}
+
+#[test]
+fn fixed_mesh_box_ignores_an_escaping_body() {
+    // Fitted to every particle, the mesh would stretch to the escaper and lose the groups entirely
+    let mut initial_conditions = separated_groups();
+    initial_conditions.add_massive_particle([1.0e4, 0.0, 0.0], [0.0; 3], 1.0e-6);
+    let settings = Settings {
+        mesh_box_size: 20.0,
+        ..settings(MassAssignment::Tsc)
+    };
+
+    let (ghost_error, group_error) = mesh_errors(settings, initial_conditions);
+    assert!(ghost_error < 1.0e-2, "ghost force error {}", ghost_error);
+    assert!(group_error < 1.0e-3, "group force error {}", group_error);
+}
+
+#[test]
+fn periodic_mesh_balances_groups_half_a_box_apart() {
+    // Each group is a centre of symmetry of the periodic images, so the pull between the groups cancels
+    // (up to the groups' own asymmetry and the mesh discreteness)
+    let isolated_settings = settings(MassAssignment::Tsc);
+    let periodic_settings = Settings {
+        mesh_boundary: MeshBoundary::Periodic,
+        mesh_box_size: 2.0 * GROUP_SEPARATION,
+        ..isolated_settings
+    };
+    let initial_conditions = separated_groups();
+    let (isolated_accelerations, _) =
+        mesh_accelerations(isolated_settings, initial_conditions.clone());
+    let (periodic_accelerations, _) =
+        mesh_accelerations(periodic_settings, initial_conditions.clone());
+
+    let isolated = group_forces(&initial_conditions, &isolated_accelerations);
+    let periodic = group_forces(&initial_conditions, &periodic_accelerations);
+    let magnitude = |force: [f64; 3]| force.iter().map(|x| x * x).sum::<f64>().sqrt();
+    for (isolated, periodic) in isolated.into_iter().zip(periodic) {
+        let ratio = magnitude(periodic) / magnitude(isolated);
+        assert!(
+            ratio < 5.0e-2,
+            "periodic to isolated group force ratio {}",
+            ratio
+        );
+    }
+}
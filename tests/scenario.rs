@@ -0,0 +1,198 @@
+use heavens::{Integrator, Scenario, Solver};
+
+const MINIMAL: &str = r#"
+[camera]
+eye_position = [10.0, 0.0, 10.0]
+field_of_view = 90.0
+zoom = 1.0
+
+[[components]]
+type = "massive_particle"
+position = [1.0, 0.0, 0.0]
+mass = 2.0
+"#;
+
+fn error_message(text: &str) -> String {
+    text.parse::<Scenario>().unwrap_err().to_string()
+}
+
+#[test]
+fn example_scenario_loads() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/disc.toml");
+    let scenario = Scenario::load(&path).unwrap();
+
+    assert_eq!(scenario.settings.solver, Solver::TiledDirect);
+    assert_eq!(scenario.settings.integrator, Integrator::Leapfrog);
+    assert_eq!(scenario.components.len(), 3);
+
+    let init_conditions = scenario.initial_conditions();
+    assert_eq!(init_conditions.num_massive_particles(), 16384);
+    assert_eq!(init_conditions.num_ghost_particles(), 8192);
+}
+
+#[test]
+fn solar_system_scenario_loads() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/solar_system.toml");
+    let init_conditions = Scenario::load(&path).unwrap().initial_conditions();
+    assert_eq!(init_conditions.num_massive_particles(), 9);
+
+    // The Earth is a little under 1 AU from the Sun in early January
+    let positions = init_conditions.massive_positions();
+    let distance = (0..3)
+        .map(|k| (positions[3][k] - positions[0][k]).powi(2))
+        .sum::<f32>()
+        .sqrt();
+    assert!((distance - 0.983).abs() < 1.0e-3, "{} AU", distance);
+}
+
+#[test]
+fn omitted_fields_keep_their_defaults() {
+    let scenario: Scenario = MINIMAL.parse().unwrap();
+
+    assert_eq!(scenario.seed, 0);
+    assert_eq!(scenario.settings.integrator, Integrator::Leapfrog);
+    assert_eq!(scenario.settings.tile_size, 64);
+
+    let init_conditions = scenario.initial_conditions();
+    assert_eq!(init_conditions.massive_positions(), [[1.0, 0.0, 0.0]]);
+    assert_eq!(init_conditions.massive_velocities(), [[0.0; 3]]);
+    assert_eq!(init_conditions.massive_masses(), [2.0]);
+}
+
+#[test]
+fn generators_are_reproducible_from_the_seed() {
+    let text = r#"
+seed = 7
+
+[camera]
+eye_position = [10.0, 0.0, 10.0]
+field_of_view = 90.0
+zoom = 1.0
+
+[[components]]
+type = "massive_disc"
+radius = 5.0
+disc_mass = 1.0
+num_particles = 50
+
+[[components]]
+type = "plummer_sphere"
+centre = [20.0, 0.0, 0.0]
+scale_radius = 2.0
+total_mass = 1.0
+num_particles = 30
+
+[[components]]
+type = "king_cluster"
+centre = [-20.0, 0.0, 0.0]
+king_radius = 0.5
+central_potential = 6.0
+total_mass = 0.5
+num_particles = 20
+"#;
+    let first = text.parse::<Scenario>().unwrap().initial_conditions();
+    let second = text.parse::<Scenario>().unwrap().initial_conditions();
+    let reseeded = text
+        .replace("seed = 7", "seed = 8")
+        .parse::<Scenario>()
+        .unwrap()
+        .initial_conditions();
+
+    assert_eq!(first.num_massive_particles(), 100);
+    assert_eq!(first.massive_positions(), second.massive_positions());
+    assert_ne!(first.massive_positions(), reseeded.massive_positions());
+}
+
+#[test]
+fn invalid_fields_are_reported() {
+    let message = error_message(&MINIMAL.replace("zoom = 1.0", "zoom = 1.0\nzooom = 2.0"));
+    assert!(message.contains("zooom"), "{}", message);
+
+    let message = error_message(&format!("{}\n[settings]\nsolver = \"fmm\"\n", MINIMAL));
+    assert!(message.contains("fmm"), "{}", message);
+
+    let message = error_message(&format!("{}\n[settings]\ntile_size = 100\n", MINIMAL));
+    assert!(message.contains("settings.tile_size"), "{}", message);
+
+    let message = error_message(&MINIMAL.replace("mass = 2.0", "mass = -2.0"));
+    assert!(message.contains("components[0].mass"), "{}", message);
+
+    let disc = r#"
+[[components]]
+type = "exponential_disc"
+scale_length = 1.0
+scale_height = 0.1
+disc_mass = 1.0
+toomre_q = 1.2
+halo = { mass = 5.0, scale_radius = -1.0 }
+num_particles = 10
+"#;
+    let message = error_message(&format!("{}{}", MINIMAL, disc));
+    assert!(
+        message.contains("components[1].halo.scale_radius"),
+        "{}",
+        message
+    );
+    assert!(format!("{}{}", MINIMAL, disc.replace("-1.0", "1.0"))
+        .parse::<Scenario>()
+        .is_ok());
+
+    let planet = r#"
+[[components]]
+type = "planetary_system"
+central_mass = 1.0
+bodies = [{ mass = 1.0e-3, semi_major_axis = 5.2, eccentricity = 1.2 }]
+"#;
+    let message = error_message(&format!("{}{}", MINIMAL, planet));
+    assert!(
+        message.contains("components[1].bodies[0].eccentricity"),
+        "{}",
+        message
+    );
+
+    let field = r#"
+[[components]]
+type = "ghost_field"
+orientation = { spin_axis = [1.0, 0.0, 0.0], inclination = 30.0 }
+radius = 1.0
+centre_mass = 1.0
+num_particles = 10
+"#;
+    let message = error_message(&format!("{}{}", MINIMAL, field));
+    assert!(message.contains("components[1].orientation"), "{}", message);
+    let message = error_message(&format!(
+        "{}{}",
+        MINIMAL,
+        field.replace("inclination", "spin = \"widdershins\", i")
+    ));
+    assert!(message.contains("widdershins"), "{}", message);
+
+    let message = error_message(&MINIMAL.replace("massive_particle", "black_hole"));
+    assert!(message.contains("black_hole"), "{}", message);
+}
+
+#[test]
+fn discs_are_turned_to_their_orientation() {
+    let disc = r#"
+[[components]]
+type = "massive_disc"
+orientation = { inclination = 90.0, position_angle = 90.0, spin = "anticlockwise" }
+radius = 5.0
+disc_mass = 1.0
+num_particles = 50
+"#;
+    let init_conditions = format!("{}{}", MINIMAL, disc)
+        .parse::<Scenario>()
+        .unwrap()
+        .initial_conditions();
+
+    // Edge-on with its line of nodes running east-west (along x), so in the xz plane with its normal along +y
+    for (position, velocity) in init_conditions.massive_positions()[1..]
+        .iter()
+        .zip(&init_conditions.massive_velocities()[1..])
+    {
+        assert!(position[1].abs() < 1.0e-5 && velocity[1].abs() < 1.0e-5);
+        let momentum = position[2] * velocity[0] - position[0] * velocity[2];
+        assert!(momentum > 0.0, "{}", momentum);
+    }
+}
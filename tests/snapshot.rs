@@ -0,0 +1,174 @@
+mod common;
+
+use common::camera;
+use heavens::{
+    simulate, simulate_on_cpu, CpuSimulation, NBody, Settings, Simulation, Snapshot, SnapshotFormat,
+};
+
+// Steps of a quarter keep the snapshot times exact
+fn settings() -> Settings {
+    Settings {
+        time_step: 0.25,
+        ..common::settings()
+    }
+}
+
+fn bodies() -> NBody {
+    let mut nbody = NBody::new();
+    nbody.add_massive_particle([1.0, 0.0, 0.0], [0.0, 0.5, 0.0], 1.0);
+    nbody.add_massive_particle([-1.0, 0.0, 0.0], [0.0, -0.5, 0.0], 1.0);
+    nbody.add_massive_particle([0.0, 0.0, 2.0], [0.1, 0.0, 0.0], 0.5);
+    nbody.add_ghost_particle([0.0, 3.0, 0.0], [0.0, 0.0, 0.2], 2.0);
+    nbody.add_ghost_particle([0.0, -3.0, 0.0], [0.0, 0.0, -0.2], 5.0);
+    nbody
+}
+
+#[test]
+fn snapshot_round_trips_the_simulation_state() {
+    let mut simulation =
+        pollster::block_on(Simulation::new_headless(settings(), camera(), bodies())).unwrap();
+    for _ in 0..5 {
+        simulation.update();
+    }
+    let snapshot = simulation.snapshot();
+    assert_eq!(snapshot.step, 5);
+    assert_eq!(snapshot.time, 1.25);
+
+    let mut bytes = Vec::new();
+    snapshot.write(&mut bytes).unwrap();
+    let restored = Snapshot::read(&mut bytes.as_slice()).unwrap();
+    let state = simulation.read_state();
+
+    assert_eq!(restored.step, 5);
+    assert_eq!(restored.time, 1.25);
+    assert_eq!(
+        restored.state.massive_positions(),
+        state.massive_positions()
+    );
+    assert_eq!(
+        restored.state.massive_velocities(),
+        state.massive_velocities()
+    );
+    assert_eq!(restored.state.massive_masses(), state.massive_masses());
+    assert_eq!(restored.state.ghost_positions(), state.ghost_positions());
+    assert_eq!(restored.state.ghost_velocities(), state.ghost_velocities());
+    assert_eq!(restored.state.ghost_kinds(), [2.0, 5.0]);
+    assert_ne!(
+        restored.state.massive_positions(),
+        bodies().massive_positions()
+    );
+
+    assert!(Snapshot::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    assert!(Snapshot::read(&mut &b"NOPE"[..]).is_err());
+}
+
+#[test]
+fn simulate_writes_numbered_snapshots() {
+    let output_dir = std::env::temp_dir().join(format!("heavens-snapshots-{}", std::process::id()));
+    pollster::block_on(simulate(settings(), camera(), bodies(), 7, 3, &output_dir)).unwrap();
+
+    let mut names = std::fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "snapshot_000000.hvs",
+            "snapshot_000001.hvs",
+            "snapshot_000002.hvs"
+        ]
+    );
+
+    let steps = names
+        .iter()
+        .map(|name| Snapshot::load(&output_dir.join(name)).unwrap().step)
+        .collect::<Vec<_>>();
+    assert_eq!(steps, [0, 3, 6]);
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn simulate_on_cpu_writes_the_cpu_state() {
+    let output_dir =
+        std::env::temp_dir().join(format!("heavens-cpu-snapshots-{}", std::process::id()));
+    simulate_on_cpu(settings(), bodies(), 7, 3, &output_dir).unwrap();
+
+    let mut simulation = CpuSimulation::new(settings(), bodies());
+    for _ in 0..6 {
+        simulation.update();
+    }
+    let snapshot = Snapshot::load(&output_dir.join("snapshot_000002.hvs")).unwrap();
+    assert_eq!(snapshot.step, 6);
+    assert_eq!(snapshot.time, 1.5);
+    assert_eq!(
+        snapshot.state.massive_positions(),
+        simulation.state().massive_positions()
+    );
+    assert_eq!(
+        snapshot.state.ghost_velocities(),
+        simulation.state().ghost_velocities()
+    );
+    assert!(!output_dir.join("snapshot_000003.hvs").exists());
+
+    std::fs::remove_dir_all(&output_dir).unwrap();
+}
+
+#[test]
+fn zero_snapshot_interval_is_rejected() {
+    let output_dir =
+        std::env::temp_dir().join(format!("heavens-zero-interval-{}", std::process::id()));
+    assert!(simulate_on_cpu(settings(), bodies(), 7, 0, &output_dir).is_err());
+    assert!(
+        pollster::block_on(simulate(settings(), camera(), bodies(), 7, 0, &output_dir)).is_err()
+    );
+    assert!(!output_dir.exists());
+}
+
+#[test]
+fn checkpoint_converts_to_a_snapshot() {
+    let mut simulation =
+        pollster::block_on(Simulation::new_headless(settings(), camera(), bodies())).unwrap();
+    for _ in 0..4 {
+        simulation.update();
+    }
+    let state = simulation.read_state();
+
+    let dir = std::env::temp_dir().join(format!("heavens-convert-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (input, output) = (dir.join("run.hvc"), dir.join("run.hvs"));
+    simulation.checkpoint().save(&input).unwrap();
+    drop(simulation);
+
+    assert_eq!(
+        SnapshotFormat::from_path(&input),
+        Some(SnapshotFormat::Checkpoint)
+    );
+    assert_eq!(
+        SnapshotFormat::from_path(&output),
+        Some(SnapshotFormat::Heavens)
+    );
+    assert_eq!(SnapshotFormat::from_path(&dir.join("run.txt")), None);
+
+    let snapshot = SnapshotFormat::Checkpoint.load(&input).unwrap();
+    SnapshotFormat::Heavens.save(&snapshot, &output).unwrap();
+    assert!(SnapshotFormat::Checkpoint.save(&snapshot, &input).is_err());
+
+    let converted = Snapshot::load(&output).unwrap();
+    assert_eq!(converted.step, 4);
+    assert_eq!(
+        converted.state.massive_positions(),
+        state.massive_positions()
+    );
+    assert_eq!(
+        converted.state.massive_velocities(),
+        state.massive_velocities()
+    );
+    assert_eq!(converted.state.massive_masses(), state.massive_masses());
+    assert_eq!(converted.state.ghost_positions(), state.ghost_positions());
+    assert_eq!(converted.state.ghost_kinds(), state.ghost_kinds());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::{assert_same_state, position, velocity};
+use std::{io::Cursor, path::Path};
+
+use heavens::NBody;
+
+// Every fixture holds three particles as NumPy and pandas would write them: particle `i` sits at
+// [i, i + 0.5, -i] moving at [i / 4, -0.5, 1], the first two are massive (masses 1 and 2) and the last is a
+// ghost of kind 3.
+//   particles.csv: `DataFrame.to_csv` output, index column included
+//   particles_fortran_big_endian.npy: a Fortran-ordered big-endian float64 table
+//   particles_compressed.npz: `numpy.savez_compressed` of float64 arrays
+const CSV: &str = "tests/fixtures/particles.csv";
+const NPY: &str = "tests/fixtures/particles_fortran_big_endian.npy";
+const NPZ: &str = "tests/fixtures/particles_compressed.npz";
+
+fn bodies() -> NBody {
+    let mut nbody = NBody::new();
+    nbody.add_massive_particle([1.0e-7, -3.5e12, 0.1], [0.2, 0.3, -0.4], 1.0 / 3.0);
+    nbody.add_massive_particle([1.0, 2.0, 3.0], [0.0; 3], 7.0);
+    nbody.add_ghost_particle([-1.0, 0.0, f32::MAX], [1.0e-30, 0.0, 0.0], 2.0);
+    nbody
+}
+
+#[test]
+fn fixtures_load_massive_and_ghost_particles() {
+    for nbody in [
+        NBody::load_csv(Path::new(CSV)).unwrap(),
+        NBody::load_npy(Path::new(NPY)).unwrap(),
+        NBody::load_npz(Path::new(NPZ)).unwrap(),
+    ] {
+        assert_eq!(nbody.massive_positions(), [position(0), position(1)]);
+        assert_eq!(nbody.massive_velocities(), [velocity(0), velocity(1)]);
+        assert_eq!(nbody.massive_masses(), [1.0, 2.0]);
+        assert_eq!(nbody.ghost_positions(), [position(2)]);
+        assert_eq!(nbody.ghost_velocities(), [velocity(2)]);
+        assert_eq!(nbody.ghost_kinds(), [3.0]);
+    }
+}
+
+#[test]
+fn every_format_round_trips_exactly() {
+    let nbody = bodies();
+
+    let mut csv = Vec::new();
+    nbody.write_csv(&mut csv).unwrap();
+    assert!(csv.starts_with(b"x,y,z,vx,vy,vz,m,kind\n"));
+    assert_same_state(&NBody::read_csv(&mut csv.as_slice()).unwrap(), &nbody);
+
+    let mut npy = Vec::new();
+    nbody.write_npy(&mut npy).unwrap();
+    let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+    let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+    assert!(npy.starts_with(b"\x93NUMPY\x01\x00"));
+    assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (3, 8), }"));
+    assert!(header.ends_with('\n'));
+    assert_eq!((10 + header_len) % 64, 0);
+    assert_eq!(npy.len(), 10 + header_len + 3 * 8 * 4);
+    assert_same_state(&NBody::read_npy(&mut npy.as_slice()).unwrap(), &nbody);
+
+    let mut npz = Cursor::new(Vec::new());
+    nbody.write_npz(&mut npz).unwrap();
+    npz.set_position(0);
+    assert_same_state(&NBody::read_npz(npz).unwrap(), &nbody);
+}
+
+#[test]
+fn malformed_tables_are_rejected() {
+    let error = NBody::read_csv(
+        &mut "x,y,z,vx,vy,vz,m,kind\n0,0,0,0,0,0,1,0\n0,0,zero,0,0,0,1,0\n".as_bytes(),
+    )
+    .unwrap_err();
+    assert_eq!(error.to_string(), "line 3: invalid number 'zero'");
+
+    assert!(NBody::read_csv(&mut "x,y,z,vx,vy,vz,m\n0,0,0,0,0,0,1\n".as_bytes()).is_err());
+    assert!(NBody::read_csv(&mut "x,y,z,vx,vy,vz,m,kind\n0,0,0,0,0,0,-1,0\n".as_bytes()).is_err());
+
+    // A table of six columns is not a particle table
+    let mut npy = Vec::new();
+    bodies().write_npy(&mut npy).unwrap();
+    let npy = String::from_utf8_lossy(&npy).replace("(3, 8)", "(4, 6)");
+    assert!(NBody::read_npy(&mut npy.as_bytes()).is_err());
+}
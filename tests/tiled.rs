@@ -0,0 +1,59 @@
+mod common;
+
+use common::{camera, random_bodies, relative_error};
+use heavens::{Integrator, NBody, Settings, Simulation, Solver};
+
+const NUM_MASSIVE_BODIES: usize = 192; // Leaves the last 128-body tile partially filled
+const NUM_GHOSTS: usize = 64;
+const TILE_SIZE: u32 = 128;
+
+fn settings(solver: Solver) -> Settings {
+    Settings {
+        time_step: 1.0,
+        integrator: Integrator::Euler,
+        solver,
+        tile_size: TILE_SIZE,
+        ..common::settings()
+    }
+}
+
+// Accelerations recovered from a single unit Euler kick from rest
+fn accelerations(solver: Solver) -> NBody {
+    let mut simulation = pollster::block_on(Simulation::new_headless(
+        settings(solver),
+        camera(),
+        random_bodies(3, NUM_MASSIVE_BODIES, NUM_GHOSTS, 0.0),
+    ))
+    .unwrap();
+    simulation.update();
+
+    simulation.read_state()
+}
+
+#[test]
+fn tiled_massive_forces_match_direct_sum() {
+    let direct = accelerations(Solver::Direct);
+    let tiled = accelerations(Solver::TiledDirect);
+
+    let max_error = direct
+        .massive_velocities()
+        .iter()
+        .zip(tiled.massive_velocities())
+        .map(|(a, b)| relative_error(*a, *b))
+        .fold(0.0, f64::max);
+    assert!(max_error < 1.0e-5, "max relative error {}", max_error);
+}
+
+#[test]
+fn tiled_ghost_forces_match_direct_sum() {
+    let direct = accelerations(Solver::Direct);
+    let tiled = accelerations(Solver::TiledDirect);
+
+    let max_error = direct
+        .ghost_velocities()
+        .iter()
+        .zip(tiled.ghost_velocities())
+        .map(|(a, b)| relative_error(*a, *b))
+        .fold(0.0, f64::max);
+    assert!(max_error < 1.0e-5, "max relative error {}", max_error);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{assert_same_state, position, velocity};
+use std::path::Path;
+
+use heavens::{Endianness, Snapshot, TipsyGas};
+
+// A big-endian file at time 0.75 holding two massless gas particles (metallicities 7 and 2), two dark
+// matter particles (masses 1 and 2) and one star (mass 0.5). Particle `i` sits at [i, i + 0.5, -i] moving
+// at [i / 4, -0.5, 1].
+const STANDARD: &str = "tests/fixtures/standard.tipsy";
+
+#[test]
+fn fixture_maps_dark_and_star_to_massive_and_gas_to_ghosts() {
+    let snapshot = Snapshot::load_tipsy(Path::new(STANDARD), TipsyGas::Ghosts).unwrap();
+    let state = &snapshot.state;
+
+    assert_eq!(snapshot.time, 0.75);
+    assert_eq!(state.massive_masses(), [1.0, 2.0, 0.5]);
+    assert_eq!(
+        state.massive_positions(),
+        (2..5).map(position).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        state.massive_velocities(),
+        (2..5).map(velocity).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        state.ghost_positions(),
+        (0..2).map(position).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        state.ghost_velocities(),
+        (0..2).map(velocity).collect::<Vec<_>>()
+    );
+    assert_eq!(state.ghost_kinds(), [7.0, 2.0]);
+
+    let state = Snapshot::load_tipsy(Path::new(STANDARD), TipsyGas::Ignored)
+        .unwrap()
+        .state;
+    assert_eq!(state.num_massive_particles(), 3);
+    assert_eq!(state.num_ghost_particles(), 0);
+
+    // The massless gas cannot be massive
+    assert!(Snapshot::load_tipsy(Path::new(STANDARD), TipsyGas::Massive).is_err());
+}
+
+#[test]
+fn both_endiannesses_round_trip() {
+    let fixture = Snapshot::load_tipsy(Path::new(STANDARD), TipsyGas::Ghosts).unwrap();
+
+    let mut little = Vec::new();
+    fixture
+        .write_tipsy(&mut little, Endianness::Little)
+        .unwrap();
+    let mut big = Vec::new();
+    fixture.write_tipsy(&mut big, Endianness::Big).unwrap();
+    assert_ne!(little, big);
+
+    for mut bytes in [little, big] {
+        let read = Snapshot::read_tipsy(&mut bytes.as_slice(), TipsyGas::Ghosts).unwrap();
+        assert_eq!(read.time, fixture.time);
+        assert_same_state(&read.state, &fixture.state);
+
+        // Neither endianness describes a truncated file
+        bytes.truncate(bytes.len() - 4);
+        assert!(Snapshot::read_tipsy(&mut bytes.as_slice(), TipsyGas::Ghosts).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use heavens::NBody;
+
+fn pieces() -> NBody {
+    let mut nbody = NBody::new();
+    nbody.add_massive_particle([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], 3.0);
+    nbody.add_massive_particle([-2.0, 1.0, 0.5], [0.5, -1.0, 0.0], 1.0);
+    nbody.add_massive_particle([0.0, -3.0, 2.0], [0.0, 0.0, 2.0], 2.0);
+    nbody.add_ghost_particle([4.0, 4.0, 0.0], [1.0, 0.0, 0.0], 5.0);
+    nbody
+}
+
+fn assert_close(found: [f32; 3], expected: [f32; 3]) {
+    let close = (0..3).all(|k| (found[k] - expected[k]).abs() < 1.0e-5);
+    assert!(close, "found {:?} but expected {:?}", found, expected);
+}
+
+#[test]
+fn transforms_move_every_particle() {
+    let mut nbody = pieces();
+    nbody.translate([1.0, 2.0, 3.0]);
+    nbody.boost([0.0, -1.0, 0.0]);
+    assert_close(nbody.massive_positions()[0], [2.0, 2.0, 3.0]);
+    assert_close(nbody.massive_velocities()[0], [0.0, 0.0, 0.0]);
+    assert_close(nbody.ghost_positions()[0], [5.0, 6.0, 3.0]);
+    assert_close(nbody.ghost_velocities()[0], [1.0, -1.0, 0.0]);
+
+    // A quarter turn anticlockwise about +z
+    let mut nbody = pieces();
+    nbody.rotate([0.0, 0.0, 2.0], std::f32::consts::FRAC_PI_2);
+    assert_close(nbody.massive_positions()[0], [0.0, 1.0, 0.0]);
+    assert_close(nbody.massive_velocities()[0], [-1.0, 0.0, 0.0]);
+    assert_close(nbody.massive_positions()[2], [3.0, 0.0, 2.0]);
+    assert_close(nbody.ghost_positions()[0], [-4.0, 4.0, 0.0]);
+
+    let mut nbody = pieces();
+    nbody.scale_masses(2.0);
+    nbody.scale_lengths(0.5);
+    nbody.scale_velocities(2.0);
+    assert_eq!(nbody.massive_masses(), [6.0, 2.0, 4.0]);
+    assert_close(nbody.massive_positions()[1], [-1.0, 0.5, 0.25]);
+    assert_close(nbody.massive_velocities()[1], [1.0, -2.0, 0.0]);
+    assert_close(nbody.ghost_positions()[0], [2.0, 2.0, 0.0]);
+    assert_close(nbody.ghost_velocities()[0], [2.0, 0.0, 0.0]);
+    assert_eq!(nbody.ghost_kinds(), [5.0]);
+}
+
+#[test]
+fn systems_compose_from_pieces() {
+    let mut nbody = pieces();
+    let mut other = pieces();
+    other.translate([10.0, 0.0, 0.0]);
+    nbody.merge(&other);
+    assert_eq!(nbody.num_massive_particles(), 6);
+    assert_eq!(nbody.num_ghost_particles(), 2);
+    assert_eq!(nbody.total_mass(), 12.0);
+    assert_close(nbody.massive_positions()[3], [11.0, 0.0, 0.0]);
+
+    // The heavy particles on the +x side, without the ghosts
+    let subset = nbody.subset(
+        |position, _, mass| position[0] > 0.0 && mass > 1.5,
+        |_, _, _| false,
+    );
+    assert_eq!(subset.massive_masses(), [3.0, 3.0, 2.0]);
+    assert_eq!(subset.num_ghost_particles(), 0);
+    assert_close(subset.massive_positions()[2], [10.0, -3.0, 2.0]);
+
+    // Mass-weighted, ghosts aside
+    let nbody = pieces();
+    assert_close(nbody.centre_of_mass(), [1.0 / 6.0, -5.0 / 6.0, 4.5 / 6.0]);
+    assert_close(
+        nbody.centre_of_mass_velocity(),
+        [0.5 / 6.0, 2.0 / 6.0, 4.0 / 6.0],
+    );
+
+    let mut nbody = pieces();
+    nbody.translate([100.0, -50.0, 7.0]);
+    nbody.boost([3.0, 0.0, -1.0]);
+    nbody.to_centre_of_mass_frame();
+    assert_close(nbody.centre_of_mass(), [0.0; 3]);
+    assert_close(nbody.centre_of_mass_velocity(), [0.0; 3]);
+    // The ghosts shifted along with the rest
+    assert_close(
+        nbody.ghost_positions()[0],
+        [4.0 - 1.0 / 6.0, 4.0 + 5.0 / 6.0, -4.5 / 6.0],
+    );
+}
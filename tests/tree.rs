@@ -0,0 +1,123 @@
+mod common;
+
+use common::{camera, relative_error};
+use heavens::{Integrator, NBody, Settings, Simulation, Solver};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const NUM_FIELD_BODIES: usize = 768;
+const NUM_CLUMP_BODIES: usize = 256;
+const NUM_GHOSTS: usize = 128;
+
+fn settings(solver: Solver, opening_angle: f32) -> Settings {
+    Settings {
+        time_step: 1.0,
+        opening_angle,
+        integrator: Integrator::Euler,
+        solver,
+        ..common::settings()
+    }
+}
+
+fn random_point(rng: &mut StdRng, centre: [f32; 3], radius: f32) -> [f32; 3] {
+    loop {
+        let p: [f32; 3] = [
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        ];
+        if p.iter().map(|x| x * x).sum::<f32>() <= 1.0 {
+            return [
+                centre[0] + radius * p[0],
+                centre[1] + radius * p[1],
+                centre[2] + radius * p[2],
+            ];
+        }
+    }
+}
+
+// A uniform sphere with a dense off-centre clump (including coincident bodies), at rest
+fn clustered_bodies() -> NBody {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut nbody = NBody::new();
+
+    for _ in 0..NUM_FIELD_BODIES {
+        let position = random_point(&mut rng, [0.0; 3], 10.0);
+        nbody.add_massive_particle(position, [0.0; 3], rng.gen_range(0.5..1.5));
+    }
+    for n in 0..NUM_CLUMP_BODIES {
+        let position = if n < 4 {
+            [3.0, 2.0, -1.0]
+        } else {
+            random_point(&mut rng, [3.0, 2.0, -1.0], 0.5)
+        };
+        nbody.add_massive_particle(position, [0.0; 3], rng.gen_range(0.5..1.5));
+    }
+    for _ in 0..NUM_GHOSTS {
+        let position = random_point(&mut rng, [0.0; 3], 15.0);
+        nbody.add_ghost_particle(position, [0.0; 3], 1.0);
+    }
+
+    nbody
+}
+
+// Accelerations recovered from a single unit Euler kick from rest
+fn accelerations(solver: Solver, opening_angle: f32) -> NBody {
+    let mut simulation = pollster::block_on(Simulation::new_headless(
+        settings(solver, opening_angle),
+        camera(),
+        clustered_bodies(),
+    ))
+    .unwrap();
+    simulation.update();
+
+    simulation.read_state()
+}
+
+// Relative acceleration errors of the tree solver against the direct sum, massive bodies then ghosts
+fn relative_errors(opening_angle: f32) -> Vec<f64> {
+    let direct = accelerations(Solver::Direct, opening_angle);
+    let tree = accelerations(Solver::Tree, opening_angle);
+
+    let massive_errors = direct
+        .massive_velocities()
+        .iter()
+        .zip(tree.massive_velocities())
+        .map(|(a, b)| relative_error(*a, *b));
+    let ghost_errors = direct
+        .ghost_velocities()
+        .iter()
+        .zip(tree.ghost_velocities())
+        .map(|(a, b)| relative_error(*a, *b));
+
+    massive_errors.chain(ghost_errors).collect()
+}
+
+#[test]
+fn tree_matches_direct_sum_when_every_node_is_opened() {
+    let errors = relative_errors(0.0);
+    let max_error = errors.iter().copied().fold(0.0, f64::max);
+    assert!(max_error < 1.0e-4, "max relative error {}", max_error);
+}
+
+#[test]
+fn tree_approximates_direct_sum() {
+    let mut errors = relative_errors(0.5);
+    errors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let rms_error = (errors.iter().map(|e| e * e).sum::<f64>() / errors.len() as f64).sqrt();
+    let percentile_99 = errors[errors.len() * 99 / 100];
+    assert!(rms_error < 1.0e-2, "rms relative error {}", rms_error);
+    assert!(
+        percentile_99 < 3.0e-2,
+        "99th percentile relative error {}",
+        percentile_99
+    );
+}
+
+#[test]
+fn tree_error_shrinks_with_opening_angle() {
+    let mean = |errors: Vec<f64>| errors.iter().sum::<f64>() / errors.len() as f64;
+    let coarse = mean(relative_errors(1.0));
+    let fine = mean(relative_errors(0.3));
+    assert!(fine < coarse, "theta 0.3: {}, theta 1.0: {}", fine, coarse);
+}
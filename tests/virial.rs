@@ -0,0 +1,139 @@
+mod common;
+
+use common::camera;
+use heavens::{CpuSimulation, Integrator, NBody, Settings, Simulation};
+use rand::{rngs::StdRng, SeedableRng};
+
+const GRAV_CONST: f32 = 2.0;
+const SMOOTHING_LENGTH: f32 = 0.05;
+
+fn plummer_sphere(seed: u64, num_particles: usize) -> NBody {
+    let mut nbody = NBody::new();
+    nbody.add_plummer_sphere(
+        &mut StdRng::seed_from_u64(seed),
+        GRAV_CONST,
+        [5.0, 0.0, -2.0],
+        [1.0, -1.0, 0.0],
+        1.0,
+        3.0,
+        num_particles,
+    );
+    nbody.add_ghost_particle([5.0, 1.0, -2.0], [1.0, 0.0, 0.0], 1.0);
+    nbody
+}
+
+#[test]
+fn energies_use_the_softened_potential() {
+    let mut nbody = NBody::new();
+    nbody.add_massive_particle([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], 1.0);
+    nbody.add_massive_particle([3.0, 0.0, 0.0], [0.0, 2.0, 0.0], 3.0);
+    nbody.add_ghost_particle([1.0, 0.0, 0.0], [100.0, 0.0, 0.0], 1.0);
+
+    // Softened by 4, the pair sits at an effective distance of 5
+    let potential = nbody.potential_energy(GRAV_CONST, 4.0);
+    assert!((potential - -GRAV_CONST as f64 * 3.0 / 5.0).abs() < 1.0e-9);
+    // Relative speed^2 of 5 with a reduced mass of 3/4
+    assert!((nbody.kinetic_energy() - 0.5 * 0.75 * 5.0).abs() < 1.0e-6);
+
+    // A Plummer sphere starts in equilibrium
+    let ratio = plummer_sphere(25, 1000).virial_ratio(GRAV_CONST, SMOOTHING_LENGTH);
+    assert!((ratio - 0.5).abs() < 0.05, "virial ratio {}", ratio);
+}
+
+#[test]
+fn velocities_rescale_to_the_virial_ratio() {
+    let mut nbody = plummer_sphere(25, 500);
+    let frame_velocity = nbody.centre_of_mass_velocity();
+    let positions = nbody.massive_positions().to_vec();
+
+    nbody.virialise(GRAV_CONST, SMOOTHING_LENGTH, 0.3);
+    let ratio = nbody.virial_ratio(GRAV_CONST, SMOOTHING_LENGTH);
+    assert!((ratio - 0.3).abs() < 1.0e-5, "virial ratio {}", ratio);
+
+    // Only the motion about the centre of mass changes, the ghost's along with the rest
+    assert_eq!(nbody.massive_positions(), positions);
+    let velocity = nbody.centre_of_mass_velocity();
+    assert!((0..3).all(|k| (velocity[k] - frame_velocity[k]).abs() < 1.0e-5));
+    let ghost = nbody.ghost_velocities()[0];
+    assert!(
+        (ghost[0] - 1.0).abs() < 1.0e-5
+            && (ghost[1] + 1.0 - 0.3f32.sqrt() / 0.5f32.sqrt()).abs() < 0.1
+    );
+}
+
+fn settings() -> Settings {
+    Settings {
+        gravitational_constant: GRAV_CONST,
+        smoothing_length: SMOOTHING_LENGTH,
+        integrator: Integrator::Leapfrog,
+        ..common::settings()
+    }
+}
+
+fn assert_moves_as_a_whole(nbody: &NBody) {
+    let velocity = nbody.centre_of_mass_velocity();
+    assert!(
+        (0..3).all(|k| (velocity[k] - [1.0, -1.0, 0.0][k]).abs() < 1.0e-3),
+        "centre of mass velocity {:?}",
+        velocity
+    );
+}
+
+// The state after relaxing and then simulating, with the step count and time, on a simulation of its own (only one
+// device at a time)
+fn relax_and_update(
+    nbody: NBody,
+    num_relaxation_steps: u32,
+    damping: f32,
+    num_steps: usize,
+) -> (NBody, u64, f64) {
+    let mut simulation =
+        pollster::block_on(Simulation::new_headless(settings(), camera(), nbody)).unwrap();
+
+    simulation.relax(num_relaxation_steps, damping);
+    (0..num_steps).for_each(|_| simulation.update());
+    (
+        simulation.read_state(),
+        simulation.steps(),
+        simulation.time(),
+    )
+}
+
+#[test]
+fn cold_system_is_left_as_it_is() {
+    let mut nbody = plummer_sphere(25, 100);
+    nbody.scale_velocities(0.0);
+    nbody.boost([1.0, 0.0, 0.0]);
+    let velocities = nbody.massive_velocities().to_vec();
+
+    nbody.virialise(GRAV_CONST, SMOOTHING_LENGTH, 0.5);
+    assert_eq!(nbody.massive_velocities(), velocities);
+}
+
+#[test]
+fn relaxation_damps_the_motion_before_the_run() {
+    // Too hot to stay bound without the damping
+    let mut nbody = plummer_sphere(25, 256);
+    nbody.virialise(GRAV_CONST, SMOOTHING_LENGTH, 2.0);
+
+    let (relaxed, steps, time) = relax_and_update(nbody.clone(), 200, 2.0, 0);
+    assert_eq!((steps, time), (0, 0.0));
+    let ratio = relaxed.virial_ratio(GRAV_CONST, SMOOTHING_LENGTH);
+    assert!(ratio < 1.0, "virial ratio {}", ratio);
+    // Only the motion about the centre of mass is damped, on the CPU as well
+    assert_moves_as_a_whole(&relaxed);
+    let mut cpu_simulation = CpuSimulation::new(settings(), nbody.clone());
+    cpu_simulation.relax(200, 2.0);
+    assert_moves_as_a_whole(cpu_simulation.state());
+    let ratio = cpu_simulation
+        .state()
+        .virial_ratio(GRAV_CONST, SMOOTHING_LENGTH);
+    assert!(ratio < 1.0, "virial ratio {}", ratio);
+
+    // Without damping, relaxing is the same as simulating
+    let (undamped, _, _) = relax_and_update(nbody.clone(), 20, 0.0, 0);
+    let (plain, steps, _) = relax_and_update(nbody, 0, 0.0, 20);
+    assert_eq!(steps, 20);
+    assert_eq!(undamped.massive_positions(), plain.massive_positions());
+    assert_eq!(undamped.massive_velocities(), plain.massive_velocities());
+}